@@ -1,15 +1,85 @@
 package db
 
 import (
+	"fmt"
+	"os"
+
 	"models"
 
 	"gorm.io/gorm"
 )
 
+// Adds the product key to a products table created before it existed. AutoMigrate can't add
+// a NOT NULL column without a default to a populated table, so the column is added as nullable,
+// backfilled the same way the scrapers derive the key, and only then made NOT NULL
+var productKeyBackfill = []string{
+	`ALTER TABLE products ADD COLUMN IF NOT EXISTS productkey VARCHAR(750)`,
+	// Albert Heijn: the AH id in the URL, e.g. wi123456
+	`UPDATE products SET productkey = 'wi' || substring(url from '/wi([0-9]+)')
+	WHERE productkey IS NULL AND store = 'Albert Heijn' AND url ~ '/wi[0-9]+'`,
+	// Jumbo: the SKU at the end of the URL path, e.g. 67649PAK
+	`UPDATE products SET productkey = substring(regexp_replace(url, '[?#].*$', '') from '-([0-9]+[A-Z]*)/?$')
+	WHERE productkey IS NULL AND store = 'Jumbo' AND regexp_replace(url, '[?#].*$', '') ~ '-[0-9]+[A-Z]*/?$'`,
+	// Anything else: the URL without query, fragment and trailing slash
+	`UPDATE products SET productkey = rtrim(regexp_replace(url, '[?#].*$', ''), '/')
+	WHERE productkey IS NULL`,
+}
+
+// Shopping lists that have an older row of a product get the most recent row instead, the older
+// rows are deleted next. A list that had several rows of the same product keeps it once
+var shoppingListRepoint = []string{
+	`INSERT INTO products_shoppinglists (product_id, shopping_list_id)
+	SELECT DISTINCT newest.id, ps.shopping_list_id
+	FROM products_shoppinglists ps
+	JOIN products p ON p.id = ps.product_id
+	JOIN (SELECT store, productkey, MAX(id) AS id FROM products GROUP BY store, productkey) newest
+		ON newest.store = p.store AND newest.productkey = p.productkey
+	WHERE p.id <> newest.id
+	ON CONFLICT DO NOTHING`,
+	`DELETE FROM products_shoppinglists ps USING products p, products newer
+	WHERE ps.product_id = p.id AND p.store = newer.store AND p.productkey = newer.productkey AND p.id < newer.id`,
+}
+
+var productKeyDedupe = []string{
+	// Older runs inserted a new row per scrape, keep the most recent one per key
+	`DELETE FROM products p USING products newer
+	WHERE p.store = newer.store AND p.productkey = newer.productkey AND p.id < newer.id`,
+	`ALTER TABLE products ALTER COLUMN productkey SET NOT NULL`,
+	`CREATE UNIQUE INDEX IF NOT EXISTS idx_product_key ON products (store, productkey)`,
+}
+
+func migrateProductKey(db *gorm.DB) error {
+	if !db.Migrator().HasTable(&models.Product{}) || db.Migrator().HasColumn(&models.Product{}, "ProductKey") {
+		return nil
+	}
+	return db.Transaction(func(tx *gorm.DB) error {
+		statements := append([]string{}, productKeyBackfill...)
+		if tx.Migrator().HasTable("products_shoppinglists") {
+			statements = append(statements, shoppingListRepoint...)
+		}
+		statements = append(statements, productKeyDedupe...)
+
+		for _, statement := range statements {
+			if err := tx.Exec(statement).Error; err != nil {
+				return err
+			}
+		}
+		return nil
+	})
+}
+
 func Migrate(db *gorm.DB) {
+	if err := migrateProductKey(db); err != nil {
+		fmt.Fprintf(os.Stderr, "Unable to migrate product keys: %v\n", err)
+		os.Exit(1)
+	}
+
 	db.AutoMigrate(
 		&models.User{}, 
 		&models.ShoppingList{},
 		&models.Product{},
+		&models.ProductMatch{},
+		&models.ScrapeAnomaly{},
+		&models.ScrapeRun{},
 	)
 }
\ No newline at end of file
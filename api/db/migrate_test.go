@@ -0,0 +1,94 @@
+package db
+
+import (
+	"os"
+	"testing"
+
+	"models"
+
+	"gorm.io/driver/postgres"
+	"gorm.io/gorm"
+)
+
+// Runs against the database in TEST_CONN_URL, in an empty schema inside a transaction that is rolled back
+func testDb(t *testing.T) *gorm.DB {
+	connString, found := os.LookupEnv("TEST_CONN_URL")
+	if !found {
+		t.Skip("TEST_CONN_URL not set")
+	}
+	db, err := gorm.Open(postgres.Open(connString), &gorm.Config{})
+	if err != nil {
+		t.Fatalf("Unable to connect to database: %v", err)
+	}
+	tx := db.Begin()
+	t.Cleanup(func() { tx.Rollback() })
+	for _, statement := range []string{`CREATE SCHEMA migrate_test`, `SET LOCAL search_path TO migrate_test`} {
+		if err := tx.Exec(statement).Error; err != nil {
+			t.Fatalf("Unable to create the test schema: %v", err)
+		}
+	}
+	return tx
+}
+
+func TestMigrateProductKeyRepointsShoppingLists(t *testing.T) {
+	db := testDb(t)
+
+	// A products table from before the product key, with two rows of the same Jumbo product
+	if err := db.AutoMigrate(&models.User{}, &models.ShoppingList{}, &models.Product{}); err != nil {
+		t.Fatal(err)
+	}
+	db.Exec(`DROP INDEX IF EXISTS idx_product_key`)
+	db.Exec(`ALTER TABLE products DROP COLUMN productkey`)
+
+	url := "https://www.jumbo.com/producten/jumbo-halfvolle-melk-1l-67649PAK"
+	older := map[string]any{"name": "Melk", "store": "Jumbo", "url": url, "price": "1.09"}
+	newer := map[string]any{"name": "Melk", "store": "Jumbo", "url": url + "?ref=list", "price": "1.19"}
+	other := map[string]any{"name": "Kaas", "store": "Jumbo", "url": "https://www.jumbo.com/producten/jumbo-kaas-12345PAK", "price": "4.99"}
+	for _, product := range []map[string]any{older, newer, other} {
+		if err := db.Table("products").Create(product).Error; err != nil {
+			t.Fatal(err)
+		}
+	}
+	var ids []uint
+	db.Table("products").Order("id").Pluck("id", &ids)
+
+	user := models.User{FirstName: "Test", LastName: "User", Email: "test@example.com", Password: "secret"}
+	db.Create(&user)
+	// One list has the older row, the other has both rows of the product
+	onlyOlder := models.ShoppingList{UserID: user.ID, Name: "Boodschappen"}
+	both := models.ShoppingList{UserID: user.ID, Name: "Weekend"}
+	db.Create(&onlyOlder)
+	db.Create(&both)
+	db.Exec(`INSERT INTO products_shoppinglists (product_id, shopping_list_id) VALUES (?, ?), (?, ?), (?, ?), (?, ?)`,
+		ids[0], onlyOlder.ID, ids[2], onlyOlder.ID, ids[0], both.ID, ids[1], both.ID)
+
+	if err := migrateProductKey(db); err != nil {
+		t.Fatalf("migration failed: %v", err)
+	}
+
+	var remaining []uint
+	db.Table("products").Order("id").Pluck("id", &remaining)
+	if len(remaining) != 2 || remaining[0] != ids[1] || remaining[1] != ids[2] {
+		t.Fatalf("expected products %v and %v to remain, got %v", ids[1], ids[2], remaining)
+	}
+
+	type listProduct struct {
+		ProductID      uint
+		ShoppingListID uint
+	}
+	var rows []listProduct
+	db.Table("products_shoppinglists").Order("shopping_list_id, product_id").Find(&rows)
+	expected := []listProduct{
+		{ProductID: ids[1], ShoppingListID: onlyOlder.ID},
+		{ProductID: ids[2], ShoppingListID: onlyOlder.ID},
+		{ProductID: ids[1], ShoppingListID: both.ID},
+	}
+	if len(rows) != len(expected) {
+		t.Fatalf("expected shopping list rows %v, got %v", expected, rows)
+	}
+	for i := range expected {
+		if rows[i] != expected[i] {
+			t.Fatalf("expected shopping list rows %v, got %v", expected, rows)
+		}
+	}
+}
@@ -0,0 +1,95 @@
+package models
+
+import (
+	"database/sql/driver"
+	"fmt"
+	"strconv"
+	"strings"
+)
+
+// Amount of money in whole cents, read from and written to a numeric(10,2) column.
+// Avoids float rounding errors when prices are added up.
+type Cents int64
+
+// Parse a decimal string like "12.34", "-0.5" or "3" into cents.
+// Digits after the second decimal are rounded half up.
+func ParseCents(s string) (Cents, error) {
+	s = strings.TrimSpace(s)
+	negative := strings.HasPrefix(s, "-")
+	s = strings.TrimLeft(s, "+-")
+
+	whole, fraction, _ := strings.Cut(s, ".")
+	if whole == "" {
+		whole = "0"
+	}
+	units, err := strconv.ParseInt(whole, 10, 64)
+	if err != nil {
+		return 0, fmt.Errorf("invalid amount %q: %w", s, err)
+	}
+
+	fraction += "000"
+	cents, err := strconv.ParseInt(fraction[:2], 10, 64)
+	if err != nil {
+		return 0, fmt.Errorf("invalid amount %q: %w", s, err)
+	}
+	if fraction[2] >= '5' {
+		cents += 1
+	}
+
+	total := units*100 + cents
+	if negative {
+		total = -total
+	}
+	return Cents(total), nil
+}
+
+// Decimal representation with two decimals, e.g. "12.34"
+func (c Cents) String() string {
+	sign := ""
+	value := int64(c)
+	if value < 0 {
+		sign = "-"
+		value = -value
+	}
+	return fmt.Sprintf("%s%d.%02d", sign, value/100, value%100)
+}
+
+func (c *Cents) Scan(src any) error {
+	switch v := src.(type) {
+	case nil:
+		*c = 0
+		return nil
+	case int64:
+		*c = Cents(v * 100)
+		return nil
+	case string:
+		parsed, err := ParseCents(v)
+		*c = parsed
+		return err
+	case []byte:
+		parsed, err := ParseCents(string(v))
+		*c = parsed
+		return err
+	case float64:
+		parsed, err := ParseCents(strconv.FormatFloat(v, 'f', -1, 64))
+		*c = parsed
+		return err
+	default:
+		return fmt.Errorf("cannot scan %T into Cents", src)
+	}
+}
+
+func (c Cents) Value() (driver.Value, error) {
+	return c.String(), nil
+}
+
+// Serialized as a JSON number with two decimals, e.g. 12.34
+func (c Cents) MarshalJSON() ([]byte, error) {
+	return []byte(c.String()), nil
+}
+
+func (c *Cents) UnmarshalJSON(data []byte) error {
+	parsed, err := ParseCents(strings.Trim(string(data), `"`))
+	*c = parsed
+	return err
+}
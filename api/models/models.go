@@ -1,22 +1,87 @@
 package models
 
-import "gorm.io/gorm"
+import (
+	"time"
+
+	"gorm.io/gorm"
+)
 
 type Product struct {
 	ID        		uint `gorm:"primarykey"`
 	Name      		string
-	Store     		string
-	Price     		float32
+	Store     		string		`gorm:"uniqueIndex:idx_product_key,priority:1"`
+	Price     		Cents	`gorm:"type:numeric(10,2)"`
+	Currency		string	`gorm:"size:3"`
 	Url       		string
+	Quantity		*string		`gorm:"size:100"`
+	UnitPrice		*Cents		`gorm:"column:unitprice;type:numeric(10,2)"`
+	UnitPriceUnit	*string		`gorm:"column:unitpriceunit;size:20"`
+	Brand			*string		`gorm:"size:255"`
+	Category		*string		`gorm:"size:500"`
+	ImageUrl		*string		`gorm:"column:imageurl;size:750"`
+	Sku				*string		`gorm:"size:100"`
+	Ean				*string		`gorm:"size:14"`
+	Available		*bool
+	PromotionType			*string		`gorm:"column:promotiontype;size:30"`
+	PromotionLabel			*string		`gorm:"column:promotionlabel;size:255"`
+	PromotionPrice			*Cents		`gorm:"column:promotionprice;type:numeric(10,2)"`
+	PromotionMinQuantity	*int		`gorm:"column:promotionminquantity"`
+	PromotionValidFrom		*time.Time	`gorm:"column:promotionvalidfrom;type:date"`
+	PromotionValidUntil		*time.Time	`gorm:"column:promotionvaliduntil;type:date"`
+	ProductKey				string		`gorm:"column:productkey;size:750;not null;uniqueIndex:idx_product_key,priority:2"`
+	Taxonomy				*string		`gorm:"size:255;index"`
+	Description				*string		`gorm:"type:text"`
+	Ingredients				*string		`gorm:"type:text"`
+	Allergens				*string		`gorm:"size:500"`
+	// Nutrition table as a JSON array of {name, per_100, per_portion}
+	Nutrition				*string		`gorm:"type:jsonb"`
+	// Last time a scrape run saw the product, products not seen by the last run are delisted
+	LastSeen				*time.Time	`gorm:"column:lastseen"`
 	ShoppingLists	[]*ShoppingList `gorm:"many2many:products_shoppinglists"`
 }
 
+// Relation between products of different stores, calculated after each scrape.
+// Relation is either "identical" or "comparable_alternative"
+type ProductMatch struct {
+	ID				uint	`gorm:"primarykey"`
+	Store			string	`gorm:"size:100"`
+	ProductKey		string	`gorm:"column:productkey;size:750"`
+	MatchStore		string	`gorm:"column:matchstore;size:100"`
+	MatchProductKey	string	`gorm:"column:matchproductkey;size:750"`
+	Relation		string	`gorm:"size:30"`
+	Confidence		float64
+	GroupKey		*string	`gorm:"column:groupkey;size:900"`
+}
+
 type ScrapeError struct {
 	ID      string `gorm:"primarykey"`
 	Scraper string
 	Message string
 }
 
+// Suspicious difference with the previous scrape of a store, the products involved weren't updated.
+// Kind is "price_change", "catalogue_drop" or "vanished_category"
+type ScrapeAnomaly struct {
+	ID			uint		`gorm:"primarykey"`
+	Store		string		`gorm:"size:100"`
+	Kind		string		`gorm:"size:30"`
+	ProductKey	*string		`gorm:"column:productkey;size:750"`
+	Category	*string		`gorm:"size:500"`
+	Message		string
+	CreatedAt	time.Time	`gorm:"column:createdat"`
+	// "open", "acknowledged" to accept it on the next run, or "released" once applied
+	Status		string		`gorm:"size:20;default:open;index"`
+}
+
+// A scrape of a single store. Runs that were held back as a whole are never finished
+type ScrapeRun struct {
+	ID			uint		`gorm:"primarykey"`
+	Store		string		`gorm:"size:100;index"`
+	StartedAt	time.Time	`gorm:"column:startedat"`
+	FinishedAt	*time.Time	`gorm:"column:finishedat"`
+	Incremental	bool		`gorm:"column:incremental;not null;default:false"`
+}
+
 type User struct {
 	gorm.Model
 	FirstName string	`gorm:"size:255;not null;unique" json:"first_name"`
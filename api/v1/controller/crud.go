@@ -18,7 +18,9 @@ func FindProductInDb(db *gorm.DB, search_text string) ([]dto.Product, error) {
 
 	var products []dto.Product
 
-	result := db.Raw(query, search_text, search_text).Find(&products)
+	// searchstr holds normalized names, the query has to be normalized the same way to match
+	search := SearchString(search_text)
+	result := db.Raw(query, search, search).Find(&products)
 	if result.Error != nil { return nil, result.Error }
 
 	return products, nil
@@ -115,7 +117,7 @@ func GetShoppingListById(db *gorm.DB, id uint64) (*dto.ShoppingList, error) {
 		return nil, errors.New("shopping list not found")
 	}
 
-	var price float32 = 0.0
+	var price models.Cents = 0
 	var products []*dto.Product
 
 	for _, dbProduct := range shoppingList.Products {
@@ -146,4 +148,31 @@ func GetShoppingListById(db *gorm.DB, id uint64) (*dto.ShoppingList, error) {
 	}
 
 	return &dtoList, nil
-}
\ No newline at end of file
+}
+
+func GetScrapeAnomalies(db *gorm.DB, status string) ([]models.ScrapeAnomaly, error) {
+	var anomalies []models.ScrapeAnomaly
+
+	query := db.Order("createdat DESC")
+	if status != "" {
+		query = query.Where("status = ?", status)
+	}
+
+	result := query.Find(&anomalies)
+	if result.Error != nil { return nil, result.Error }
+
+	return anomalies, nil
+}
+
+// Accept an anomaly, the next scrape of the store publishes the products it held back
+func AcknowledgeScrapeAnomaly(db *gorm.DB, id uint64) error {
+	result := db.Model(&models.ScrapeAnomaly{}).
+		Where("id = ? AND status = ?", id, "open").
+		Update("status", "acknowledged")
+	if result.Error != nil { return result.Error }
+
+	if result.RowsAffected == 0 {
+		return errors.New("no open anomaly with this id")
+	}
+	return nil
+}
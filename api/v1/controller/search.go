@@ -0,0 +1,155 @@
+package controller
+
+import (
+	"strings"
+	"unicode"
+
+	"golang.org/x/text/unicode/norm"
+)
+
+// Search queries are normalized the same way the scrapers normalize product names into searchstr,
+// see scrape/core/src/text.rs. The tables below have to be kept in sync with the ones there
+
+// Abbreviations used in product names and what they stand for
+var abbreviations = map[string]string{
+	"hal.":   "halfvolle",
+	"halfv.": "halfvolle",
+	"hv":     "halfvolle",
+	"vol.":   "volle",
+	"mag.":   "magere",
+	"zv":     "zonder vet",
+	"z.v.":   "zonder vet",
+	"gesn.":  "gesneden",
+	"ongez.": "ongezouten",
+	"gez.":   "gezouten",
+	"ger.":   "gerookte",
+	"gekr.":  "gekruide",
+	"bl.":    "blik",
+	"fl.":    "fles",
+	"st.":    "stuk",
+}
+
+// Different ways stores write the same unit
+var unitAliases = map[string]string{
+	"gram":       "g",
+	"gr":         "g",
+	"grams":      "g",
+	"kilo":       "kg",
+	"kilogram":   "kg",
+	"liter":      "l",
+	"litre":      "l",
+	"ltr":        "l",
+	"lt":         "l",
+	"milliliter": "ml",
+	"centiliter": "cl",
+	"stuks":      "stuk",
+	"st":         "stuk",
+	"stk":        "stuk",
+}
+
+// Words that say something about the offer instead of the product
+var noiseWords = map[string]bool{
+	"nieuw": true, "new": true, "actie": true, "aanbieding": true,
+	"bonus": true, "online": true, "exclusief": true, "voordeel": true,
+}
+
+// Store brands put in front of their product names, longest first
+var storePrefixes = []string{"albert heijn", "jumbo", "ah"}
+
+const symbols = "™®©℗"
+
+// Lowercase, fold diacritics, expand abbreviations, split sizes like "1,5l" into "1,5 l",
+// normalize units and drop symbols, punctuation and marketing words
+func NormalizeText(value string) string {
+	var folded strings.Builder
+	for _, c := range norm.NFD.String(strings.ToLower(value)) {
+		switch {
+		case unicode.In(c, unicode.Mn, unicode.Mc, unicode.Me):
+		case strings.ContainsRune(symbols, c):
+			folded.WriteRune(' ')
+		default:
+			folded.WriteRune(c)
+		}
+	}
+
+	words := []string{}
+	for _, word := range strings.Fields(folded.String()) {
+		for _, expanded := range expandAbbreviation(word) {
+			for _, part := range splitWord(expanded) {
+				part = normalizeUnit(part)
+				if !noiseWords[part] {
+					words = append(words, part)
+				}
+			}
+		}
+	}
+	return strings.Join(words, " ")
+}
+
+// The normalized text without a store brand in front, what products are searched on
+func SearchString(value string) string {
+	normalized := NormalizeText(value)
+	for _, prefix := range storePrefixes {
+		if stripped, found := strings.CutPrefix(normalized, prefix+" "); found {
+			return stripped
+		}
+	}
+	return normalized
+}
+
+func expandAbbreviation(word string) []string {
+	trimmed := strings.TrimFunc(word, func(c rune) bool { return !isAlphanumeric(c) && c != '.' })
+	if expansion, found := abbreviations[trimmed]; found {
+		return strings.Split(expansion, " ")
+	}
+	return []string{word}
+}
+
+// Split a word on punctuation and between numbers and letters, "6x330ml" becomes ["6", "x", "330", "ml"].
+// Decimal separators between digits are kept
+func splitWord(word string) []string {
+	chars := []rune(word)
+	parts := []string{}
+	current := []rune{}
+
+	for i, c := range chars {
+		isDecimalSeparator := (c == ',' || c == '.') &&
+			i > 0 && isDigit(chars[i-1]) &&
+			i+1 < len(chars) && isDigit(chars[i+1])
+
+		if !isAlphanumeric(c) && !isDecimalSeparator {
+			if len(current) > 0 {
+				parts = append(parts, string(current))
+				current = current[:0]
+			}
+			continue
+		}
+		if len(current) > 0 {
+			last := current[len(current)-1]
+			if isAlphanumeric(c) && isAlphanumeric(last) && isDigit(last) != isDigit(c) {
+				parts = append(parts, string(current))
+				current = current[:0]
+			}
+		}
+		current = append(current, c)
+	}
+	if len(current) > 0 {
+		parts = append(parts, string(current))
+	}
+	return parts
+}
+
+func normalizeUnit(word string) string {
+	if unit, found := unitAliases[word]; found {
+		return unit
+	}
+	return word
+}
+
+func isAlphanumeric(c rune) bool {
+	return unicode.IsLetter(c) || unicode.IsNumber(c)
+}
+
+func isDigit(c rune) bool {
+	return c >= '0' && c <= '9'
+}
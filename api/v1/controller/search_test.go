@@ -0,0 +1,35 @@
+package controller
+
+import "testing"
+
+// Same cases as the tests of scrape/core/src/text.rs, the query has to end up like searchstr
+func TestNormalizeText(t *testing.T) {
+	cases := map[string]string{
+		"Coca-Cola Zero Sugar 1,5L": "coca cola zero sugar 1,5 l",
+		"Hal. melk":                 "halfvolle melk",
+		"Yoghurt ZV 500gram":        "yoghurt zonder vet 500 g",
+		"Heineken® Pils 6x330ml":    "heineken pils 6 x 330 ml",
+		"NIEUW! Crème fraîche":      "creme fraiche",
+		"Eieren 10 stuks":           "eieren 10 stuk",
+		"Calvé pindakaas":           "calve pindakaas",
+	}
+	for input, expected := range cases {
+		if normalized := NormalizeText(input); normalized != expected {
+			t.Errorf("normalizing %q: expected %q, got %q", input, expected, normalized)
+		}
+	}
+}
+
+func TestSearchString(t *testing.T) {
+	cases := map[string]string{
+		"AH Halfvolle melk":           "halfvolle melk",
+		"Jumbo Hal. Melk 1L":          "halfvolle melk 1 l",
+		"Albert Heijn Halfvolle Melk": "halfvolle melk",
+		"Ahoy koekjes":                "ahoy koekjes",
+	}
+	for input, expected := range cases {
+		if search := SearchString(input); search != expected {
+			t.Errorf("search string of %q: expected %q, got %q", input, expected, search)
+		}
+	}
+}
@@ -2,12 +2,13 @@ package dto
 
 import (
 	"auth"
+	"models"
 )
 
 type Product struct {
 	ID    uint  `json:"id"`
 	Name  string  `json:"name"`
-	Price float32 `json:"price"`
+	Price models.Cents `json:"price"`
 	Store string  `json:"store"`
 	Url   string  `json:"url"`
 }
@@ -36,5 +37,5 @@ type ShoppingList struct {
 	Owner      auth.User 	`json:"owner"`
 	Name       string      	`json:"name"`
 	Products   []*Product   `json:"products"`
-	TotalPrice float32     	`json:"total_price"`
+	TotalPrice models.Cents 	`json:"total_price"`
 }
\ No newline at end of file
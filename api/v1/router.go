@@ -25,6 +25,8 @@ func Add(base gin.IRouter, cdeps core.Depends) {
 	router.GET("/scraper_health", getScraperHealth)
 	router.GET("/scraper_state", getScraperState)
 	router.GET("/scrape_errors", deps.getScrapeErrors)
+	router.GET("/scrape_anomalies", deps.getScrapeAnomalies)
+	router.POST("/scrape_anomaly/acknowledge", deps.acknowledgeScrapeAnomaly)
 	router.GET("/store", deps.getProductsByStoreName)
 	router.POST("/shopping_list", deps.createShoppingList)
 	router.GET("/shopping_list", deps.getShoppingListById)
@@ -112,6 +114,24 @@ func (deps *Depends) getScrapeErrors(ctx *gin.Context) {
 	core.SendResponseOrError(ctx, errors, err)
 }
 
+func (deps *Depends) getScrapeAnomalies(ctx *gin.Context) {
+	anomalies, err := controller.GetScrapeAnomalies(deps.Database, ctx.Query("status"))
+	core.SendResponseOrError(ctx, anomalies, err)
+}
+
+func (deps *Depends) acknowledgeScrapeAnomaly(ctx *gin.Context) {
+	id, err := strconv.ParseUint(ctx.Query("id"), 10, 64)
+
+	if err != nil {
+		deps.Logger.Println("Not a valid id value: ", err.Error())
+		ctx.AbortWithStatus(http.StatusBadRequest)
+		return
+	}
+
+	err = controller.AcknowledgeScrapeAnomaly(deps.Database, id)
+	core.SendResponseOrError(ctx, gin.H{"status": "acknowledged", "id": id}, err)
+}
+
 func (deps *Depends) getProductsByStoreName(ctx *gin.Context) {
 	products, err := controller.GetProductsByStore(deps.Database, ctx.Query("store"))
 	core.SendResponseOrError(ctx, products, err)
@@ -1,18 +1,82 @@
 CREATE TABLE IF NOT EXISTS products (
     ID BIGSERIAL PRIMARY KEY,
     Name VARCHAR(255),
-    Price FLOAT4,
+    Price NUMERIC(10, 2),
+    Currency VARCHAR(3),
     Store VARCHAR(100),
     Url VARCHAR(750),
-    SearchStr VARCHAR(255)
+    SearchStr VARCHAR(255),
+    Quantity VARCHAR(100),
+    UnitPrice NUMERIC(10, 2),
+    UnitPriceUnit VARCHAR(20),
+    Brand VARCHAR(255),
+    Category VARCHAR(500),
+    ImageUrl VARCHAR(750),
+    Sku VARCHAR(100),
+    Ean VARCHAR(14),
+    Available BOOLEAN,
+    PromotionType VARCHAR(30),
+    PromotionLabel VARCHAR(255),
+    PromotionPrice NUMERIC(10, 2),
+    PromotionMinQuantity INT,
+    PromotionValidFrom DATE,
+    PromotionValidUntil DATE,
+    ProductKey VARCHAR(750) NOT NULL,
+    Taxonomy VARCHAR(255),
+    Description TEXT,
+    Ingredients TEXT,
+    Allergens VARCHAR(500),
+    Nutrition JSONB,
+    LastSeen TIMESTAMP
 );
 
 CREATE EXTENSION pg_trgm;
 
+CREATE UNIQUE INDEX idx_product_key ON products (Store, ProductKey);
+
 CREATE INDEX idx_product_name ON products USING gin(to_tsvector('dutch', SearchStr));
+CREATE INDEX idx_products_taxonomy ON products (Taxonomy);
 
 CREATE TABLE IF NOT EXISTS scrape_errors (
     ID BIGSERIAL PRIMARY KEY,
     Scraper VARCHAR(255),
     Message TEXT
 );
+
+CREATE TABLE IF NOT EXISTS scrape_anomalies (
+    ID BIGSERIAL PRIMARY KEY,
+    Store VARCHAR(100),
+    Kind VARCHAR(30),
+    ProductKey VARCHAR(750),
+    Category VARCHAR(500),
+    Message TEXT,
+    CreatedAt TIMESTAMP DEFAULT NOW(),
+    -- open, acknowledged (accepted, applied by the next run) or released (applied)
+    Status VARCHAR(20) DEFAULT 'open'
+);
+
+CREATE INDEX idx_scrape_anomaly_status ON scrape_anomalies (Store, Status);
+
+CREATE TABLE IF NOT EXISTS scrape_runs (
+    ID BIGSERIAL PRIMARY KEY,
+    Store VARCHAR(100),
+    StartedAt TIMESTAMP,
+    FinishedAt TIMESTAMP,
+    Incremental BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX idx_scrape_run_store ON scrape_runs (Store, StartedAt);
+
+CREATE TABLE IF NOT EXISTS product_matches (
+    ID BIGSERIAL PRIMARY KEY,
+    Store VARCHAR(100),
+    ProductKey VARCHAR(750),
+    MatchStore VARCHAR(100),
+    MatchProductKey VARCHAR(750),
+    Relation VARCHAR(30),
+    Confidence DOUBLE PRECISION,
+    GroupKey VARCHAR(900)
+);
+
+CREATE INDEX idx_product_match ON product_matches (Store, ProductKey);
+CREATE INDEX idx_product_match_reverse ON product_matches (MatchStore, MatchProductKey);
@@ -1,8 +1,41 @@
+use std::sync::Arc;
 use anyhow::Result;
-use log::info;
-use scrape_core::scrape_utils::build_selector;
-use scrape_core::{AsyncTransform, HtmlLoader, ProductInfo, AsyncExecutor, ResultCollector, ScrapeError, Scraper};
-use super::parse::{get_product_name, get_price, get_links, get_product_url};
+use log::{info, warn};
+use scraper::Html;
+use scrape_core::scrape_utils::{build_selector, chain_usage, merge_page_products};
+use scrape_core::{
+    HtmlLoader,
+    ProductInfo,
+    AsyncExecutor,
+    ResultCollector,
+    ScrapeError,
+    Scraper,
+    Pipeline,
+    Stage,
+    StoreConfig,
+    Discovery,
+    CategoryTree,
+    CategoryPage,
+};
+use super::parse::{
+    get_product_name,
+    get_price,
+    get_links,
+    get_product_url,
+    get_quantity,
+    get_unit_price,
+    get_brand,
+    get_image_url,
+    get_sku,
+    get_availability,
+    get_offer,
+    get_category,
+    product_key,
+    product_id_from_url,
+    get_total_count,
+    BrandPage,
+};
+use super::listing::{Listing, PagePlan, PageTask, PageOutcome, collect_pages};
 
 pub const SRC: &str = "Albert Heijn";
 pub const BASE_URL: &str = "https://www.ah.nl";
@@ -10,59 +43,138 @@ pub const PAGE_PART: &str = "?page=";
 pub const OFFSET_PART: &str = "&withOffset=true";
 const LETTER_URL: &str = "/producten/merk?letter=";
 const PRODUCTS_PER_PAGE: usize = 36;
+const CATEGORIES_PATH: &str = "/producten";
+// Pages directly below /producten that aren't categories
+const NOT_CATEGORIES: [&str; 4] = ["merk", "product", "bonus", "zoeken"];
+/// Supported ways of finding products, the first is the default. Both cover the catalogue, they are
+/// kept side by side to compare which finds more products
+pub const DISCOVERY: [Discovery; 2] = [Discovery::Brands, Discovery::Categories];
 const LETTERS: [&str; 27] = [
     "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o",
     "p", "q", "r", "s", "t", "u", "v", "w", "x", "y", "z", "%23",
 ];
 
+/// Settings the Albert Heijn scraper uses when nothing else is configured.
+/// ah.nl blocks bursts of requests, so a random delay is applied
+pub fn default_config() -> StoreConfig {
+    StoreConfig::default()
+        .with_base_url(BASE_URL)
+        .with_delay(100, 5000)
+        .with_header("Connection", "keep-alive")
+}
+
 pub struct AlbertHeijnScraper<'a, T: HtmlLoader + Send + Sync> {
     connector: &'a T,
+    base_url: String,
+    discovery: Discovery,
 }
 
 impl<'a, T: HtmlLoader + Send + Sync> AlbertHeijnScraper<'a, T> {
     pub fn new(connector: &'a T) -> Self {
-        Self { connector }
+        Self { connector, base_url: BASE_URL.to_owned(), discovery: DISCOVERY[0] }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
     }
 
-    async fn scrape_brand_urls_for_letter(&self, letter: &str) -> Result<Vec<String>> {
-        let url = format!("{}{}{}", BASE_URL, LETTER_URL, letter);
+    /// How products are found, one of `DISCOVERY`
+    pub fn with_discovery(mut self, discovery: Discovery) -> Self {
+        self.discovery = discovery;
+        self
+    }
+
+    async fn scrape_brand_urls_for_letter(&self, letter: &str) -> Result<Vec<BrandPage>> {
+        let url = format!("{}{}{}", self.base_url, LETTER_URL, letter);
 
         info!("Scraping brand urls at {}", &url);
         let document = self.connector.load(url).await?;
 
-        Ok(get_links(document.root_element())?)
+        get_links(document.root_element(), &self.base_url)
+    }
+
+    fn brand_listing(&self, brand: BrandPage) -> Listing {
+        Listing { url: brand.url, brand: brand.name, category: Vec::new() }
     }
 
-    async fn scrape_product_link_until_exhausted(&self, url: String) -> ResultCollector<ProductInfo> {
-        let mut should_break = false;
-        let mut offset = 0;
-        let mut collector = ResultCollector::new();
+    fn category_listing(&self, category: CategoryPage) -> Listing {
+        Listing { url: format!("{}{}", self.base_url, category.url_path), brand: None, category: category.path }
+    }
+
+    /// Load the first page of a listing and plan the other pages from the result count on it
+    async fn plan_pages(&self, listing: Listing) -> Result<Vec<PageTask>> {
+        let url = page_url(&listing.url, 0);
+        info!("Scraping url {}", &url);
+        let document = self.connector.load(url.clone()).await?;
+        let first = self.parse_page(&document, url)?;
+
+        let total = get_total_count(&document)?;
+        if total.is_none() {
+            warn!(target: SRC, "No result count on {}, requesting pages until one is short", &listing.url);
+        }
+        let plan = PagePlan::new(total, PRODUCTS_PER_PAGE);
+        let listing = Arc::new(listing);
+        let pages = match plan {
+            PagePlan::Known { pages, .. } => pages,
+            // The first page tells whether there is a next one
+            PagePlan::UntilExhausted if first.len() < PRODUCTS_PER_PAGE => 1,
+            PagePlan::UntilExhausted => 2,
+        };
+
+        let mut tasks = vec![PageTask { listing: listing.clone(), page: 0, plan, loaded: Some(first) }];
+        tasks.extend((1..pages).map(|page| PageTask { listing: listing.clone(), page, plan, loaded: None }));
+        Ok(tasks)
+    }
+
+    async fn scrape_planned_page(&self, task: PageTask) -> Result<Vec<PageOutcome>> {
+        let outcome = |page: usize, result: Result<Vec<ProductInfo>>| PageOutcome {
+            listing: task.listing.clone(),
+            page,
+            plan: task.plan,
+            result,
+        };
+        if let Some(products) = task.loaded {
+            return Ok(vec![outcome(task.page, Ok(products))]);
+        }
+        match task.plan {
+            PagePlan::Known { .. } => Ok(vec![outcome(task.page, self.scrape_page_with_offset(&task.listing.url, task.page).await)]),
+            PagePlan::UntilExhausted => {
+                let pages = self.scrape_product_link_until_exhausted(&task.listing.url, task.page).await;
+                Ok(pages.into_iter().map(|(page, result)| outcome(page, result)).collect())
+            },
+        }
+    }
+
+    /// Pages from `first_page` on, until a page has less than a full page of products or fails
+    async fn scrape_product_link_until_exhausted(&self, url: &str, first_page: usize) -> Vec<(usize, Result<Vec<ProductInfo>>)> {
+        let mut pages = Vec::new();
+        let mut offset = first_page;
 
         loop {
-            let products_result = self.scrape_page_with_offset(&url, offset).await;
-            
-            match &products_result {
-                Ok(prods) => {
-                    if prods.len() < PRODUCTS_PER_PAGE {
-                        should_break = true;
-                    }
-                },
-                Err(_) => should_break = true,
+            let products_result = self.scrape_page_with_offset(url, offset).await;
+            let should_break = match &products_result {
+                Ok(prods) => prods.len() < PRODUCTS_PER_PAGE,
+                Err(_) => true,
             };
 
-            collector.collect(products_result);            
+            pages.push((offset, products_result));
             if should_break {
                 break;
             }
             offset += 1;
         }
-        collector.flatten()
+        pages
     }
 
     async fn scrape_page_with_offset(&self, url: &str, offset: usize) -> Result<Vec<ProductInfo>> {
-        let offset_url = format!("{}{}{}{}", url, PAGE_PART, offset.to_string(), OFFSET_PART);
+        let offset_url = page_url(url, offset);
         info!("Scraping url {}", &offset_url);
         let document = self.connector.load(offset_url.clone()).await?;
+        self.parse_page(&document, offset_url)
+    }
+
+    fn parse_page(&self, document: &Html, offset_url: String) -> Result<Vec<ProductInfo>> {
         let product_container_selector = build_selector(
             "article", 
             SRC
@@ -72,17 +184,28 @@ impl<'a, T: HtmlLoader + Send + Sync> AlbertHeijnScraper<'a, T> {
         let result: Result<Vec<ProductInfo>> = product_containers
             .into_iter()
             .map(|product_container| -> Result<ProductInfo> {
-                Ok(ProductInfo::new(
+                let url = get_product_url(product_container, &self.base_url)?;
+                let mut product = ProductInfo::new(
                     get_product_name(product_container)?,
                     get_price(product_container)?,
-                    get_product_url(product_container)?,
-                ))
+                    url.clone(),
+                ).with_key(product_key(&url));
+                product.quantity = get_quantity(product_container)?;
+                product.unit_price = get_unit_price(product_container)?;
+                product.brand = get_brand(product_container)?;
+                product.image_url = get_image_url(product_container)?;
+                product.sku = get_sku(product_container)?;
+                product.availability = get_availability(product_container)?;
+                product.offer = get_offer(product_container)?;
+                product.category = get_category(product_container)?;
+                Ok(product)
             })
             .collect();
+        let result = merge_page_products(document, result, &self.base_url, product_key, product_id_from_url, SRC, &offset_url);
 
         match result {
             Ok(products) => {
-                if products.len() == 0 {
+                if products.is_empty() {
                     return Err(ScrapeError::NoProductsFound { src: SRC.to_owned(), url: offset_url }.into())
                 };
                 Ok(products)
@@ -94,12 +217,46 @@ impl<'a, T: HtmlLoader + Send + Sync> AlbertHeijnScraper<'a, T> {
 
 impl<'a, T: HtmlLoader + Send + Sync> Scraper for AlbertHeijnScraper<'a, T> {
     async fn scrape<R: AsyncExecutor + Send + Sync>(&self, rate_limiter: &R) ->  ResultCollector<ProductInfo> {
-        info!(target: SRC, "Start scraping");
-        ResultCollector::from(LETTERS.to_vec())
-            .transform_async(|l| self.scrape_brand_urls_for_letter(l), rate_limiter)
-            .await
-            .flatten()
-            .transform_async(|url| self.scrape_product_link_until_exhausted(url), rate_limiter)
-            .await
+        info!(target: SRC, "Start scraping by {}", self.discovery.as_str());
+
+        let (products, report) = match self.discovery {
+            Discovery::Categories => {
+                let categories = CategoryTree::new(self.connector, &self.base_url, CATEGORIES_PATH)
+                    .with_excluded(&NOT_CATEGORIES)
+                    .leaves(rate_limiter)
+                    .await;
+                info!(target: SRC, "Found {} leaf categories", categories.successes.len());
+
+                Pipeline::new(SRC)
+                    .stage(Stage::new("plan", rate_limiter, |category| self.plan_pages(self.category_listing(category))))
+                    .stage(Stage::new("pages", rate_limiter, |task| self.scrape_planned_page(task)))
+                    .run(categories)
+                    .await
+            },
+            _ => {
+                Pipeline::new(SRC)
+                    .stage(Stage::new("brands", rate_limiter, |l| self.scrape_brand_urls_for_letter(l)))
+                    .stage(Stage::new("plan", rate_limiter, |brand| self.plan_pages(self.brand_listing(brand))))
+                    .stage(Stage::new("pages", rate_limiter, |task| self.scrape_planned_page(task)))
+                    .run(ResultCollector::from(LETTERS.to_vec()))
+                    .await
+            },
+        };
+        let mut products = collect_pages(products);
+
+        info!(target: SRC, "{}", report);
+        for usage in chain_usage(SRC) {
+            info!(target: SRC, "Selectors {}", usage);
+        }
+        // Products are listed on more than one page, e.g. under "AH" and "AH Biologisch" or in two categories
+        let removed = products.dedupe_by_key(|product| product.key.clone());
+        info!(target: SRC, "Removed {} products listed on more than one page", removed);
+        // Logged the same for each discovery, to compare their coverage between runs
+        info!(target: SRC, "Coverage: {} unique products by {}", products.successes.len(), self.discovery.as_str());
+        products
     }
-}
\ No newline at end of file
+}
+
+fn page_url(url: &str, page: usize) -> String {
+    format!("{}{}{}{}", url, PAGE_PART, page, OFFSET_PART)
+}
@@ -1,4 +1,6 @@
 mod albert_heijn_scraper;
 mod parse;
+mod listing;
 
-pub use albert_heijn_scraper::AlbertHeijnScraper;
\ No newline at end of file
+pub use albert_heijn_scraper::{AlbertHeijnScraper, SRC, BASE_URL, DISCOVERY, default_config};
+pub use parse::product_key;
\ No newline at end of file
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use log::warn;
+use anyhow::Result;
+use scrape_core::{ProductInfo, ResultCollector, ScrapeError};
+use super::albert_heijn_scraper::SRC;
+
+/// The pages of a brand or a category. The products get the brand or category of the listing,
+/// those are more reliable than what their tiles show
+#[derive(Debug)]
+pub struct Listing {
+    pub url: String,
+    pub brand: Option<String>,
+    pub category: Vec<String>,
+}
+
+impl Listing {
+    pub fn apply(&self, products: Vec<ProductInfo>) -> Vec<ProductInfo> {
+        products
+            .into_iter()
+            .map(|mut product| {
+                if let Some(brand) = &self.brand {
+                    product.brand = Some(brand.clone());
+                }
+                if !self.category.is_empty() {
+                    product.category = self.category.clone();
+                }
+                product
+            })
+            .collect()
+    }
+}
+
+/// How many pages a listing has, known from the result count on its first page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagePlan {
+    Known { pages: usize, expected: usize },
+    /// No result count was found, pages are requested one by one until a short page
+    UntilExhausted,
+}
+
+impl PagePlan {
+    pub fn new(expected: Option<usize>, per_page: usize) -> Self {
+        match expected {
+            Some(expected) => PagePlan::Known { pages: expected.div_ceil(per_page).max(1), expected },
+            None => PagePlan::UntilExhausted,
+        }
+    }
+}
+
+/// A page of a listing to scrape, the first page is already loaded to read the result count
+pub struct PageTask {
+    pub listing: Arc<Listing>,
+    pub page: usize,
+    pub plan: PagePlan,
+    pub loaded: Option<Vec<ProductInfo>>,
+}
+
+pub struct PageOutcome {
+    pub listing: Arc<Listing>,
+    pub page: usize,
+    pub plan: PagePlan,
+    pub result: Result<Vec<ProductInfo>>,
+}
+
+/// Combine the pages of all listings, listings with failed pages are reported against their expected total
+pub fn collect_pages(outcomes: ResultCollector<PageOutcome>) -> ResultCollector<ProductInfo> {
+    let mut collector = ResultCollector { successes: Vec::new(), errors: outcomes.errors };
+    let mut listings: BTreeMap<String, (PagePlan, usize, Vec<usize>)> = BTreeMap::new();
+
+    for outcome in outcomes.successes {
+        let (_, found, missing) = listings.entry(outcome.listing.url.clone()).or_insert((outcome.plan, 0, Vec::new()));
+        match outcome.result {
+            Ok(products) => {
+                *found += products.len();
+                collector.successes.extend(outcome.listing.apply(products));
+            },
+            Err(e) => {
+                missing.push(outcome.page);
+                collector.errors.push(e);
+            },
+        };
+    }
+
+    for (url, (plan, found, mut missing_pages)) in listings {
+        let PagePlan::Known { pages, expected } = plan else {
+            continue;
+        };
+        if missing_pages.is_empty() {
+            continue;
+        }
+        missing_pages.sort();
+        let incomplete = ScrapeError::IncompleteListing { src: SRC.to_owned(), url, pages, missing_pages, found, expected };
+        warn!(target: SRC, "{}", incomplete);
+        collector.errors.push(incomplete.into());
+    }
+    collector
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use anyhow::anyhow;
+    use scrape_core::{Money, ProductInfo, ResultCollector, ScrapeError};
+    use super::{collect_pages, Listing, PageOutcome, PagePlan};
+
+    fn products(count: usize) -> Vec<ProductInfo> {
+        (0..count)
+            .map(|i| ProductInfo::new(format!("Product {i}"), Money::parse("1,00").unwrap(), format!("https://www.ah.nl/producten/product/wi{i}")))
+            .collect()
+    }
+
+    fn outcome(listing: &Arc<Listing>, page: usize, plan: PagePlan, result: anyhow::Result<Vec<ProductInfo>>) -> PageOutcome {
+        PageOutcome { listing: listing.clone(), page, plan, result }
+    }
+
+    #[test]
+    fn test_page_plan() {
+        assert_eq!(PagePlan::new(Some(72), 36), PagePlan::Known { pages: 2, expected: 72 });
+        assert_eq!(PagePlan::new(Some(73), 36), PagePlan::Known { pages: 3, expected: 73 });
+        assert_eq!(PagePlan::new(Some(5), 36), PagePlan::Known { pages: 1, expected: 5 });
+        assert_eq!(PagePlan::new(Some(0), 36), PagePlan::Known { pages: 1, expected: 0 });
+        assert_eq!(PagePlan::new(None, 36), PagePlan::UntilExhausted);
+    }
+
+    #[test]
+    fn test_collect_pages() {
+        let listing = Arc::new(Listing { url: "https://www.ah.nl/producten/zuivel".to_owned(), brand: None, category: vec!["Zuivel".to_owned()] });
+        let plan = PagePlan::new(Some(40), 36);
+        let outcomes = ResultCollector {
+            successes: vec![outcome(&listing, 0, plan, Ok(products(36))), outcome(&listing, 1, plan, Ok(products(4)))],
+            errors: Vec::new(),
+        };
+
+        let collected = collect_pages(outcomes);
+        assert_eq!(collected.successes.len(), 40);
+        assert!(collected.errors.is_empty());
+        assert!(collected.successes.iter().all(|product| product.category == vec!["Zuivel"]));
+    }
+
+    #[test]
+    fn test_collect_pages_reports_failed_page() {
+        let listing = Arc::new(Listing { url: "https://www.ah.nl/producten/zuivel".to_owned(), brand: None, category: Vec::new() });
+        let plan = PagePlan::new(Some(80), 36);
+        let outcomes = ResultCollector {
+            successes: vec![
+                outcome(&listing, 2, plan, Ok(products(8))),
+                outcome(&listing, 1, plan, Err(anyhow!("timed out"))),
+                outcome(&listing, 0, plan, Ok(products(36))),
+            ],
+            errors: Vec::new(),
+        };
+
+        let collected = collect_pages(outcomes);
+        assert_eq!(collected.successes.len(), 44);
+        assert_eq!(collected.errors.len(), 2);
+        match collected.errors[1].downcast_ref::<ScrapeError>() {
+            Some(ScrapeError::IncompleteListing { url, pages, missing_pages, found, expected, .. }) => {
+                assert_eq!(url, "https://www.ah.nl/producten/zuivel");
+                assert_eq!((*pages, missing_pages.as_slice(), *found, *expected), (3, &[1][..], 44, 80));
+            },
+            other => panic!("expected IncompleteListing, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_collect_pages_without_count_reports_no_listing() {
+        let listing = Arc::new(Listing { url: "https://www.ah.nl/producten/merk/ah".to_owned(), brand: Some("AH".to_owned()), category: Vec::new() });
+        let outcomes = ResultCollector {
+            successes: vec![
+                outcome(&listing, 0, PagePlan::UntilExhausted, Ok(products(36))),
+                outcome(&listing, 1, PagePlan::UntilExhausted, Err(anyhow!("timed out"))),
+            ],
+            errors: Vec::new(),
+        };
+
+        let collected = collect_pages(outcomes);
+        assert_eq!(collected.successes.len(), 36);
+        assert_eq!(collected.errors.len(), 1);
+        assert!(collected.successes.iter().all(|product| product.brand.as_deref() == Some("AH")));
+    }
+}
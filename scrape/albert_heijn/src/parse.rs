@@ -1,38 +1,106 @@
-use scraper::ElementRef;
-use std::num::ParseFloatError;
+use scraper::{ElementRef, Html};
 use anyhow::Result;
-use scrape_core::ScrapeError;
-use scrape_core::scrape_utils::{build_selector, walk_selectors};
-use super::albert_heijn_scraper::{SRC, BASE_URL};
+use scrape_core::{ScrapeError, Money, UnitPrice, Availability, Offer, Promotion};
+use scrape_core::scrape_utils::{
+    build_selector,
+    build_selectors,
+    walk_selectors,
+    find_text,
+    find_attr,
+    normalize_url,
+    class_prefix,
+    SelectorChain,
+    next_data,
+    find_objects,
+};
+use super::albert_heijn_scraper::SRC;
 
-pub fn get_product_url(element: ElementRef) -> Result<String> {
+// Product links look like /producten/product/wi123456/name, the wi-number is AH's own id
+const PRODUCT_ID_PREFIX: &str = "/wi";
+const UNAVAILABLE_TEXTS: [&str; 3] = ["niet leverbaar", "uitverkocht", "niet beschikbaar"];
+const BONUS_LABEL: &str = "Bonus";
+
+pub fn get_product_url(element: ElementRef, base_url: &str) -> Result<String> {
     let selector = build_selector("a", SRC)?;
     let link_html = walk_selectors(element, &[selector], SRC)?;
     let link = link_html
         .attr("href")
         .ok_or(ScrapeError::InvalidStructureAssumed { src: SRC.to_string(), info: String::from("href") })?;
-    let mut prod_url = BASE_URL.to_owned();
+    let mut prod_url = base_url.to_owned();
     prod_url.push_str(link);
     Ok(prod_url)
 }
 
-pub fn get_links(element: ElementRef) -> Result<Vec<String>> {
-    let brand_links_selector = build_selector("div.brand-hub_links__E6cvr", SRC)?;
-    let brand_links_container = walk_selectors(element, &[brand_links_selector], SRC)?;
+/// A brand page, its products are all of this brand
+#[derive(Debug, Clone)]
+pub struct BrandPage {
+    pub name: Option<String>,
+    pub url: String,
+}
+
+pub fn get_links(element: ElementRef, base_url: &str) -> Result<Vec<BrandPage>> {
+    // The class names are generated by AH's front-end build, the hash changes with every deploy
+    let brand_links_chain = SelectorChain::new(SRC, "brand links", &[
+        "div.brand-hub_links__E6cvr",
+        &class_prefix("div", "brand-hub_links"),
+        "[data-testhook='brand-hub-links']",
+    ])?;
+    let brand_links_container = brand_links_chain.require(element)?;
     let a_selector = build_selector("a", SRC)?;
-    let brand_links = brand_links_container.select(&a_selector);
-    Ok(
-        brand_links
-        .into_iter()
-        .map(|l| l
-            .value()
-            .attr("href")
-            .ok_or(ScrapeError::InvalidStructureAssumed { src: SRC.to_string(), info: String::from("href") }))
-        .collect::<Result<Vec<&str>, ScrapeError>>()?
-        .into_iter()
-        .map(|e| format!("{}{}", BASE_URL, e))
+    brand_links_container
+        .select(&a_selector)
+        .map(|link| {
+            let href = link
+                .value()
+                .attr("href")
+                .ok_or(ScrapeError::InvalidStructureAssumed { src: SRC.to_string(), info: String::from("href") })?;
+            Ok(BrandPage { name: brand_name(&link.text().collect::<String>()), url: format!("{}{}", base_url, href) })
+        })
         .collect()
-    )
+}
+
+/// The brand of a brand link, without the number of products some links show, e.g. "AH Biologisch (312)"
+pub fn brand_name(link_text: &str) -> Option<String> {
+    let text = link_text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let name = match text.rsplit_once(" (") {
+        Some((name, count)) if count.trim_end_matches(')').chars().all(|c| c.is_ascii_digit()) => name,
+        _ => text.as_str(),
+    };
+    match name.is_empty() {
+        true => None,
+        false => Some(name.to_owned()),
+    }
+}
+
+/// Number of products of a brand or category as shown above the listing, e.g. "1.234 resultaten".
+/// Falls back on the page state, `None` when neither has it
+pub fn get_total_count(document: &Html) -> Result<Option<usize>> {
+    let chain = SelectorChain::new(SRC, "result count", &[
+        "[data-testhook='search-result-count']",
+        "[data-testhook='product-count']",
+        &class_prefix("span", "search-header_resultCount"),
+        &class_prefix("div", "product-count"),
+    ])?;
+    let from_text = chain.text(document.root_element()).and_then(|text| {
+        // Thousands are separated by a point
+        let digits: String = text
+            .split_whitespace()
+            .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))?
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    });
+    if from_text.is_some() {
+        return Ok(from_text);
+    }
+
+    Ok(next_data(document).and_then(|state| {
+        find_objects(&state, &|object| object.get("totalElements").is_some_and(|total| total.is_u64()))
+            .first()
+            .and_then(|object| object["totalElements"].as_u64())
+            .map(|total| total as usize)
+    }))
 }
 
 pub fn get_product_name(element: ElementRef) -> Result<String> {
@@ -43,16 +111,160 @@ pub fn get_product_name(element: ElementRef) -> Result<String> {
     Ok(product_name.to_owned())
 }
 
-pub fn get_price(element: ElementRef) -> Result<f32> {
-    let price_selector = build_selector("div.price-amount_root__Sa88q", SRC)?;
-    let price_str: String = element
-        .select(&price_selector)
-        .last()
+pub fn get_price(element: ElementRef) -> Result<Money> {
+    // Products in the Bonus show the struck through regular price first, followed by the Bonus price
+    let was_price_chain = SelectorChain::new(SRC, "was price", &["div[class*='price-amount_was']"])?;
+    let price_chain = SelectorChain::new(SRC, "price", &[
+        "div.price-amount_root__Sa88q",
+        &class_prefix("div", "price-amount_root"),
+        "[data-testhook='price-amount']",
+    ])?;
+    let price_str: String = was_price_chain
+        .select_first(element)
+        .or_else(|| price_chain.select_all(element).pop())
         .ok_or(ScrapeError::InvalidStructureAssumed { src: SRC.to_string(), info: String::from("last in price") })?
         .text()
         .collect::<String>();
 
-    Ok(price_str.parse().map_err(|e: ParseFloatError| {
+    Ok(Money::parse(&price_str).map_err(|e| {
         ScrapeError::FailedToParseStringValue{ src: SRC.to_string(), err: e.to_string() }
     })?)
-}
\ No newline at end of file
+}
+
+pub fn get_quantity(element: ElementRef) -> Result<Option<String>> {
+    let chain = SelectorChain::new(SRC, "quantity", &[
+        "[data-testhook='product-unit-size']",
+        "span.price_unitSize__Hk6E4",
+        &class_prefix("span", "price_unitSize"),
+    ])?;
+    Ok(chain.text(element))
+}
+
+pub fn get_unit_price(element: ElementRef) -> Result<Option<UnitPrice>> {
+    let chain = SelectorChain::new(SRC, "unit price", &[
+        "[data-testhook='product-price-per-unit']",
+        "span.price_unitPrice__Hk6E4",
+        &class_prefix("span", "price_unitPrice"),
+    ])?;
+    Ok(chain.text(element).and_then(|text| UnitPrice::parse(&text)))
+}
+
+pub fn get_brand(element: ElementRef) -> Result<Option<String>> {
+    let selectors = build_selectors(&["[data-testhook='product-brand']"], SRC)?;
+    Ok(find_text(element, &selectors))
+}
+
+pub fn get_image_url(element: ElementRef) -> Result<Option<String>> {
+    let selectors = build_selectors(&["img[data-testhook='product-image']", "img"], SRC)?;
+    Ok(find_attr(element, &selectors, "src"))
+}
+
+pub fn get_sku(element: ElementRef) -> Result<Option<String>> {
+    let selector = build_selector("a", SRC)?;
+    Ok(find_attr(element, &[selector], "href").and_then(|href| product_id_from_url(&href)))
+}
+
+/// AH's own product id, e.g. "wi123456", taken from a product URL
+pub fn product_id_from_url(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once(PRODUCT_ID_PREFIX)?;
+    let id: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    match id.is_empty() {
+        true => None,
+        false => Some(format!("wi{}", id)),
+    }
+}
+
+/// Key that identifies a product across scrape runs, the AH id or the normalized URL when there is none
+pub fn product_key(url: &str) -> String {
+    product_id_from_url(url).unwrap_or_else(|| normalize_url(url))
+}
+
+/// Category path of a product tile, e.g. ["Zuivel, plantaardig en eieren", "Melk"]
+pub fn get_category(element: ElementRef) -> Result<Vec<String>> {
+    let selectors = build_selectors(&["[data-testhook='product-category']"], SRC)?;
+    let path = element
+        .value()
+        .attr("data-category")
+        .map(str::to_owned)
+        .or_else(|| find_text(element, &selectors))
+        .unwrap_or_default();
+
+    Ok(path.split(['/', '>']).map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect())
+}
+
+pub fn get_availability(element: ElementRef) -> Result<Availability> {
+    let text = element.text().collect::<String>().to_lowercase();
+    if UNAVAILABLE_TEXTS.iter().any(|t| text.contains(t)) {
+        return Ok(Availability::Unavailable);
+    }
+
+    let basket_selector = build_selector("button[data-testhook='product-plus']", SRC)?;
+    match element.select(&basket_selector).next() {
+        Some(_) => Ok(Availability::Available),
+        None => Ok(Availability::Unknown),
+    }
+}
+
+pub fn get_offer(element: ElementRef) -> Result<Option<Offer>> {
+    let label_selectors = build_selectors(&["[data-testhook='product-shield']", "div[class*='shield_root']"], SRC)?;
+    let validity_selectors = build_selectors(&["[data-testhook='product-shield-period']"], SRC)?;
+    let bonus_price_selectors = build_selectors(&["div[class*='price-amount_bonus']"], SRC)?;
+
+    let label = find_text(element, &label_selectors);
+    let validity = find_text(element, &validity_selectors);
+    if let Some(offer) = label.as_deref().and_then(|label| Offer::parse(label, validity.as_deref())) {
+        return Ok(Some(offer));
+    }
+
+    // A plain Bonus shield only comes with a reduced price
+    let bonus_price = find_text(element, &bonus_price_selectors).and_then(|price| Money::parse(&price).ok());
+    Ok(bonus_price.map(|price| {
+        let offer = Offer::new(Promotion::FixedPrice { price }, label.as_deref().unwrap_or(BONUS_LABEL));
+        match validity.as_deref() {
+            Some(validity) => offer.with_validity(validity),
+            None => offer,
+        }
+    }))
+}
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+    use super::{brand_name, get_total_count, product_id_from_url, product_key};
+
+    #[test]
+    fn test_product_id_from_url() {
+        assert_eq!(product_id_from_url("https://www.ah.nl/producten/product/wi123456/ah-halfvolle-melk"), Some("wi123456".to_owned()));
+        assert_eq!(product_id_from_url("/producten/product/wi4235/brood?ref=bonus"), Some("wi4235".to_owned()));
+        assert_eq!(product_id_from_url("https://www.ah.nl/producten/product/wi/zonder-id"), None);
+        assert_eq!(product_id_from_url("https://www.ah.nl/producten/zuivel"), None);
+    }
+
+    #[test]
+    fn test_product_key_falls_back_on_normalized_url() {
+        assert_eq!(product_key("https://www.ah.nl/producten/product/wi123456/ah-halfvolle-melk?x=1"), "wi123456");
+        assert_eq!(product_key("https://www.ah.nl/producten/product/melk/?x=1#top"), "https://www.ah.nl/producten/product/melk");
+    }
+
+    #[test]
+    fn test_total_count() {
+        let from_text = Html::parse_document(r#"<span data-testhook="search-result-count">1.234 resultaten</span>"#);
+        assert_eq!(get_total_count(&from_text).unwrap(), Some(1234));
+
+        let from_next_data = Html::parse_document(r#"
+            <script id="__NEXT_DATA__" type="application/json">{"props": {"pageProps": {"search": {"page": {"totalElements": 72}}}}}</script>
+        "#);
+        assert_eq!(get_total_count(&from_next_data).unwrap(), Some(72));
+
+        let missing = Html::parse_document("<div>Geen resultaten</div>");
+        assert_eq!(get_total_count(&missing).unwrap(), None);
+    }
+
+    #[test]
+    fn test_brand_name() {
+        assert_eq!(brand_name("AH Biologisch (312)").as_deref(), Some("AH Biologisch"));
+        assert_eq!(brand_name("  Coca-Cola\n  (48) ").as_deref(), Some("Coca-Cola"));
+        assert_eq!(brand_name("7Up").as_deref(), Some("7Up"));
+        assert_eq!(brand_name("Dr. Oetker (Nederland)").as_deref(), Some("Dr. Oetker (Nederland)"));
+        assert_eq!(brand_name("  "), None);
+    }
+}
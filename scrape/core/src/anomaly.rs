@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use serde::Serialize;
+use super::{AnomalyConfig, InDbProduct, Money};
+
+/// A product as it was written by the previous run of a store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviousProduct {
+    pub key: String,
+    pub price: Money,
+    /// Store category path joined with " > "
+    pub category: Option<String>,
+}
+
+/// Anomalies of earlier runs of a store that were looked into and accepted. The next run
+/// publishes the products they held back instead of flagging them again
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Acknowledged {
+    /// Keys of products whose price change is accepted
+    pub price_changes: HashSet<String>,
+    /// A smaller catalogue than the previous run is accepted
+    pub catalogue_drop: bool,
+}
+
+impl Acknowledged {
+    pub fn is_empty(&self) -> bool {
+        self.price_changes.is_empty() && !self.catalogue_drop
+    }
+
+    pub fn len(&self) -> usize {
+        self.price_changes.len() + usize::from(self.catalogue_drop)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// The price of a product changed more than allowed
+    PriceChange,
+    /// The run found a lot less products than the previous run
+    CatalogueDrop,
+    /// A category of the previous run has no products anymore
+    VanishedCategory,
+}
+
+impl AnomalyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::PriceChange => "price_change",
+            AnomalyKind::CatalogueDrop => "catalogue_drop",
+            AnomalyKind::VanishedCategory => "vanished_category",
+        }
+    }
+}
+
+/// A suspicious difference between a run and the previous run of the same store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Anomaly {
+    pub store: String,
+    pub kind: AnomalyKind,
+    pub product_key: Option<String>,
+    pub category: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.as_str(), self.message)
+    }
+}
+
+/// Outcome of comparing a run with the previous one
+#[derive(Debug, Default, Serialize)]
+pub struct AnomalyReport {
+    pub previous: usize,
+    pub current: usize,
+    /// Products that aren't published because of an anomaly
+    pub held_back: usize,
+    /// Acknowledged anomalies that were applied and didn't hold back products this time
+    pub released: Acknowledged,
+    /// The run only had the products that changed, the catalogue and its categories weren't compared
+    pub incremental: bool,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl AnomalyReport {
+    pub fn is_clean(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+
+    /// The whole run is held back because the catalogue shrunk too much
+    pub fn is_catalogue_drop(&self) -> bool {
+        self.anomalies.iter().any(|a| a.kind == AnomalyKind::CatalogueDrop)
+    }
+
+    /// Keys of the products held back because of their price change
+    pub fn held_back_keys(&self) -> Vec<&str> {
+        self.anomalies
+            .iter()
+            .filter(|a| a.kind == AnomalyKind::PriceChange)
+            .filter_map(|a| a.product_key.as_deref())
+            .collect()
+    }
+}
+
+impl fmt::Display for AnomalyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut per_kind: BTreeMap<AnomalyKind, usize> = BTreeMap::new();
+        for anomaly in self.anomalies.iter() {
+            *per_kind.entry(anomaly.kind).or_insert(0) += 1;
+        }
+        let per_kind: Vec<String> = per_kind
+            .iter()
+            .map(|(kind, count)| format!("{}: {}", kind.as_str(), count))
+            .collect();
+
+        write!(
+            f,
+            "{} products, {} in the previous run, {} held back, {} released",
+            self.current,
+            self.previous,
+            self.held_back,
+            self.released.len(),
+        )?;
+        match per_kind.is_empty() {
+            true => Ok(()),
+            false => write!(f, " ({})", per_kind.join(", ")),
+        }
+    }
+}
+
+/// Compares the products of a run with the previous run of the same store, so a parser bug
+/// can't replace the catalogue with wrong prices unnoticed.
+///
+/// # Example
+/// ```
+/// use scrape_core::{Acknowledged, AnomalyConfig, AnomalyDetector, InDbProduct, Money, PreviousProduct, ProductInfo};
+///
+/// let previous = vec![PreviousProduct { key: "melk".to_owned(), price: Money::from_cents(119), category: None }];
+/// let current = vec![
+///     InDbProduct::new("Jumbo".to_owned(), ProductInfo::new("Melk".to_owned(), Money::from_cents(11900), "melk".to_owned())),
+/// ];
+/// let (published, report) = AnomalyDetector::new(AnomalyConfig::default()).detect("Jumbo", &previous, current, &Acknowledged::default());
+///
+/// assert!(published.is_empty());
+/// assert_eq!(report.held_back, 1);
+/// ```
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    incremental: bool,
+}
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        AnomalyDetector { config, incremental: false }
+    }
+
+    /// For runs that only scrape the products changed since the previous run. Those are a fraction
+    /// of the catalogue, so only their prices are compared
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Split the products of `store` in the ones that can be published and a report of the anomalies.
+    /// `previous` are the products written by the last run, `acknowledged` the anomalies that are accepted
+    pub fn detect(
+        &self,
+        store: &str,
+        previous: &[PreviousProduct],
+        current: Vec<InDbProduct>,
+        acknowledged: &Acknowledged,
+    ) -> (Vec<InDbProduct>, AnomalyReport) {
+        let mut report = AnomalyReport { previous: previous.len(), current: current.len(), incremental: self.incremental, ..Default::default() };
+        // Nothing to compare with on the first run of a store
+        if !self.config.enabled || previous.is_empty() {
+            return (current, report);
+        }
+
+        let drop_limit = match self.incremental {
+            true => 0,
+            false => previous.len() * (100 - self.config.max_catalogue_drop_percent as usize),
+        };
+        if !self.incremental {
+            report.anomalies.extend(self.vanished_categories(store, previous, &current));
+        }
+        if current.len() * 100 < drop_limit && acknowledged.catalogue_drop {
+            report.released.catalogue_drop = true;
+        } else if current.len() * 100 < drop_limit {
+            report.anomalies.push(Anomaly {
+                store: store.to_owned(),
+                kind: AnomalyKind::CatalogueDrop,
+                product_key: None,
+                category: None,
+                message: format!("{} products, {} in the previous run", current.len(), previous.len()),
+            });
+            report.held_back = current.len();
+            return (Vec::new(), report);
+        }
+
+        let previous_prices: HashMap<&str, Money> = previous.iter().map(|p| (p.key.as_str(), p.price)).collect();
+        let mut published = Vec::with_capacity(current.len());
+        for product in current {
+            let change = previous_prices
+                .get(product.info.key.as_str())
+                .and_then(|old| self.price_change(*old, product.info.price));
+            if change.is_some() && acknowledged.price_changes.contains(&product.info.key) {
+                report.released.price_changes.insert(product.info.key.clone());
+                published.push(product);
+                continue;
+            }
+
+            match change {
+                Some(anomaly) => {
+                    report.held_back += 1;
+                    report.anomalies.push(Anomaly {
+                        store: store.to_owned(),
+                        kind: AnomalyKind::PriceChange,
+                        product_key: Some(product.info.key.clone()),
+                        category: product.info.category_path(),
+                        message: format!("{} ({})", anomaly, product.info.name),
+                    });
+                },
+                None => published.push(product),
+            };
+        }
+        (published, report)
+    }
+
+    // Describes the change when it is larger than allowed
+    fn price_change(&self, old: Money, new: Money) -> Option<String> {
+        if old.cents() <= 0 {
+            return None;
+        }
+        let percent = (new.cents() - old.cents()).abs() * 100 / old.cents();
+        match percent > self.config.max_price_change_percent as i64 {
+            true => Some(format!("price changed {}% from {} to {}", percent, old, new)),
+            false => None,
+        }
+    }
+
+    fn vanished_categories(&self, store: &str, previous: &[PreviousProduct], current: &[InDbProduct]) -> Vec<Anomaly> {
+        let mut previous_sizes: BTreeMap<&str, usize> = BTreeMap::new();
+        for category in previous.iter().filter_map(|p| p.category.as_deref()) {
+            *previous_sizes.entry(category).or_insert(0) += 1;
+        }
+        let current_categories: HashSet<String> = current.iter().filter_map(|p| p.info.category_path()).collect();
+
+        previous_sizes
+            .into_iter()
+            .filter(|(category, size)| *size >= self.config.min_vanished_category_size && !current_categories.contains(*category))
+            .map(|(category, size)| Anomaly {
+                store: store.to_owned(),
+                kind: AnomalyKind::VanishedCategory,
+                product_key: None,
+                category: Some(category.to_owned()),
+                message: format!("'{}' had {} products in the previous run, none now", category, size),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnomalyConfig, InDbProduct, Money, ProductInfo};
+    use super::{Acknowledged, AnomalyDetector, AnomalyKind, PreviousProduct};
+
+    fn previous(key: &str, cents: i64, category: &str) -> PreviousProduct {
+        PreviousProduct { key: key.to_owned(), price: Money::from_cents(cents), category: Some(category.to_owned()) }
+    }
+
+    fn current(key: &str, cents: i64, category: &str) -> InDbProduct {
+        let mut info = ProductInfo::new(key.to_owned(), Money::from_cents(cents), key.to_owned());
+        info.category = vec![category.to_owned()];
+        InDbProduct::new("Jumbo".to_owned(), info)
+    }
+
+    fn kinds(anomalies: &[super::Anomaly]) -> Vec<AnomalyKind> {
+        anomalies.iter().map(|a| a.kind).collect()
+    }
+
+    #[test]
+    fn test_price_change() {
+        let previous = vec![previous("melk", 100, "Zuivel"), previous("kaas", 500, "Zuivel"), previous("brood", 250, "Brood")];
+        let products = vec![current("melk", 149, "Zuivel"), current("kaas", 50, "Zuivel"), current("brood", 300, "Brood")];
+
+        let (published, report) = AnomalyDetector::new(AnomalyConfig::default()).detect("Jumbo", &previous, products, &Acknowledged::default());
+
+        assert_eq!(published.iter().map(|p| p.info.key.as_str()).collect::<Vec<&str>>(), vec!["melk", "brood"]);
+        assert_eq!(kinds(&report.anomalies), vec![AnomalyKind::PriceChange]);
+        assert_eq!(report.anomalies[0].product_key.as_deref(), Some("kaas"));
+        assert_eq!(report.held_back, 1);
+    }
+
+    #[test]
+    fn test_catalogue_drop() {
+        let previous: Vec<PreviousProduct> = (0..10).map(|i| previous(&i.to_string(), 100, "Zuivel")).collect();
+        let products: Vec<InDbProduct> = (0..7).map(|i| current(&i.to_string(), 100, "Zuivel")).collect();
+
+        let (published, report) = AnomalyDetector::new(AnomalyConfig::default()).detect("Jumbo", &previous, products, &Acknowledged::default());
+
+        assert!(published.is_empty());
+        assert_eq!(report.held_back, 7);
+        assert_eq!(kinds(&report.anomalies), vec![AnomalyKind::CatalogueDrop]);
+    }
+
+    #[test]
+    fn test_vanished_category() {
+        let mut previous: Vec<PreviousProduct> = (0..10).map(|i| previous(&i.to_string(), 100, "Zuivel")).collect();
+        previous.extend((10..15).map(|i| self::previous(&i.to_string(), 100, "Brood")));
+        let products: Vec<InDbProduct> = (0..15).map(|i| current(&i.to_string(), 100, "Zuivel")).collect();
+
+        let (published, report) = AnomalyDetector::new(AnomalyConfig::default()).detect("Jumbo", &previous, products, &Acknowledged::default());
+
+        assert_eq!(published.len(), 15);
+        assert_eq!(kinds(&report.anomalies), vec![AnomalyKind::VanishedCategory]);
+        assert_eq!(report.anomalies[0].category.as_deref(), Some("Brood"));
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut previous: Vec<PreviousProduct> = (0..10).map(|i| previous(&i.to_string(), 100, "Zuivel")).collect();
+        previous.extend((10..15).map(|i| self::previous(&i.to_string(), 100, "Brood")));
+        let products = vec![current("0", 100, "Zuivel"), current("1", 500, "Zuivel")];
+
+        let detector = AnomalyDetector::new(AnomalyConfig::default()).with_incremental(true);
+        let (published, report) = detector.detect("Jumbo", &previous, products, &Acknowledged::default());
+
+        assert_eq!(published.len(), 1);
+        assert_eq!(kinds(&report.anomalies), vec![AnomalyKind::PriceChange]);
+        assert!(report.incremental);
+    }
+
+    #[test]
+    fn test_first_run() {
+        let (published, report) = AnomalyDetector::new(AnomalyConfig::default()).detect("Jumbo", &[], vec![current("melk", 100, "Zuivel")], &Acknowledged::default());
+
+        assert_eq!(published.len(), 1);
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_acknowledged() {
+        let previous: Vec<PreviousProduct> = (0..10).map(|i| previous(&i.to_string(), 100, "Zuivel")).collect();
+        let products = || (0..7).map(|i| current(&i.to_string(), if i == 0 { 500 } else { 100 }, "Zuivel")).collect::<Vec<InDbProduct>>();
+        let detector = AnomalyDetector::new(AnomalyConfig::default());
+
+        let acknowledged = Acknowledged { catalogue_drop: true, ..Default::default() };
+        let (published, report) = detector.detect("Jumbo", &previous, products(), &acknowledged);
+        assert_eq!(published.len(), 6);
+        assert_eq!(kinds(&report.anomalies), vec![AnomalyKind::PriceChange]);
+        assert_eq!(report.held_back_keys(), vec!["0"]);
+
+        // The product of the other acknowledged price change wasn't in this run, it stays acknowledged
+        let acknowledged = Acknowledged { catalogue_drop: true, price_changes: ["0".to_owned(), "42".to_owned()].into() };
+        let (published, report) = detector.detect("Jumbo", &previous, products(), &acknowledged);
+        assert_eq!(published.len(), 7);
+        assert!(report.is_clean());
+        assert_eq!(report.released, Acknowledged { catalogue_drop: true, price_changes: ["0".to_owned()].into() });
+    }
+}
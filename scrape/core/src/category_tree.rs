@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use scraper::Html;
+use log::info;
+use anyhow::Result;
+use super::{AsyncExecutor, HtmlLoader, ResultCollector};
+use super::scrape_utils::build_selector;
+
+const DEFAULT_MAX_DEPTH: usize = 5;
+
+/// A page of a store's category tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryPage {
+    /// Category names from the top level down, e.g. ["Zuivel, eieren, boter", "Melk"]
+    pub path: Vec<String>,
+    /// Path of the page relative to the base URL of the store
+    pub url_path: String,
+}
+
+/// Finds the leaves of a category tree whose pages link to their subcategories, e.g.
+/// "/producten/zuivel" links to "/producten/zuivel/melk". The tree is walked level by level,
+/// the pages of a level are loaded through the executor.
+///
+/// # Example
+/// ```no_run
+/// # use scrape_core::{CategoryTree, ReqwestHtmlLoader, SimpleRateLimiter};
+/// # async fn example() {
+/// # let client = reqwest::Client::new();
+/// # let connector = ReqwestHtmlLoader::new(&client);
+/// # let rate_limiter = SimpleRateLimiter::default();
+/// let leaves = CategoryTree::new(&connector, "https://www.jumbo.com", "/producten/categorieen")
+///     .with_excluded(&["aanbiedingen"])
+///     .leaves(&rate_limiter)
+///     .await;
+/// # }
+/// ```
+pub struct CategoryTree<'a, T: HtmlLoader + Send + Sync> {
+    loader: &'a T,
+    base_url: String,
+    root_path: String,
+    max_depth: usize,
+    excluded: Vec<String>,
+}
+
+impl<'a, T: HtmlLoader + Send + Sync> CategoryTree<'a, T> {
+    pub fn new(loader: &'a T, base_url: &str, root_path: &str) -> Self {
+        CategoryTree {
+            loader,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            root_path: root_path.trim_end_matches('/').to_owned(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Categories below this depth aren't visited, the ones at this depth are taken as leaves
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Last path segments that look like a category but aren't, e.g. "merk" for "/producten/merk"
+    pub fn with_excluded(mut self, segments: &[&str]) -> Self {
+        self.excluded = segments.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Categories without subcategories, a page that fails to load ends up as an error
+    pub async fn leaves<R: AsyncExecutor + Send + Sync>(&self, executor: &R) -> ResultCollector<CategoryPage> {
+        let mut leaves = ResultCollector::new();
+        let mut visited: HashSet<String> = HashSet::new();
+        let mut level = vec![CategoryPage { path: Vec::new(), url_path: self.root_path.clone() }];
+
+        for depth in 0..self.max_depth {
+            let futures: Vec<_> = level.iter().map(|category| self.subcategories(category)).collect();
+            let results = executor.run(futures).await;
+            let mut next_level = Vec::new();
+
+            for (category, result) in level.iter().cloned().zip(results) {
+                match result.and_then(|r| r) {
+                    // The root itself is never a leaf
+                    Ok(subcategories) if subcategories.is_empty() && depth > 0 => leaves.successes.push(category),
+                    Ok(subcategories) => next_level.extend(
+                        subcategories.into_iter().filter(|sub| visited.insert(sub.url_path.clone()))
+                    ),
+                    Err(e) => leaves.errors.push(e),
+                };
+            }
+            info!("Found {} categories at depth {} of {}", next_level.len(), depth + 1, self.root_path);
+            if next_level.is_empty() {
+                return leaves;
+            }
+            level = next_level;
+        }
+
+        leaves.successes.extend(level);
+        leaves
+    }
+
+    async fn subcategories(&self, category: &CategoryPage) -> Result<Vec<CategoryPage>> {
+        let document = self.loader.load(format!("{}{}", self.base_url, category.url_path)).await?;
+        Ok(child_links(&document, &category.url_path)?
+            .into_iter()
+            .filter(|(_, url_path)| !self.excluded.iter().any(|e| url_path.rsplit('/').next() == Some(e.as_str())))
+            .map(|(name, url_path)| {
+                let mut path = category.path.clone();
+                path.push(name);
+                CategoryPage { path, url_path }
+            })
+            .collect())
+    }
+}
+
+/// Links to the pages directly below `parent_path`, as the link text and the path of the page. Absolute
+/// links are compared by their path, query strings and fragments are dropped
+pub fn child_links(document: &Html, parent_path: &str) -> Result<Vec<(String, String)>> {
+    let selector = build_selector("a[href]", "category tree")?;
+    let parent_path = parent_path.trim_end_matches('/');
+    let mut children: Vec<(String, String)> = Vec::new();
+
+    for link in document.select(&selector) {
+        let Some(href) = link.value().attr("href") else {
+            continue;
+        };
+        let href = reqwest::Url::parse(href).map(|url| url.path().to_owned()).unwrap_or_else(|_| href.to_owned());
+        let path = href.split(['?', '#']).next().unwrap_or_default().trim_end_matches('/');
+        let is_child = path
+            .strip_prefix(parent_path)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .is_some_and(|segment| !segment.is_empty() && !segment.contains('/'));
+        let name = link.text().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" ");
+        if is_child && !name.is_empty() && !children.iter().any(|(_, p)| p == path) {
+            children.push((name, path.to_owned()));
+        }
+    }
+    Ok(children)
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+    use crate::SimpleRateLimiter;
+    use crate::test_utils::FixtureLoader;
+    use super::{child_links, CategoryTree};
+
+    #[test]
+    fn test_child_links() {
+        let document = Html::parse_document(r#"
+            <a href="/producten/zuivel/melk?sort=price">Melk</a>
+            <a href="https://www.example.com/producten/zuivel/kaas/">Kaas</a>
+            <a href="/producten/zuivel/kaas">Kaas</a>
+            <a href="/producten/zuivel/melk/halfvol">Halfvolle melk</a>
+            <a href="/producten/brood">Brood</a>
+        "#);
+
+        let links = child_links(&document, "/producten/zuivel").unwrap();
+
+        assert_eq!(links, vec![
+            ("Melk".to_owned(), "/producten/zuivel/melk".to_owned()),
+            ("Kaas".to_owned(), "/producten/zuivel/kaas".to_owned()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_leaves() {
+        let loader = FixtureLoader::new([
+            ("https://www.example.com/producten", r#"<a href="/producten/zuivel">Zuivel</a><a href="/producten/brood">Brood</a><a href="/producten/merk">Merken</a>"#),
+            ("https://www.example.com/producten/zuivel", r#"<a href="/producten/brood">Brood</a><a href="/producten/zuivel/melk">Melk</a><a href="/producten/zuivel/kaas">Kaas</a>"#),
+            ("https://www.example.com/producten/brood", ""),
+            ("https://www.example.com/producten/zuivel/melk", ""),
+        ]);
+
+        let leaves = CategoryTree::new(&loader, "https://www.example.com/", "/producten")
+            .with_excluded(&["merk"])
+            .leaves(&SimpleRateLimiter::default())
+            .await;
+
+        let paths: Vec<Vec<String>> = leaves.successes.into_iter().map(|c| c.path).collect();
+        assert_eq!(paths, vec![vec!["Brood".to_owned()], vec!["Zuivel".to_owned(), "Melk".to_owned()]]);
+        assert_eq!(leaves.errors.len(), 1);
+    }
+}
@@ -1,43 +1,127 @@
-// TypeStates
-pub struct HasNot;
-pub struct Has<T>(T);
-pub type HasUInt = Has<usize>;
+mod builder;
+mod store;
+mod loader;
+mod validation;
+mod anomaly;
 
-pub struct ConfigBuilder<R> {
-    max_concurrent_requests: R,
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::{ConfigError, Taxonomy};
+
+pub use builder::ConfigBuilder;
+pub use store::{StoreConfig, DelayRange, RetryPolicy, UserAgentStrategy, DetailPagesConfig, Discovery};
+pub use loader::{CONFIG_PATH_VAR, DEFAULT_CONFIG_PATH};
+pub use validation::{ValidationConfig, Severity, RuleSeverities};
+pub use anomaly::AnomalyConfig;
+
+/// Where the results of a scrape are written to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum OutputSink {
+    Database,
+    /// Write the collected results of each store as JSON to the directory
+    JsonFile { dir: PathBuf },
 }
 
-impl ConfigBuilder<HasNot> {
-    pub fn new() -> Self {
-        Self { max_concurrent_requests: HasNot }
+/// Runtime configuration of the scraper, see `ScrapeConfig::load` to
+/// read it from a TOML file and the environment.
+/// 
+/// # Example
+/// ```toml
+/// max_concurrent_requests = 50
+/// 
+/// [stores.albert_heijn]
+/// delay = { min_ms = 100, max_ms = 5000 }
+/// headers = { Connection = "keep-alive" }
+/// retry = { max_retries = 2, backoff_ms = 1000 }
+/// user_agent = { strategy = "rotate" }
+/// 
+/// [[sinks]]
+/// type = "database"
+///
+/// [validation]
+/// max_price_cents = 50000
+///
+/// [anomalies]
+/// max_price_change_percent = 60
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ScrapeConfig {
+    pub max_concurrent_requests: Option<usize>,
+    pub stores: BTreeMap<String, StoreConfig>,
+    pub sinks: Vec<OutputSink>,
+    pub validation: ValidationConfig,
+    pub anomalies: AnomalyConfig,
+    /// Path of a taxonomy file in the format of `data/taxonomy.toml`, the builtin taxonomy is used when not set
+    pub taxonomy: Option<String>,
+}
+
+impl Default for ScrapeConfig {
+    fn default() -> Self {
+        ScrapeConfig::new(None)
     }
 }
 
-impl ConfigBuilder<HasNot> {
-    pub fn max_concurrent_requests(self, nr: usize) -> ConfigBuilder<HasUInt> {
-        ConfigBuilder {
-            max_concurrent_requests: Has { 0: nr }
+impl ScrapeConfig {
+    pub fn new(max_concurrent_requests: Option<usize>) -> Self {
+        ScrapeConfig {
+            max_concurrent_requests,
+            stores: BTreeMap::new(),
+            sinks: vec![OutputSink::Database],
+            validation: ValidationConfig::default(),
+            anomalies: AnomalyConfig::default(),
+            taxonomy: None,
         }
     }
 
-    pub fn buid(self) -> ScrapeConfig {
-        ScrapeConfig::new(None)
+    /// The configured taxonomy file, or the builtin taxonomy
+    pub fn load_taxonomy(&self) -> Result<Taxonomy, ConfigError> {
+        match &self.taxonomy {
+            Some(path) => Taxonomy::from_file(path),
+            None => Ok(Taxonomy::builtin()),
+        }
     }
-}
 
-impl ConfigBuilder<HasUInt> {
-    pub fn build(self) -> ScrapeConfig {
-        ScrapeConfig::new(Some(self.max_concurrent_requests.0))
+    /// Settings for a store, stores without a section use the defaults
+    pub fn store(&self, name: &str) -> StoreConfig {
+        self.stores.get(name).cloned().unwrap_or_default()
     }
-}
 
-#[derive(Debug)]
-pub struct ScrapeConfig {
-    pub max_concurrent_requests: Option<usize>,
-}
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_concurrent_requests == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                field: "max_concurrent_requests".to_owned(),
+                reason: "must be at least 1".to_owned(),
+            });
+        }
+        if self.sinks.is_empty() {
+            return Err(ConfigError::InvalidValue {
+                field: "sinks".to_owned(),
+                reason: "at least one output sink is required".to_owned(),
+            });
+        }
+        for (name, store) in self.stores.iter() {
+            store.validate(name)?;
+        }
+        self.validation.validate()?;
+        self.anomalies.validate()
+    }
 
-impl ScrapeConfig {
-    pub fn new(max_concurrent_requests: Option<usize>) -> Self {
-        ScrapeConfig { max_concurrent_requests }
+    /// Check that all configured stores are known, stores with a definition file don't need a scraper of their own
+    pub fn validate_stores(&self, known: &[&str]) -> Result<(), ConfigError> {
+        let unknown = self.stores
+            .iter()
+            .find(|(name, store)| store.definition.is_none() && !known.contains(&name.as_str()));
+        match unknown.map(|(name, _)| name) {
+            Some(name) => Err(ConfigError::UnknownStore { store: name.clone(), known: known.join(", ") }),
+            None => Ok(()),
+        }
+    }
+
+    /// The configuration formatted as TOML, used for logging
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap_or_else(|e| format!("{:?} (failed to format: {})", self, e))
     }
 }
\ No newline at end of file
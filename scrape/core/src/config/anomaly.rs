@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use crate::ConfigError;
+
+/// Thresholds for comparing a scrape with the previous run of the same store, see `AnomalyDetector`
+///
+/// # Example
+/// ```toml
+/// [anomalies]
+/// max_price_change_percent = 60
+/// max_catalogue_drop_percent = 20
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct AnomalyConfig {
+    pub enabled: bool,
+    /// Products with a larger price change, up or down, are held back
+    pub max_price_change_percent: u32,
+    /// All products of a run are held back when the catalogue shrinks by more than this
+    pub max_catalogue_drop_percent: u32,
+    /// Categories with fewer products in the previous run aren't reported when they vanish
+    pub min_vanished_category_size: usize,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        AnomalyConfig {
+            enabled: true,
+            max_price_change_percent: 50,
+            max_catalogue_drop_percent: 25,
+            min_vanished_category_size: 5,
+        }
+    }
+}
+
+impl AnomalyConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_catalogue_drop_percent > 100 {
+            return Err(ConfigError::InvalidValue {
+                field: "anomalies.max_catalogue_drop_percent".to_owned(),
+                reason: format!("must be at most 100, got {}", self.max_catalogue_drop_percent),
+            });
+        }
+        Ok(())
+    }
+}
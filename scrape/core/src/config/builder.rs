@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use super::{ScrapeConfig, StoreConfig, OutputSink, ValidationConfig, AnomalyConfig};
+
+// TypeStates
+pub struct HasNot;
+pub struct Has<T>(T);
+pub type HasStores = Has<BTreeMap<String, StoreConfig>>;
+pub type HasSinks = Has<Vec<OutputSink>>;
+
+/// Build a `ScrapeConfig`. Selecting at least one store and one output sink
+/// is required, `build` is only available once both are set.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use scrape_core::{ConfigBuilder, OutputSink, StoreConfig};
+///
+/// let config = ConfigBuilder::new()
+///     .max_concurrent_requests(50)
+///     .store("jumbo", StoreConfig::default().with_request_timeout(Duration::from_secs(10)))
+///     .sink(OutputSink::Database)
+///     .build();
+/// ```
+pub struct ConfigBuilder<S, O> {
+    max_concurrent_requests: Option<usize>,
+    stores: S,
+    sinks: O,
+    validation: Option<ValidationConfig>,
+    anomalies: Option<AnomalyConfig>,
+    taxonomy: Option<String>,
+}
+
+impl ConfigBuilder<HasNot, HasNot> {
+    pub fn new() -> Self {
+        Self { max_concurrent_requests: None, stores: HasNot, sinks: HasNot, validation: None, anomalies: None, taxonomy: None }
+    }
+}
+
+impl Default for ConfigBuilder<HasNot, HasNot> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, O> ConfigBuilder<S, O> {
+    /// Default for stores that don't set their own limit
+    pub fn max_concurrent_requests(mut self, nr: usize) -> Self {
+        self.max_concurrent_requests = Some(nr);
+        self
+    }
+
+    pub fn validation(mut self, config: ValidationConfig) -> Self {
+        self.validation = Some(config);
+        self
+    }
+
+    pub fn anomalies(mut self, config: AnomalyConfig) -> Self {
+        self.anomalies = Some(config);
+        self
+    }
+
+    /// Taxonomy file to use instead of the builtin taxonomy
+    pub fn taxonomy(mut self, path: &str) -> Self {
+        self.taxonomy = Some(path.to_owned());
+        self
+    }
+}
+
+impl<O> ConfigBuilder<HasNot, O> {
+    pub fn store(self, name: &str, config: StoreConfig) -> ConfigBuilder<HasStores, O> {
+        ConfigBuilder {
+            max_concurrent_requests: self.max_concurrent_requests,
+            stores: Has(BTreeMap::from([(name.to_owned(), config)])),
+            sinks: self.sinks,
+            validation: self.validation,
+            anomalies: self.anomalies,
+            taxonomy: self.taxonomy,
+        }
+    }
+}
+
+impl<O> ConfigBuilder<HasStores, O> {
+    pub fn store(mut self, name: &str, config: StoreConfig) -> Self {
+        self.stores.0.insert(name.to_owned(), config);
+        self
+    }
+}
+
+impl<S> ConfigBuilder<S, HasNot> {
+    pub fn sink(self, sink: OutputSink) -> ConfigBuilder<S, HasSinks> {
+        ConfigBuilder {
+            max_concurrent_requests: self.max_concurrent_requests,
+            stores: self.stores,
+            sinks: Has(vec![sink]),
+            validation: self.validation,
+            anomalies: self.anomalies,
+            taxonomy: self.taxonomy,
+        }
+    }
+}
+
+impl<S> ConfigBuilder<S, HasSinks> {
+    pub fn sink(mut self, sink: OutputSink) -> Self {
+        self.sinks.0.push(sink);
+        self
+    }
+}
+
+impl ConfigBuilder<HasStores, HasSinks> {
+    pub fn build(self) -> ScrapeConfig {
+        ScrapeConfig {
+            max_concurrent_requests: self.max_concurrent_requests,
+            stores: self.stores.0,
+            sinks: self.sinks.0,
+            validation: self.validation.unwrap_or_default(),
+            anomalies: self.anomalies.unwrap_or_default(),
+            taxonomy: self.taxonomy,
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::ConfigError;
+use super::{ScrapeConfig, StoreConfig, DelayRange, OutputSink, UserAgentStrategy};
+
+pub const CONFIG_PATH_VAR: &str = "SCRAPE_CONFIG";
+pub const DEFAULT_CONFIG_PATH: &str = "scrape.toml";
+const ENV_PREFIX: &str = "SCRAPE_";
+const GLOBAL_CONCURRENCY_VAR: &str = "MAX_CONCURRENT_REQUESTS";
+const DUMP_DIR_VAR: &str = "DUMP_DIR";
+const TAXONOMY_VAR: &str = "TAXONOMY";
+
+// Store fields that can be overridden with SCRAPE_<STORE>_<FIELD>
+const STORE_FIELDS: [&str; 12] = [
+    "ENABLED",
+    "MAX_CONCURRENT_REQUESTS",
+    "MIN_DELAY_MS",
+    "MAX_DELAY_MS",
+    "REQUEST_TIMEOUT_MS",
+    "CONNECT_TIMEOUT_MS",
+    "MAX_RETRIES",
+    "RETRY_BACKOFF_MS",
+    "USER_AGENT",
+    "BASE_URL",
+    "DISCOVERY",
+    "INCREMENTAL",
+];
+
+impl ScrapeConfig {
+    /// Load the configuration from the TOML file at `$SCRAPE_CONFIG`, or `scrape.toml` when not set,
+    /// and apply overrides from the environment. A missing default file results in the default configuration.
+    /// The resulting configuration is validated.
+    pub fn load() -> Result<Self, ConfigError> {
+        ScrapeConfig::load_onto(ScrapeConfig::default())
+    }
+
+    /// Same as `load`, but values that are not set in the file or the environment are taken from `defaults`
+    pub fn load_onto(defaults: ScrapeConfig) -> Result<Self, ConfigError> {
+        let mut config = match env::var(CONFIG_PATH_VAR) {
+            Ok(path) => defaults.merge_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => defaults.merge_file(DEFAULT_CONFIG_PATH)?,
+            Err(_) => defaults,
+        };
+        config.apply_env(env::vars())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        ScrapeConfig::default().merge_file(path)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// Overwrite the values in this configuration with the values set in the TOML file at `path`
+    pub fn merge_file(self, path: &str) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError::FailedToRead { path: path.to_owned(), err: e.to_string() })?;
+        self.merge_toml(&content)
+            .map_err(|err| ConfigError::FailedToParse { path: path.to_owned(), err })
+    }
+
+    /// Overwrite the values in this configuration with the values set in `content`.
+    /// Tables are merged, other values, including arrays, are replaced
+    pub fn merge_toml(self, content: &str) -> Result<Self, String> {
+        let mut base = toml::Table::try_from(self).map_err(|e| e.to_string())?;
+        let overlay: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+        merge_tables(&mut base, overlay);
+        toml::Value::Table(base).try_into().map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    /// Apply overrides from environment variables. Supported are `SCRAPE_MAX_CONCURRENT_REQUESTS`,
+    /// `SCRAPE_DUMP_DIR`, `SCRAPE_TAXONOMY` and `SCRAPE_<STORE>_<FIELD>`, e.g. `SCRAPE_ALBERT_HEIJN_MIN_DELAY_MS`. Other variables are ignored
+    pub fn apply_env(&mut self, vars: impl Iterator<Item = (String, String)>) -> Result<(), ConfigError> {
+        for (var, value) in vars {
+            let Some(key) = var.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+
+            if key == GLOBAL_CONCURRENCY_VAR {
+                self.max_concurrent_requests = Some(parse_env(&var, &value)?);
+                continue;
+            }
+            if key == DUMP_DIR_VAR {
+                self.sinks.push(OutputSink::JsonFile { dir: PathBuf::from(value) });
+                continue;
+            }
+            if key == TAXONOMY_VAR {
+                self.taxonomy = Some(value);
+                continue;
+            }
+
+            let Some((store, field)) = split_store_key(key) else {
+                continue;
+            };
+            let store_config = self.stores.entry(store).or_default();
+            apply_store_override(store_config, field, &var, &value)?;
+        }
+        Ok(())
+    }
+}
+
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table)
+            },
+            (_, value) => {
+                base.insert(key, value);
+            },
+        };
+    }
+}
+
+fn split_store_key(key: &str) -> Option<(String, &'static str)> {
+    STORE_FIELDS.iter().find_map(|field| {
+        let store = key.strip_suffix(field)?.strip_suffix('_')?;
+        match store.is_empty() {
+            true => None,
+            false => Some((store.to_lowercase(), *field)),
+        }
+    })
+}
+
+fn apply_store_override(config: &mut StoreConfig, field: &str, var: &str, value: &str) -> Result<(), ConfigError> {
+    match field {
+        "ENABLED" => config.enabled = parse_env(var, value)?,
+        "MAX_CONCURRENT_REQUESTS" => config.max_concurrent_requests = Some(parse_env(var, value)?),
+        "MIN_DELAY_MS" => {
+            let min_ms = parse_env(var, value)?;
+            let max_ms = config.delay.map_or(min_ms, |d| d.max_ms);
+            config.delay = Some(DelayRange { min_ms, max_ms });
+        },
+        "MAX_DELAY_MS" => {
+            let max_ms = parse_env(var, value)?;
+            let min_ms = config.delay.map_or(0, |d| d.min_ms);
+            config.delay = Some(DelayRange { min_ms, max_ms });
+        },
+        "REQUEST_TIMEOUT_MS" => config.request_timeout_ms = Some(parse_env(var, value)?),
+        "CONNECT_TIMEOUT_MS" => config.connect_timeout_ms = Some(parse_env(var, value)?),
+        "MAX_RETRIES" => config.retry.max_retries = parse_env(var, value)?,
+        "RETRY_BACKOFF_MS" => config.retry.backoff_ms = parse_env(var, value)?,
+        "USER_AGENT" => config.user_agent = UserAgentStrategy::Fixed { value: value.to_owned() },
+        "BASE_URL" => config.base_url = Some(value.to_owned()),
+        "DISCOVERY" => config.discovery = Some(parse_env(var, value)?),
+        "INCREMENTAL" => config.incremental = parse_env(var, value)?,
+        _ => {},
+    };
+    Ok(())
+}
+
+fn parse_env<T: FromStr>(var: &str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: ToString,
+{
+    value.trim().parse().map_err(|e: T::Err| ConfigError::InvalidEnvValue {
+        var: var.to_owned(),
+        value: value.to_owned(),
+        err: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::{ConfigBuilder, ConfigError, Discovery, OutputSink, ScrapeConfig, Severity, StoreConfig, UserAgentStrategy};
+    use super::super::DelayRange;
+
+    const CONFIG: &str = r#"
+        max_concurrent_requests = 50
+
+        [stores.jumbo]
+        base_url = "https://www.jumbo.com"
+
+        [stores.albert_heijn]
+        delay = { min_ms = 100, max_ms = 5000 }
+        headers = { Connection = "keep-alive" }
+        retry = { max_retries = 2 }
+    "#;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = ScrapeConfig::from_toml(CONFIG).unwrap();
+        let ah = &config.stores["albert_heijn"];
+
+        assert_eq!(config.max_concurrent_requests, Some(50));
+        assert!(config.stores["jumbo"].enabled);
+        assert_eq!(ah.delay, Some(DelayRange { min_ms: 100, max_ms: 5000 }));
+        assert_eq!(ah.retry.max_retries, 2);
+        assert_eq!(ah.retry.backoff_ms, 500);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_merge_toml() {
+        let defaults = ConfigBuilder::new()
+            .max_concurrent_requests(10)
+            .store("albert_heijn", StoreConfig::default().with_header("Host", "www.ah.nl").with_delay(1, 2))
+            .sink(OutputSink::Database)
+            .build();
+
+        let config = defaults.merge_toml(CONFIG).unwrap();
+        let ah = &config.stores["albert_heijn"];
+
+        assert_eq!(config.max_concurrent_requests, Some(50));
+        assert_eq!(ah.delay, Some(DelayRange { min_ms: 100, max_ms: 5000 }));
+        assert_eq!(ah.headers.len(), 2);
+        assert_eq!(config.sinks, vec![OutputSink::Database]);
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        assert!(ScrapeConfig::from_toml("[stores.jumbo]\nconcurency = 5").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = ScrapeConfig::from_toml(CONFIG).unwrap();
+        config.apply_env(vars(&[
+            ("SCRAPE_MAX_CONCURRENT_REQUESTS", "10"),
+            ("SCRAPE_ALBERT_HEIJN_MAX_DELAY_MS", "200"),
+            ("SCRAPE_JUMBO_ENABLED", "false"),
+            ("SCRAPE_PLUS_MAX_CONCURRENT_REQUESTS", "3"),
+            ("SCRAPE_PLUS_USER_AGENT", "test"),
+            ("SCRAPE_DUMP_DIR", "/tmp"),
+            ("SCRAPE_TAXONOMY", "/etc/scrape/taxonomy.toml"),
+            ("SCRAPE_JUMBO_DISCOVERY", "categories"),
+            ("SCRAPE_JUMBO_INCREMENTAL", "true"),
+            ("HOME", "/root"),
+        ])).unwrap();
+
+        assert_eq!(config.max_concurrent_requests, Some(10));
+        assert_eq!(config.stores["albert_heijn"].delay, Some(DelayRange { min_ms: 100, max_ms: 200 }));
+        assert!(!config.stores["jumbo"].enabled);
+        assert_eq!(config.stores["jumbo"].discovery, Some(Discovery::Categories));
+        assert!(config.stores["jumbo"].incremental);
+        assert_eq!(config.taxonomy.as_deref(), Some("/etc/scrape/taxonomy.toml"));
+        assert_eq!(config.stores["plus"].max_concurrent_requests, Some(3));
+        assert_eq!(config.stores["plus"].user_agent, UserAgentStrategy::Fixed { value: "test".to_owned() });
+        assert_eq!(config.sinks, vec![OutputSink::Database, OutputSink::JsonFile { dir: PathBuf::from("/tmp") }]);
+    }
+
+    #[test]
+    fn test_invalid_env_value() {
+        let mut config = ScrapeConfig::default();
+        let result = config.apply_env(vars(&[("SCRAPE_JUMBO_ENABLED", "yes")]));
+
+        assert!(matches!(result, Err(ConfigError::InvalidEnvValue { .. })));
+    }
+
+    #[test]
+    fn test_taxonomy() {
+        assert!(ScrapeConfig::default().load_taxonomy().is_ok());
+
+        let config = ScrapeConfig::from_toml("taxonomy = \"does/not/exist.toml\"").unwrap();
+        assert!(matches!(config.load_taxonomy(), Err(ConfigError::FailedToRead { .. })));
+    }
+
+    #[test]
+    fn test_discovery() {
+        let config = ScrapeConfig::from_toml("[stores.jumbo]\ndiscovery = \"categories\"").unwrap();
+        let supported = [Discovery::Listing, Discovery::Categories];
+
+        assert_eq!(config.stores["jumbo"].discovery_of("jumbo", &supported).unwrap(), Discovery::Categories);
+        assert_eq!(StoreConfig::default().discovery_of("jumbo", &supported).unwrap(), Discovery::Listing);
+        assert!(config.stores["jumbo"].discovery_of("jumbo", &[Discovery::Listing]).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = ScrapeConfig::from_toml(CONFIG).unwrap();
+        config.apply_env(vars(&[("SCRAPE_ALBERT_HEIJN_MIN_DELAY_MS", "6000")])).unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidValue { .. })));
+
+        let config = ScrapeConfig::from_toml("[stores.jumbo]\nbase_url = \"ftp://jumbo.com\"").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidValue { .. })));
+
+        let config = ScrapeConfig::from_toml("[stores.jumbo]\nheaders = { \"bad header\" = \"x\" }").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidValue { .. })));
+
+        let config = ScrapeConfig::from_toml("sinks = []").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidValue { .. })));
+
+        let config = ScrapeConfig::from_toml("[anomalies]\nmax_catalogue_drop_percent = 150").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidValue { .. })));
+
+        let config = ScrapeConfig::from_toml("[stores.jumbo.details]\nenabled = true\nmax_concurrent_requests = 0").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn test_validation_section() {
+        let config = ScrapeConfig::from_toml("[validation]\nmax_price_cents = 500\nrules = { unit_price = \"off\" }").unwrap();
+
+        assert_eq!(config.validation.max_price_cents, 500);
+        assert_eq!(config.validation.min_price_cents, 1);
+        assert_eq!(config.validation.rules.unit_price, Severity::Off);
+        assert_eq!(config.validation.rules.empty_name, Severity::Error);
+
+        let config = ScrapeConfig::from_toml("[validation]\nmin_price_cents = 1000\nmax_price_cents = 500").unwrap();
+        assert!(matches!(config.validate(), Err(ConfigError::InvalidValue { .. })));
+        assert!(ScrapeConfig::from_toml("[validation]\nrules = { empty_name = \"fatal\" }").is_err());
+    }
+
+    #[test]
+    fn test_validate_stores() {
+        let config = ScrapeConfig::from_toml(CONFIG).unwrap();
+
+        assert!(config.validate_stores(&["jumbo", "albert_heijn"]).is_ok());
+        assert!(matches!(config.validate_stores(&["jumbo"]), Err(ConfigError::UnknownStore { .. })));
+
+        let config = ScrapeConfig::from_toml("[stores.dirk]\ndefinition = \"stores/dirk.toml\"").unwrap();
+        assert!(config.validate_stores(&["jumbo"]).is_ok());
+    }
+}
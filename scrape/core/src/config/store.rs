@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use crate::{ConfigError, RateLimiter, RandomDelayRateLimiter, SimpleRateLimiter};
+
+/// Random delay in milliseconds before each request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DelayRange {
+    pub min_ms: usize,
+    pub max_ms: usize,
+}
+
+/// How often a failed request is retried, the backoff grows linearly with each attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct RetryPolicy {
+    pub max_retries: usize,
+    pub backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_retries: 0, backoff_ms: 500 }
+    }
+}
+
+impl RetryPolicy {
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        Duration::from_millis(self.backoff_ms * attempt as u64)
+    }
+}
+
+/// How the user agent header is picked for each request
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case", deny_unknown_fields)]
+pub enum UserAgentStrategy {
+    /// Pick a random user agent from the built-in list for each request
+    #[default]
+    Random,
+    /// Cycle through the built-in list
+    Rotate,
+    /// Always use the same user agent
+    Fixed { value: String },
+}
+
+/// How a store scraper finds its products. Stores support a subset of these and have their own default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Discovery {
+    /// Paging through the listing of all products
+    Listing,
+    /// Paging through every leaf of the store's category tree
+    Categories,
+    /// Paging through the page of every brand
+    Brands,
+    /// Visiting the product URLs listed in the store's sitemaps
+    Sitemap,
+}
+
+impl Discovery {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Discovery::Listing => "listing",
+            Discovery::Categories => "categories",
+            Discovery::Brands => "brands",
+            Discovery::Sitemap => "sitemap",
+        }
+    }
+}
+
+impl FromStr for Discovery {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "listing" => Ok(Discovery::Listing),
+            "categories" => Ok(Discovery::Categories),
+            "brands" => Ok(Discovery::Brands),
+            "sitemap" => Ok(Discovery::Sitemap),
+            other => Err(format!("unknown discovery '{}'", other)),
+        }
+    }
+}
+
+/// Visiting the page of each product after the listings, for stores that support it. The detail
+/// pages get their own rate limiter, so they can be requested slower than the listings
+///
+/// # Example
+/// ```toml
+/// [stores.jumbo.details]
+/// enabled = true
+/// max_concurrent_requests = 2
+/// delay = { min_ms = 500, max_ms = 2000 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct DetailPagesConfig {
+    pub enabled: bool,
+    pub max_concurrent_requests: usize,
+    pub delay: Option<DelayRange>,
+    /// Only visit products that are new or changed price since the previous run
+    pub only_changed: bool,
+}
+
+impl Default for DetailPagesConfig {
+    fn default() -> Self {
+        DetailPagesConfig {
+            enabled: false,
+            max_concurrent_requests: 4,
+            delay: None,
+            only_changed: true,
+        }
+    }
+}
+
+impl DetailPagesConfig {
+    pub fn rate_limiter(&self) -> RateLimiter {
+        match self.delay {
+            Some(delay) => RateLimiter::RandomDelay(
+                RandomDelayRateLimiter::new(Some(self.max_concurrent_requests), delay.min_ms, delay.max_ms)
+            ),
+            None => RateLimiter::Simple(SimpleRateLimiter::new(Some(self.max_concurrent_requests))),
+        }
+    }
+}
+
+/// Settings for a single store scraper
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct StoreConfig {
+    pub enabled: bool,
+    /// Falls back to the global `max_concurrent_requests` when not set
+    pub max_concurrent_requests: Option<usize>,
+    pub delay: Option<DelayRange>,
+    pub request_timeout_ms: Option<u64>,
+    pub connect_timeout_ms: Option<u64>,
+    pub retry: RetryPolicy,
+    pub user_agent: UserAgentStrategy,
+    pub headers: BTreeMap<String, String>,
+    /// Falls back to the scraper's own base URL when not set
+    pub base_url: Option<String>,
+    /// Path of a `StoreDefinition` file, the store is then scraped by the `DeclarativeScraper`
+    pub definition: Option<String>,
+    pub details: DetailPagesConfig,
+    /// Falls back to the scraper's own default when not set
+    pub discovery: Option<Discovery>,
+    /// Only visit the sitemap entries modified since the previous run of the store, the other
+    /// products keep what was stored. Only used with the sitemap discovery
+    pub incremental: bool,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            enabled: true,
+            max_concurrent_requests: None,
+            delay: None,
+            request_timeout_ms: None,
+            connect_timeout_ms: None,
+            retry: RetryPolicy::default(),
+            user_agent: UserAgentStrategy::default(),
+            headers: BTreeMap::new(),
+            base_url: None,
+            definition: None,
+            details: DetailPagesConfig::default(),
+            discovery: None,
+            incremental: false,
+        }
+    }
+}
+
+impl StoreConfig {
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn with_max_concurrent_requests(mut self, nr: usize) -> Self {
+        self.max_concurrent_requests = Some(nr);
+        self
+    }
+
+    pub fn with_delay(mut self, min_ms: usize, max_ms: usize) -> Self {
+        self.delay = Some(DelayRange { min_ms, max_ms });
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    pub fn with_retry(mut self, max_retries: usize, backoff: Duration) -> Self {
+        self.retry = RetryPolicy { max_retries, backoff_ms: backoff.as_millis() as u64 };
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: UserAgentStrategy) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_owned(), value.to_owned());
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.to_owned());
+        self
+    }
+
+    pub fn with_definition(mut self, path: &str) -> Self {
+        self.definition = Some(path.to_owned());
+        self
+    }
+
+    pub fn with_details(mut self, details: DetailPagesConfig) -> Self {
+        self.details = details;
+        self
+    }
+
+    pub fn with_discovery(mut self, discovery: Discovery) -> Self {
+        self.discovery = Some(discovery);
+        self
+    }
+
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// The configured discovery when `supported` has it, the first of `supported` when none is configured
+    pub fn discovery_of(&self, store: &str, supported: &[Discovery]) -> Result<Discovery, ConfigError> {
+        match self.discovery {
+            None => supported.first().copied().ok_or_else(|| ConfigError::InvalidValue {
+                field: format!("stores.{}.discovery", store),
+                reason: "the store has no discovery".to_owned(),
+            }),
+            Some(discovery) if supported.contains(&discovery) => Ok(discovery),
+            Some(discovery) => Err(ConfigError::InvalidValue {
+                field: format!("stores.{}.discovery", store),
+                reason: format!(
+                    "'{}' is not supported, use one of {}",
+                    discovery.as_str(),
+                    supported.iter().map(|d| d.as_str()).collect::<Vec<&str>>().join(", "),
+                ),
+            }),
+        }
+    }
+
+    pub fn validate(&self, store: &str) -> Result<(), ConfigError> {
+        let invalid = |field: &str, reason: String| ConfigError::InvalidValue {
+            field: format!("stores.{}.{}", store, field),
+            reason,
+        };
+
+        if self.max_concurrent_requests == Some(0) {
+            return Err(invalid("max_concurrent_requests", "must be at least 1".to_owned()));
+        }
+        if let Some(delay) = self.delay {
+            if delay.min_ms > delay.max_ms {
+                return Err(invalid("delay", format!("min_ms ({}) is larger than max_ms ({})", delay.min_ms, delay.max_ms)));
+            }
+        }
+        if self.details.max_concurrent_requests == 0 {
+            return Err(invalid("details.max_concurrent_requests", "must be at least 1".to_owned()));
+        }
+        if let Some(delay) = self.details.delay {
+            if delay.min_ms > delay.max_ms {
+                return Err(invalid("details.delay", format!("min_ms ({}) is larger than max_ms ({})", delay.min_ms, delay.max_ms)));
+            }
+        }
+        if self.request_timeout_ms == Some(0) {
+            return Err(invalid("request_timeout_ms", "must be larger than 0".to_owned()));
+        }
+        if self.connect_timeout_ms == Some(0) {
+            return Err(invalid("connect_timeout_ms", "must be larger than 0".to_owned()));
+        }
+        if let Some(base_url) = &self.base_url {
+            let url = reqwest::Url::parse(base_url).map_err(|e| invalid("base_url", e.to_string()))?;
+            if !["http", "https"].contains(&url.scheme()) {
+                return Err(invalid("base_url", format!("unsupported scheme '{}'", url.scheme())));
+            }
+        }
+        if let UserAgentStrategy::Fixed { value } = &self.user_agent {
+            HeaderValue::from_str(value).map_err(|e| invalid("user_agent", e.to_string()))?;
+        }
+        self.header_map().map_err(|e| invalid("headers", e.to_string()))?;
+        Ok(())
+    }
+
+    /// The configured headers as a `HeaderMap`, fails on invalid header names or values
+    pub fn header_map(&self) -> anyhow::Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers.iter() {
+            headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+        }
+        Ok(headers)
+    }
+
+    /// Build a request client using the configured headers and timeouts
+    pub fn build_client(&self) -> anyhow::Result<reqwest::Client> {
+        let mut builder = reqwest::ClientBuilder::new()
+            .default_headers(self.header_map()?)
+            .gzip(true);
+
+        if let Some(timeout) = self.request_timeout_ms {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = self.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(timeout));
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Build the rate limiter for this store, a random delay is only applied when configured
+    pub fn rate_limiter(&self, default_concurrent_requests: Option<usize>) -> RateLimiter {
+        let concurrent_requests = self.max_concurrent_requests.or(default_concurrent_requests);
+        match self.delay {
+            Some(delay) => RateLimiter::RandomDelay(
+                RandomDelayRateLimiter::new(concurrent_requests, delay.min_ms, delay.max_ms)
+            ),
+            None => RateLimiter::Simple(SimpleRateLimiter::new(concurrent_requests)),
+        }
+    }
+
+    pub fn base_url_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.base_url.as_deref().unwrap_or(default)
+    }
+}
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use crate::ConfigError;
+
+/// What happens with a product that breaks a validation rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The rule isn't checked
+    Off,
+    /// The issue is reported, the product is still written
+    Warning,
+    /// The issue is reported and the product isn't written
+    Error,
+}
+
+/// Severity of each validation rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct RuleSeverities {
+    pub price_bounds: Severity,
+    pub empty_name: Severity,
+    pub url_host: Severity,
+    pub duplicate_key: Severity,
+    pub unit_price: Severity,
+}
+
+impl Default for RuleSeverities {
+    fn default() -> Self {
+        RuleSeverities {
+            price_bounds: Severity::Error,
+            empty_name: Severity::Error,
+            url_host: Severity::Error,
+            duplicate_key: Severity::Warning,
+            unit_price: Severity::Warning,
+        }
+    }
+}
+
+/// Sanity checks on scraped products before they are written, see `Validator`
+///
+/// # Example
+/// ```toml
+/// [validation]
+/// max_price_cents = 50000
+/// rules = { unit_price = "off", duplicate_key = "error" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ValidationConfig {
+    pub min_price_cents: i64,
+    pub max_price_cents: i64,
+    pub max_unit_price_cents: i64,
+    /// How far the unit price shown by the store may be off from the one calculated from the package size
+    pub unit_price_tolerance_percent: u32,
+    pub rules: RuleSeverities,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            min_price_cents: 1,
+            max_price_cents: 100_000,
+            max_unit_price_cents: 500_000,
+            unit_price_tolerance_percent: 25,
+            rules: RuleSeverities::default(),
+        }
+    }
+}
+
+impl ValidationConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field: &str, reason: String| ConfigError::InvalidValue {
+            field: format!("validation.{}", field),
+            reason,
+        };
+
+        if self.min_price_cents > self.max_price_cents {
+            return Err(invalid(
+                "min_price_cents",
+                format!("larger than max_price_cents ({} > {})", self.min_price_cents, self.max_price_cents),
+            ));
+        }
+        if self.max_unit_price_cents <= 0 {
+            return Err(invalid("max_unit_price_cents", "must be larger than 0".to_owned()));
+        }
+        Ok(())
+    }
+}
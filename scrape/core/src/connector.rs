@@ -1,45 +1,107 @@
-use super::{HtmlLoader, ScrapeError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::{HtmlLoader, ScrapeError, RetryPolicy, UserAgentStrategy};
 use anyhow::Result;
-use rand::{Rng, SeedableRng};
-use super::constants::{USER_AGENTS, SEED32};
+use rand::Rng;
+use super::constants::USER_AGENTS;
 use reqwest::header::{USER_AGENT, ACCEPT_LANGUAGE, REFERER, ACCEPT_ENCODING};
 
 pub struct ReqwestHtmlLoader<'a> {
     client: &'a reqwest::Client,
+    retry_policy: RetryPolicy,
+    user_agent: UserAgentStrategy,
+    requests: AtomicUsize,
 }
 
 impl<'a> ReqwestHtmlLoader<'a> {
     pub fn new(client: &'a reqwest::Client) -> Self {
-        Self { client }
+        Self {
+            client,
+            retry_policy: RetryPolicy::default(),
+            user_agent: UserAgentStrategy::default(),
+            requests: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: UserAgentStrategy) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    fn pick_user_agent(&self) -> &str {
+        match &self.user_agent {
+            UserAgentStrategy::Random => USER_AGENTS[rand::thread_rng().gen_range(0..USER_AGENTS.len())],
+            UserAgentStrategy::Rotate => USER_AGENTS[self.requests.fetch_add(1, Ordering::Relaxed) % USER_AGENTS.len()],
+            UserAgentStrategy::Fixed { value } => value,
+        }
     }
 }
 
 impl<'a> HtmlLoader for ReqwestHtmlLoader<'a> {
     async fn load(&self, url: String) -> Result<scraper::Html> {
-        Ok(get_html_document_from_url(self.client, url).await?)
+        let mut attempt = 0;
+        let html_content = loop {
+            match get_html_content_from_url(self.client, &url, self.pick_user_agent()).await {
+                Err(_) if attempt < self.retry_policy.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+                },
+                result => break result?,
+            };
+        };
+        Ok(scraper::Html::parse_document(&html_content))
     }
-}
 
-fn get_rand_user_agent() -> &'static str {
-    let idx = rand::rngs::StdRng::from_seed(SEED32).gen_range(0..USER_AGENTS.len());
-    USER_AGENTS[idx]
+    async fn load_bytes(&self, url: String) -> Result<Vec<u8>> {
+        let mut attempt = 0;
+        loop {
+            match get_bytes_from_url(self.client, &url, self.pick_user_agent()).await {
+                Err(_) if attempt < self.retry_policy.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+                },
+                result => return result,
+            };
+        }
+    }
 }
 
-async fn get_html_document_from_url(client: &reqwest::Client, url: String) -> Result<scraper::Html> {
-    // Fetch a html document using a client
-    let user_agent = get_rand_user_agent();
-    let html_content = client
-        .get(&url)
+fn get_request(client: &reqwest::Client, url: &str, user_agent: &str) -> reqwest::RequestBuilder {
+    client
+        .get(url)
         .header(USER_AGENT, user_agent)
         .header(ACCEPT_LANGUAGE, "en-US,en;q=0.5" )
         .header(REFERER, "https://google.com/")
         .header(ACCEPT_ENCODING, "gzip, deflate, br")
+}
+
+async fn get_html_content_from_url(client: &reqwest::Client, url: &str, user_agent: &str) -> Result<String> {
+    // Fetch the content of a html document using a client
+    let html_content = get_request(client, url, user_agent)
         .send()
         .await
-        .map_err(|e| ScrapeError::FailedToConnect { url: url.clone(), err: e.to_string() })?
+        .map_err(|e| ScrapeError::FailedToConnect { url: url.to_owned(), err: e.to_string() })?
         .text()
         .await
-        .map_err(|e| ScrapeError::FailedToParseHtml { url: url.clone(), err: e.to_string() })?;
+        .map_err(|e| ScrapeError::FailedToParseHtml { url: url.to_owned(), err: e.to_string() })?;
+
+    Ok(html_content)
+}
+
+async fn get_bytes_from_url(client: &reqwest::Client, url: &str, user_agent: &str) -> Result<Vec<u8>> {
+    let bytes = get_request(client, url, user_agent)
+        .send()
+        .await
+        .map_err(|e| ScrapeError::FailedToConnect { url: url.to_owned(), err: e.to_string() })?
+        .error_for_status()
+        .map_err(|e| ScrapeError::FailedToConnect { url: url.to_owned(), err: e.to_string() })?
+        .bytes()
+        .await
+        .map_err(|e| ScrapeError::FailedToConnect { url: url.to_owned(), err: e.to_string() })?;
 
-    Ok(scraper::Html::parse_document(&html_content))
+    Ok(bytes.to_vec())
 }
\ No newline at end of file
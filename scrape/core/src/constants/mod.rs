@@ -1,4 +1,3 @@
 mod user_agents;
 
-pub use user_agents::USER_AGENTS;
-pub const SEED32: [u8; 32] = [1, 2, 3, 4, 3, 2, 1, 0, 10, 11, 12, 9, 1, 2, 3, 4, 3, 2, 1, 0, 10, 11, 12, 9, 1, 2, 3, 4, 3, 2, 1, 0];
\ No newline at end of file
+pub use user_agents::USER_AGENTS;
\ No newline at end of file
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use anyhow::Result;
+use log::info;
+use regex::Regex;
+use reqwest::Url;
+use scraper::Html;
+use super::{AsyncExecutor, ConfigError, HtmlLoader, ResultCollector};
+use super::scrape_utils::build_selector;
+
+const DEFAULT_MAX_DEPTH: usize = 3;
+const DEFAULT_MAX_PAGES: usize = 10_000;
+// Query parameters that only tell where a visitor came from, they don't change the page
+const TRACKING_PARAMS: [&str; 7] = ["gclid", "fbclid", "msclkid", "dclid", "mc_cid", "mc_eid", "_ga"];
+const TRACKING_PREFIX: &str = "utm_";
+
+/// What the callback found on a crawled page: items to keep and links to follow
+#[derive(Debug)]
+pub struct Visit<O> {
+    pub items: Vec<O>,
+    /// Absolute, or relative to the normalized page URL, which has no trailing slash
+    pub links: Vec<String>,
+}
+
+impl<O> Visit<O> {
+    pub fn new(items: Vec<O>, links: Vec<String>) -> Self {
+        Visit { items, links }
+    }
+}
+
+/// Crawls a store by following links from the start URLs. Every URL is normalized before it is
+/// queued, so a page is only loaded once however it is linked. Pages are loaded level by level
+/// through the executor, only links on the host of the start URLs are followed.
+///
+/// # Example
+/// ```no_run
+/// # use scrape_core::{CrawlFrontier, ReqwestHtmlLoader, SimpleRateLimiter, Visit, page_links};
+/// # fn parse_products(_document: &scraper::Html, url: &str) -> anyhow::Result<Vec<String>> { Ok(vec![url.to_owned()]) }
+/// # async fn example() -> anyhow::Result<()> {
+/// # let client = reqwest::Client::new();
+/// # let connector = ReqwestHtmlLoader::new(&client);
+/// # let rate_limiter = SimpleRateLimiter::default();
+/// let products = CrawlFrontier::new(&connector, &["https://www.example.com/producten"])
+///     .with_max_depth(4)
+///     .with_include(&["/producten/"])?
+///     .with_exclude(&["/recepten/"])?
+///     .crawl(&rate_limiter, |document, url| {
+///         Ok(Visit::new(parse_products(document, url)?, page_links(document)?))
+///     })
+///     .await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CrawlFrontier<'a, T: HtmlLoader + Send + Sync> {
+    loader: &'a T,
+    start_urls: Vec<String>,
+    max_depth: usize,
+    max_pages: usize,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl<'a, T: HtmlLoader + Send + Sync> CrawlFrontier<'a, T> {
+    pub fn new(loader: &'a T, start_urls: &[&str]) -> Self {
+        CrawlFrontier {
+            loader,
+            start_urls: start_urls.iter().filter_map(|url| canonical_crawl_url(url)).collect(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_pages: DEFAULT_MAX_PAGES,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Links on pages at this depth aren't followed, the start URLs are at depth 0
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Safety cap on the number of pages loaded
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Regexes of which at least one must match a link before it is followed
+    pub fn with_include(mut self, patterns: &[&str]) -> Result<Self, ConfigError> {
+        self.include = compile(patterns, "include")?;
+        Ok(self)
+    }
+
+    /// Regexes of links that are never followed
+    pub fn with_exclude(mut self, patterns: &[&str]) -> Result<Self, ConfigError> {
+        self.exclude = compile(patterns, "exclude")?;
+        Ok(self)
+    }
+
+    /// Whether a normalized URL may be queued
+    pub fn allows(&self, url: &str) -> bool {
+        let same_host = Url::parse(url)
+            .ok()
+            .is_some_and(|url| self.start_urls.iter().any(|start| Url::parse(start).ok().is_some_and(|s| s.host_str() == url.host_str())));
+        same_host
+            && (self.include.is_empty() || self.include.iter().any(|pattern| pattern.is_match(url)))
+            && !self.exclude.iter().any(|pattern| pattern.is_match(url))
+    }
+
+    /// Items of all crawled pages, a page that fails to load or parse ends up as an error
+    pub async fn crawl<O, V, R>(&self, executor: &R, visit: V) -> ResultCollector<O>
+    where
+        O: Send + Sync,
+        V: Fn(&Html, &str) -> Result<Visit<O>> + Send + Sync,
+        R: AsyncExecutor + Send + Sync,
+    {
+        let mut items = ResultCollector::new();
+        let mut visited: HashSet<String> = self.start_urls.iter().cloned().collect();
+        let mut level: Vec<String> = visited.iter().cloned().collect();
+        level.sort();
+        let mut loaded = 0;
+
+        for depth in 0..=self.max_depth {
+            level.truncate(self.max_pages - loaded);
+            loaded += level.len();
+            let futures: Vec<_> = level.iter().map(|url| self.visit_page(url, &visit)).collect();
+            let results = executor.run(futures).await;
+            let mut next_level = Vec::new();
+
+            for (url, result) in level.iter().zip(results) {
+                match result.and_then(|r| r) {
+                    Ok(page) => {
+                        items.successes.extend(page.items);
+                        if depth < self.max_depth {
+                            next_level.extend(
+                                page.links
+                                    .iter()
+                                    .filter_map(|link| Url::parse(url).ok()?.join(link.trim()).ok())
+                                    .filter_map(|link| canonical_crawl_url(link.as_str()))
+                                    .filter(|link| self.allows(link) && visited.insert(link.clone()))
+                            );
+                        }
+                    },
+                    Err(e) => items.errors.push(e),
+                };
+            }
+            info!("Crawled {} pages at depth {}, {} new links", level.len(), depth, next_level.len());
+            if next_level.is_empty() || loaded >= self.max_pages {
+                break;
+            }
+            level = next_level;
+        }
+        items
+    }
+
+    async fn visit_page<O, V>(&self, url: &str, visit: &V) -> Result<Visit<O>>
+    where
+        V: Fn(&Html, &str) -> Result<Visit<O>> + Send + Sync,
+    {
+        let document = self.loader.load(url.to_owned()).await?;
+        visit(&document, url)
+    }
+}
+
+fn compile(patterns: &[&str], field: &str) -> Result<Vec<Regex>, ConfigError> {
+    patterns
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(|e| ConfigError::InvalidValue { field: format!("crawl.{}", field), reason: e.to_string() }))
+        .collect()
+}
+
+/// The `href` of every link on a page, for the visit callback of `CrawlFrontier`
+pub fn page_links(document: &Html) -> Result<Vec<String>> {
+    let selector = build_selector("a[href]", "crawl frontier")?;
+    Ok(document.select(&selector).filter_map(|link| link.value().attr("href")).map(str::to_owned).collect())
+}
+
+/// One spelling of a URL for the crawl: no fragment, tracking parameters or trailing slash, and the
+/// query sorted. Unlike `scrape_utils::normalize_url` the query is kept, pages of a listing differ by it.
+/// Only http(s) URLs are kept
+pub fn canonical_crawl_url(url: &str) -> Option<String> {
+    let mut url = Url::parse(url.trim()).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    url.set_fragment(None);
+
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with(TRACKING_PREFIX) && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    query.sort();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+
+    let path = url.path().trim_end_matches('/').to_owned();
+    url.set_path(if path.is_empty() { "/" } else { &path });
+    Some(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SimpleRateLimiter;
+    use crate::test_utils::FixtureLoader;
+    use super::{canonical_crawl_url, page_links, CrawlFrontier, Visit};
+
+    #[test]
+    fn test_canonical_crawl_url() {
+        assert_eq!(
+            canonical_crawl_url("https://WWW.Example.com/producten/?sort=price&utm_source=mail&page=2#top").as_deref(),
+            Some("https://www.example.com/producten?page=2&sort=price"),
+        );
+        assert_eq!(canonical_crawl_url("https://www.example.com/?gclid=abc").as_deref(), Some("https://www.example.com/"));
+        assert_eq!(canonical_crawl_url("mailto:info@example.com"), None);
+    }
+
+    #[tokio::test]
+    async fn test_crawl() {
+        let loader = FixtureLoader::new([
+            ("https://www.example.com/producten", r#"
+                <a href="/producten/melk/">Melk</a>
+                <a href="/producten/melk?utm_campaign=x">Melk</a>
+                <a href="/recepten/pannenkoeken">Recept</a>
+                <a href="https://www.other.com/producten/kaas">Kaas</a>
+                <a href="/producten/kapot">Kapot</a>
+            "#),
+            ("https://www.example.com/producten/melk", r#"<a href="/producten">Terug</a><a href="/producten/melk/halfvol">Halfvol</a>"#),
+            ("https://www.example.com/producten/melk/halfvol", ""),
+        ]);
+
+        let frontier = CrawlFrontier::new(&loader, &["https://www.example.com/producten/"])
+            .with_max_depth(1)
+            .with_exclude(&["/recepten/"])
+            .unwrap();
+        let crawled = frontier
+            .crawl(&SimpleRateLimiter::default(), |document, url| Ok(Visit::new(vec![url.to_owned()], page_links(document)?)))
+            .await;
+
+        assert_eq!(crawled.successes, vec!["https://www.example.com/producten", "https://www.example.com/producten/melk"]);
+        assert_eq!(crawled.errors.len(), 1);
+        assert!(CrawlFrontier::new(&loader, &[]).with_include(&["(["]).is_err());
+    }
+}
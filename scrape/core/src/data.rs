@@ -1,32 +1,212 @@
+use serde::Serialize;
+use super::{Money, Quantity, ComparablePrice, Offer};
+use super::scrape_utils::normalize_url;
+use super::text::search_string;
+
 #[derive(Debug)]
 pub struct InDbProduct {
     pub store: String,
     pub info: ProductInfo,
+    /// Path in the shared taxonomy, e.g. "Zuivel > Melk", see `Taxonomy::assign`
+    pub taxonomy: Option<String>,
 }
 
 impl InDbProduct {
     pub fn new(store: String, info: ProductInfo) -> Self {
-        Self { store, info }
+        Self { store, info, taxonomy: None }
     }
 
     pub fn db_search_string(&self) -> String {
-        self.info.name.to_lowercase()
+        search_string(&self.info.name)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum Availability {
+    Available,
+    Unavailable,
+    #[default]
+    Unknown,
+}
+
+impl Availability {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Availability::Available => Some(true),
+            Availability::Unavailable => Some(false),
+            Availability::Unknown => None,
+        }
+    }
+}
+
+/// Price per unit as shown by the store, e.g. "€ 2,58 per kg"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnitPrice {
+    pub price: Money,
+    pub unit: String,
+}
+
+impl UnitPrice {
+    /// Parse texts like "€ 2,58 per kg", "2.58/kg" or "prijs per liter 1,20"
+    pub fn parse(text: &str) -> Option<UnitPrice> {
+        let lowered = text.to_lowercase().replace("prijs", "");
+        let (left, right) = lowered
+            .split_once(" per ")
+            .or_else(|| lowered.split_once('/'))
+            .or_else(|| lowered.split_once("per "))?;
+
+        let (price, unit) = match Money::parse(left.trim()) {
+            Ok(price) => (price, right),
+            Err(_) => {
+                // The price comes after the unit, e.g. "per kg € 2,58"
+                let (unit, price) = right.trim().rsplit_once(|c: char| c.is_whitespace() || c == '€')?;
+                (Money::parse(price).ok()?, unit)
+            },
+        };
+
+        let unit = unit.trim().trim_end_matches('.').trim_end_matches('€').trim().to_owned();
+        match unit.is_empty() {
+            true => None,
+            false => Some(UnitPrice { price, unit }),
+        }
+    }
+}
+
+/// A row of the nutrition table on a product page, values are kept as shown, e.g. "3,5 g"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NutritionValue {
+    pub name: String,
+    pub per_100: Option<String>,
+    pub per_portion: Option<String>,
+}
+
+/// Information that is only shown on the page of a single product
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct ProductDetails {
+    pub description: Option<String>,
+    pub ingredients: Option<String>,
+    pub allergens: Vec<String>,
+    pub nutrition: Vec<NutritionValue>,
+}
+
+impl ProductDetails {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none() && self.ingredients.is_none() && self.allergens.is_empty() && self.nutrition.is_empty()
+    }
+
+    /// The nutrition table as a JSON array, as it's stored in the database
+    pub fn nutrition_json(&self) -> Option<String> {
+        match self.nutrition.is_empty() {
+            true => None,
+            false => serde_json::to_string(&self.nutrition).ok(),
+        }
+    }
+}
+
+/// A product as scraped from a store. Only name, price and url are required,
+/// the other fields are filled in when the store shows them.
+#[derive(Debug, Clone, Serialize)]
 pub struct ProductInfo {
     pub name: String,
-    pub price: f32,
+    pub price: Money,
     pub url: String,
+    /// Identifies the product within its store across scrape runs, the normalized URL unless
+    /// the store crate sets something more stable
+    pub key: String,
+    /// Package size as shown by the store, e.g. "6 x 330 ml"
+    pub quantity: Option<String>,
+    pub unit_price: Option<UnitPrice>,
+    pub brand: Option<String>,
+    /// Category path from the root of the store's category tree
+    pub category: Vec<String>,
+    pub image_url: Option<String>,
+    /// The store's own identifier for the product
+    pub sku: Option<String>,
+    /// EAN / GTIN barcode
+    pub ean: Option<String>,
+    pub availability: Availability,
+    /// The promotion shown next to the product, `price` is always the regular price
+    pub offer: Option<Offer>,
+    /// Only set by stores that scrape the product's own page
+    pub details: Option<ProductDetails>,
 }
 
 impl ProductInfo {
-    pub fn new(name: String, price: f32, url: String) -> Self {
-        ProductInfo { name, price, url }
+    pub fn new(name: String, price: Money, url: String) -> Self {
+        ProductInfo {
+            key: normalize_url(&url),
+            name,
+            price,
+            url,
+            quantity: None,
+            unit_price: None,
+            brand: None,
+            category: Vec::new(),
+            image_url: None,
+            sku: None,
+            ean: None,
+            availability: Availability::default(),
+            offer: None,
+            details: None,
+        }
+    }
+
+    pub fn with_key(mut self, key: String) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Price per kilogram, litre or piece based on the package size, if it can be parsed
+    pub fn comparable_price(&self) -> Option<ComparablePrice> {
+        let quantity = Quantity::parse(self.quantity.as_deref()?).ok()?;
+        quantity.comparable_price(self.price)
+    }
+
+    /// Price per item with the offer applied, when buying just enough items to get the discount
+    pub fn promotion_price(&self) -> Option<Money> {
+        let promotion = self.offer.as_ref()?.promotion;
+        Some(promotion.effective_price(self.price, promotion.min_quantity()))
+    }
+
+    pub fn category_path(&self) -> Option<String> {
+        match self.category.is_empty() {
+            true => None,
+            false => Some(self.category.join(" > ")),
+        }
     }
 }
 
+/// How two products from different stores relate to each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum MatchRelation {
+    /// The same product sold by both stores
+    Identical,
+    /// A different product that can be bought instead, e.g. a house brand cola instead of Coca-Cola
+    ComparableAlternative,
+}
+
+impl MatchRelation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchRelation::Identical => "identical",
+            MatchRelation::ComparableAlternative => "comparable_alternative",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InDbProductMatch {
+    pub store: String,
+    pub product_key: String,
+    pub match_store: String,
+    pub match_product_key: String,
+    pub relation: MatchRelation,
+    /// Between 0 and 1
+    pub confidence: f64,
+    /// Shared by all products in the same group of identical products
+    pub group_key: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct InDbError {
     pub scraper: String,
@@ -37,4 +217,22 @@ impl InDbError {
     pub fn new(scraper: String, message: String) -> Self {
         InDbError { scraper, message }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Money;
+    use super::UnitPrice;
+
+    #[test]
+    fn test_parse_unit_price() {
+        let expected = Some(UnitPrice { price: Money::from_cents(258), unit: "kg".to_owned() });
+
+        assert_eq!(UnitPrice::parse("€ 2,58 per kg"), expected);
+        assert_eq!(UnitPrice::parse("2.58/kg"), expected);
+        assert_eq!(UnitPrice::parse("Prijs per kg € 2,58"), expected);
+        assert_eq!(UnitPrice::parse("per kg 2.58"), expected);
+        assert_eq!(UnitPrice::parse("€ 2,58"), None);
+        assert_eq!(UnitPrice::parse("per kg"), None);
+    }
 }
\ No newline at end of file
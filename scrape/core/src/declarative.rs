@@ -0,0 +1,5 @@
+mod definition;
+mod declarative_scraper;
+
+pub use definition::{StoreDefinition, Pagination, Fields, FieldRule, Extract, PriceRule, PriceFormat};
+pub use declarative_scraper::DeclarativeScraper;
@@ -0,0 +1,477 @@
+use anyhow::Result;
+use log::info;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use crate::{
+    AsyncExecutor,
+    ConfigError,
+    ErrorPolicy,
+    HtmlLoader,
+    Money,
+    NextLink,
+    Offer,
+    PagePosition,
+    PageRequest,
+    PageResponse,
+    PageUntilExhausted,
+    Paginator,
+    Pipeline,
+    ProductInfo,
+    ResultCollector,
+    ScrapeError,
+    Scraper,
+    Stage,
+    UnitPrice,
+};
+use super::definition::{resolve_url, Extract, FieldRule, Pagination, PriceFormat, StoreDefinition};
+
+// A field rule with its selectors and regex compiled
+struct CompiledRule {
+    selectors: Vec<Selector>,
+    extract: Extract,
+    regex: Option<Regex>,
+    separator: Option<String>,
+}
+
+impl CompiledRule {
+    fn compile(rule: &FieldRule, field: &str) -> Result<Self, ConfigError> {
+        let invalid = |reason: String| ConfigError::InvalidValue { field: field.to_owned(), reason };
+
+        let selectors = rule.selectors
+            .iter()
+            .map(|s| Selector::parse(s).map_err(|e| invalid(format!("invalid selector '{}': {}", s, e))))
+            .collect::<Result<Vec<Selector>, ConfigError>>()?;
+        let regex = rule.regex
+            .as_deref()
+            .map(|r| Regex::new(r).map_err(|e| invalid(e.to_string())))
+            .transpose()?;
+        Ok(CompiledRule { selectors, extract: rule.extract.clone(), regex, separator: rule.separator.clone() })
+    }
+
+    fn compile_optional(rule: &Option<FieldRule>, field: &str) -> Result<Option<Self>, ConfigError> {
+        rule.as_ref().map(|r| CompiledRule::compile(r, field)).transpose()
+    }
+
+    /// Value of the first matched element that gives one
+    fn first(&self, element: ElementRef) -> Option<String> {
+        self.matched(element).into_iter().next()
+    }
+
+    /// Values of all elements matched by the first selector that gives any
+    fn all(&self, element: ElementRef) -> Vec<String> {
+        let values = self.matched(element);
+        match (&self.separator, values.as_slice()) {
+            (Some(separator), [single]) => single
+                .split(separator.as_str())
+                .map(|v| v.trim().to_owned())
+                .filter(|v| !v.is_empty())
+                .collect(),
+            _ => values,
+        }
+    }
+
+    fn matched(&self, element: ElementRef) -> Vec<String> {
+        if self.selectors.is_empty() {
+            return self.value(element).into_iter().collect();
+        }
+        self.selectors
+            .iter()
+            .map(|selector| element.select(selector).filter_map(|found| self.value(found)).collect::<Vec<String>>())
+            .find(|values| !values.is_empty())
+            .unwrap_or_default()
+    }
+
+    fn value(&self, element: ElementRef) -> Option<String> {
+        let raw = match &self.extract {
+            Extract::Text => element.text().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" "),
+            Extract::Attr(name) => element.value().attr(name)?.trim().to_owned(),
+        };
+        let value = match &self.regex {
+            Some(regex) => {
+                let captures = regex.captures(&raw)?;
+                captures.get(1).or_else(|| captures.get(0))?.as_str().trim().to_owned()
+            },
+            None => raw,
+        };
+        match value.is_empty() {
+            true => None,
+            false => Some(value),
+        }
+    }
+}
+
+/// Scrapes a store described by a `StoreDefinition`, so a simple store can be added without
+/// writing a store crate.
+///
+/// # Example
+/// ```no_run
+/// use scrape_core::{ReqwestHtmlLoader, SimpleRateLimiter, Scraper, StoreDefinition, DeclarativeScraper};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let definition = StoreDefinition::from_file("stores/dirk.toml")?;
+/// let client = reqwest::Client::new();
+/// let connector = ReqwestHtmlLoader::new(&client);
+/// let scraper = DeclarativeScraper::new(&connector, definition)?;
+/// let products = scraper.scrape(&SimpleRateLimiter::default()).await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct DeclarativeScraper<'a, T: HtmlLoader + Send + Sync> {
+    connector: &'a T,
+    name: String,
+    base_url: String,
+    start_url: String,
+    pagination: Pagination,
+    container: Selector,
+    pages: Option<CompiledRule>,
+    next: Option<CompiledRule>,
+    name_rule: CompiledRule,
+    price: CompiledRule,
+    price_format: PriceFormat,
+    price_fraction: Option<CompiledRule>,
+    url: CompiledRule,
+    quantity: Option<CompiledRule>,
+    unit_price: Option<CompiledRule>,
+    brand: Option<CompiledRule>,
+    image_url: Option<CompiledRule>,
+    sku: Option<CompiledRule>,
+    ean: Option<CompiledRule>,
+    category: Option<CompiledRule>,
+    promotion: Option<CompiledRule>,
+    promotion_validity: Option<CompiledRule>,
+}
+
+impl<'a, T: HtmlLoader + Send + Sync> DeclarativeScraper<'a, T> {
+    /// Compile the selectors and regexes of the definition, fails on invalid ones
+    pub fn new(connector: &'a T, definition: StoreDefinition) -> Result<Self, ConfigError> {
+        let field = |name: &str| format!("{}.fields.{}", definition.name, name);
+        let fields = &definition.fields;
+
+        let container = Selector::parse(&definition.product_container).map_err(|e| ConfigError::InvalidValue {
+            field: format!("{}.product_container", definition.name),
+            reason: e.to_string(),
+        })?;
+        if fields.price.format == PriceFormat::Parts && fields.price.fraction.is_none() {
+            return Err(ConfigError::InvalidValue {
+                field: field("price.fraction"),
+                reason: "required for the 'parts' price format".to_owned(),
+            });
+        }
+        let (pages, next) = match &definition.pagination {
+            Pagination::Offset { pages, .. } => (Some(CompiledRule::compile(pages, &format!("{}.pagination.pages", definition.name))?), None),
+            Pagination::NextLink { next, .. } => (None, Some(CompiledRule::compile(next, &format!("{}.pagination.next", definition.name))?)),
+            _ => (None, None),
+        };
+
+        Ok(DeclarativeScraper {
+            connector,
+            start_url: definition.start_url()?,
+            pages,
+            next,
+            container,
+            name_rule: CompiledRule::compile(&fields.name, &field("name"))?,
+            price: CompiledRule::compile(&fields.price.field, &field("price.field"))?,
+            price_format: fields.price.format,
+            price_fraction: CompiledRule::compile_optional(&fields.price.fraction, &field("price.fraction"))?,
+            url: CompiledRule::compile(&fields.url, &field("url"))?,
+            quantity: CompiledRule::compile_optional(&fields.quantity, &field("quantity"))?,
+            unit_price: CompiledRule::compile_optional(&fields.unit_price, &field("unit_price"))?,
+            brand: CompiledRule::compile_optional(&fields.brand, &field("brand"))?,
+            image_url: CompiledRule::compile_optional(&fields.image_url, &field("image_url"))?,
+            sku: CompiledRule::compile_optional(&fields.sku, &field("sku"))?,
+            ean: CompiledRule::compile_optional(&fields.ean, &field("ean"))?,
+            category: CompiledRule::compile_optional(&fields.category, &field("category"))?,
+            promotion: CompiledRule::compile_optional(&fields.promotion, &field("promotion"))?,
+            promotion_validity: CompiledRule::compile_optional(&fields.promotion_validity, &field("promotion_validity"))?,
+            pagination: definition.pagination,
+            base_url: definition.base_url,
+            name: definition.name,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn missing(&self, info: &str) -> ScrapeError {
+        ScrapeError::InvalidStructureAssumed { src: self.name.clone(), info: info.to_owned() }
+    }
+
+    fn page_url(&self, param: &str, value: usize) -> Result<String> {
+        let mut url = reqwest::Url::parse(&self.start_url)?;
+        url.query_pairs_mut().append_pair(param, &value.to_string());
+        Ok(url.into())
+    }
+
+    // Products on a page and the link to the next page, when the pagination uses one. A product
+    // that fails to parse, e.g. an ad without a price, doesn't affect the others
+    fn parse_page(&self, document: &Html) -> (Vec<Result<ProductInfo>>, Option<String>) {
+        let products = document
+            .select(&self.container)
+            .map(|container| self.parse_product(container))
+            .collect();
+        let next = self.next
+            .as_ref()
+            .and_then(|rule| rule.first(document.root_element()))
+            .and_then(|link| resolve_url(&self.base_url, &link));
+        (products, next)
+    }
+
+    fn parse_product(&self, container: ElementRef) -> Result<ProductInfo> {
+        let optional = |rule: &Option<CompiledRule>| rule.as_ref().and_then(|r| r.first(container));
+
+        let name = self.name_rule.first(container).ok_or_else(|| self.missing("name"))?;
+        let price = self.parse_price(container)?;
+        let link = self.url.first(container).ok_or_else(|| self.missing("url"))?;
+        let url = resolve_url(&self.base_url, &link).ok_or_else(|| self.missing("url"))?;
+
+        let mut product = ProductInfo::new(name, price, url);
+        product.sku = optional(&self.sku);
+        if let Some(sku) = &product.sku {
+            product.key = sku.clone();
+        }
+        product.quantity = optional(&self.quantity);
+        product.unit_price = optional(&self.unit_price).and_then(|text| UnitPrice::parse(&text));
+        product.brand = optional(&self.brand);
+        product.image_url = optional(&self.image_url).and_then(|image| resolve_url(&self.base_url, &image));
+        product.ean = optional(&self.ean);
+        product.category = self.category.as_ref().map(|rule| rule.all(container)).unwrap_or_default();
+        product.offer = optional(&self.promotion)
+            .and_then(|label| Offer::parse(&label, optional(&self.promotion_validity).as_deref()));
+        Ok(product)
+    }
+
+    fn parse_price(&self, container: ElementRef) -> Result<Money> {
+        let text = self.price.first(container).ok_or_else(|| self.missing("price"))?;
+        let parse_error = |err: String| ScrapeError::FailedToParseStringValue { src: self.name.clone(), err };
+
+        let price = match self.price_format {
+            PriceFormat::Decimal => Money::parse(&text).map_err(|e| parse_error(e.to_string()))?,
+            PriceFormat::Cents => Money::from_cents(text.parse().map_err(|e: std::num::ParseIntError| parse_error(e.to_string()))?),
+            PriceFormat::Parts => {
+                let fraction = self.price_fraction
+                    .as_ref()
+                    .and_then(|rule| rule.first(container))
+                    .unwrap_or_default();
+                let whole = text.trim_end_matches(['.', ',']);
+                Money::from_parts(whole, &fraction).map_err(|e| parse_error(e.to_string()))?
+            },
+        };
+        Ok(price)
+    }
+
+    async fn scrape_page(&self, url: String) -> ResultCollector<ProductInfo> {
+        info!(target: self.name.as_str(), "Scraping url {}", &url);
+        match self.connector.load(url).await {
+            Ok(document) => self.parse_page(&document).0.into_iter().collect(),
+            Err(e) => ResultCollector::from(e),
+        }
+    }
+
+    // Page urls for offset pagination, the number of pages is shown on the start page
+    async fn scrape_offsets(&self, param: &str, step: usize) -> Result<Vec<String>> {
+        let document = self.connector.load(self.start_url.clone()).await?;
+        let nr_pages: usize = self.pages
+            .as_ref()
+            .and_then(|rule| rule.first(document.root_element()))
+            .ok_or_else(|| self.missing("number of pages"))?
+            .parse()
+            .map_err(|e: std::num::ParseIntError| ScrapeError::FailedToParseStringValue { src: self.name.clone(), err: e.to_string() })?;
+        info!(target: self.name.as_str(), "Found {} pages", nr_pages);
+
+        (0..nr_pages).map(|page| self.page_url(param, page * step)).collect()
+    }
+
+    // A page of a pagination where the next page is only known after this one
+    async fn fetch_page(&self, request: PageRequest) -> Result<PageResponse<Result<ProductInfo>>> {
+        let url = match (&request.position, &self.pagination) {
+            (PagePosition::Number(number), Pagination::PageParam { param, .. }) => self.page_url(param, *number)?,
+            (PagePosition::Link(link), _) => link.clone(),
+            _ => self.start_url.clone(),
+        };
+        info!(target: self.name.as_str(), "Scraping url {}", &url);
+        let document = self.connector.load(url).await?;
+        let (products, next) = self.parse_page(&document);
+        Ok(PageResponse::new(products).with_next(next))
+    }
+
+    // Pages are requested one after the other through the executor, so its delay and concurrency apply
+    async fn scrape_sequential<R: AsyncExecutor + Send + Sync>(&self, executor: &R) -> ResultCollector<ProductInfo> {
+        let fetch = |request| self.fetch_page(request);
+        let results = match &self.pagination {
+            Pagination::PageParam { start, max_pages, .. } => {
+                PageUntilExhausted::new(*start).with_max_pages(*max_pages).paginate(executor, fetch).await
+            },
+            Pagination::NextLink { max_pages, .. } => {
+                NextLink::new(&self.start_url).with_max_pages(*max_pages).paginate(executor, fetch).await
+            },
+            _ => NextLink::new(&self.start_url).with_max_pages(1).paginate(executor, fetch).await,
+        };
+        info!(target: self.name.as_str(), "{} pages, stopped: {:?}", results.pages.len(), results.stop);
+
+        let pages = results.into_collector(&self.name);
+        let mut products: ResultCollector<ProductInfo> = pages.successes.into_iter().collect();
+        products.errors.extend(pages.errors);
+        products
+    }
+}
+
+impl<'a, T: HtmlLoader + Send + Sync> Scraper for DeclarativeScraper<'a, T> {
+    async fn scrape<R: AsyncExecutor + Send + Sync>(&self, rate_limiter: &R) -> ResultCollector<ProductInfo> {
+        info!(target: self.name.as_str(), "Start scraping");
+
+        let (products, report) = match &self.pagination {
+            Pagination::Offset { param, step, .. } => {
+                Pipeline::new(&self.name)
+                    .stage(
+                        Stage::new("offsets", rate_limiter, |_: ()| self.scrape_offsets(param, *step))
+                        .error_policy(ErrorPolicy::FailFast)
+                    )
+                    .stage(Stage::new("pages", rate_limiter, |url| self.scrape_page(url)))
+                    .run(ResultCollector::from_value(()))
+                    .await
+            },
+            _ => return self.scrape_sequential(rate_limiter).await,
+        };
+
+        info!(target: self.name.as_str(), "{}", report);
+        products
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use anyhow::Result;
+    use crate::{AsyncExecutor, Money, Scraper, SimpleRateLimiter, StoreDefinition};
+    use crate::test_utils::FixtureLoader;
+    use super::DeclarativeScraper;
+
+    const DEFINITION: &str = r#"
+        name = "Buurtsuper"
+        base_url = "https://www.buurtsuper.nl"
+        start_url = "/producten"
+        product_container = "div.product"
+        pagination = { type = "next_link", next = { selectors = ["a.next"], extract = { attr = "href" } }, max_pages = 10 }
+
+        [fields]
+        name = { selectors = ["h2"] }
+        url = { selectors = ["a.title", "a"], extract = { attr = "href" } }
+        sku = { extract = { attr = "data-id" } }
+        quantity = { selectors = ["span.info"], regex = "Inhoud: (.+)" }
+        category = { selectors = ["span.category"], separator = "/" }
+        promotion = { selectors = ["span.promo"] }
+
+        [fields.price]
+        field = { selectors = ["span.euros"] }
+        format = "parts"
+        fraction = { selectors = ["span.cents"] }
+    "#;
+
+    fn product(id: &str, name: &str, euros: &str, cents: &str) -> String {
+        format!(
+            r#"<div class="product" data-id="{id}">
+                <a class="title" href="/p/{id}"><h2>{name}</h2></a>
+                <span class="euros">{euros}.</span><span class="cents">{cents}</span>
+                <span class="info">Inhoud: 1 l</span>
+                <span class="category">Zuivel / Melk</span>
+                <span class="promo">2e halve prijs</span>
+            </div>"#
+        )
+    }
+
+    // Counts the futures it runs, to see that every page goes through the executor
+    #[derive(Default)]
+    struct CountingExecutor {
+        runs: AtomicUsize,
+    }
+
+    impl AsyncExecutor for CountingExecutor {
+        async fn run<T: Send + Sync>(&self, futures: Vec<impl Future<Output = T> + Send + Sync>) -> Vec<Result<T>> {
+            self.runs.fetch_add(futures.len(), Ordering::SeqCst);
+            SimpleRateLimiter::default().run(futures).await
+        }
+    }
+
+    fn first_page() -> String {
+        format!("{}{}<a class=\"next\" href=\"/producten?pagina=2\">Volgende</a>", product("1", "Halfvolle melk", "1", "19"), product("2", "Volle melk", "1", "29"))
+    }
+
+    fn loader() -> FixtureLoader {
+        FixtureLoader::new([
+            ("https://www.buurtsuper.nl/producten", first_page()),
+            ("https://www.buurtsuper.nl/producten?pagina=2", product("3", "Karnemelk", "0", "99")),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_scrape_next_link() {
+        let loader = loader();
+        let scraper = DeclarativeScraper::new(&loader, StoreDefinition::from_toml(DEFINITION).unwrap()).unwrap();
+
+        let products = scraper.scrape(&SimpleRateLimiter::default()).await;
+        let first = &products.successes[0];
+
+        assert!(products.errors.is_empty());
+        assert_eq!(products.successes.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>(), vec!["Halfvolle melk", "Volle melk", "Karnemelk"]);
+        assert_eq!(first.price, Money::from_cents(119));
+        assert_eq!(first.url, "https://www.buurtsuper.nl/p/1");
+        assert_eq!(first.key, "1");
+        assert_eq!(first.quantity.as_deref(), Some("1 l"));
+        assert_eq!(first.category, vec!["Zuivel", "Melk"]);
+        assert!(first.offer.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_scrape_offset() {
+        let definition = DEFINITION.replace(
+            r#"pagination = { type = "next_link", next = { selectors = ["a.next"], extract = { attr = "href" } }, max_pages = 10 }"#,
+            r#"pagination = { type = "offset", param = "offset", step = 2, pages = { selectors = ["span.pages"] } }"#,
+        );
+        let mut loader = loader();
+        let start = format!("{}<span class=\"pages\">2</span>", first_page());
+        loader.insert("https://www.buurtsuper.nl/producten", start.clone());
+        loader.insert("https://www.buurtsuper.nl/producten?offset=0", start);
+        loader.insert("https://www.buurtsuper.nl/producten?offset=2", product("3", "Karnemelk", "0", "99"));
+        let scraper = DeclarativeScraper::new(&loader, StoreDefinition::from_toml(&definition).unwrap()).unwrap();
+
+        let products = scraper.scrape(&SimpleRateLimiter::default()).await;
+
+        assert!(products.errors.is_empty());
+        assert_eq!(products.successes.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_scrape_page_param() {
+        let definition = DEFINITION.replace(
+            r#"pagination = { type = "next_link", next = { selectors = ["a.next"], extract = { attr = "href" } }, max_pages = 10 }"#,
+            r#"pagination = { type = "page_param", param = "pagina", start = 1, max_pages = 10 }"#,
+        );
+        let ad = r#"<div class="product" data-id="ad"><a class="title" href="/actie"><h2>Bekijk de folder</h2></a></div>"#;
+        let loader = FixtureLoader::new([
+            ("https://www.buurtsuper.nl/producten?pagina=1", format!("{}{}", product("1", "Halfvolle melk", "1", "19"), ad)),
+            ("https://www.buurtsuper.nl/producten?pagina=2", product("2", "Volle melk", "1", "29")),
+            ("https://www.buurtsuper.nl/producten?pagina=3", String::new()),
+        ]);
+        let scraper = DeclarativeScraper::new(&loader, StoreDefinition::from_toml(&definition).unwrap()).unwrap();
+        let executor = CountingExecutor::default();
+
+        let products = scraper.scrape(&executor).await;
+
+        assert_eq!(products.successes.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>(), vec!["Halfvolle melk", "Volle melk"]);
+        assert_eq!(products.errors.len(), 1);
+        assert_eq!(executor.runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_invalid_definition() {
+        let loader = loader();
+        let invalid_selector = DEFINITION.replace(r#"name = { selectors = ["h2"] }"#, r#"name = { selectors = ["h2[["] }"#);
+        let invalid_regex = DEFINITION.replace("Inhoud: (.+)", "Inhoud: (.+");
+
+        assert!(DeclarativeScraper::new(&loader, StoreDefinition::from_toml(&invalid_selector).unwrap()).is_err());
+        assert!(DeclarativeScraper::new(&loader, StoreDefinition::from_toml(&invalid_regex).unwrap()).is_err());
+        assert!(StoreDefinition::from_toml(&DEFINITION.replace("product_container", "container")).is_err());
+    }
+}
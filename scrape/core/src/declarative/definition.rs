@@ -0,0 +1,152 @@
+use std::fs;
+use serde::{Deserialize, Serialize};
+use crate::ConfigError;
+
+/// A store described in TOML instead of Rust, scraped by `DeclarativeScraper`.
+///
+/// # Example
+/// ```toml
+/// name = "Dirk"
+/// base_url = "https://www.dirk.nl"
+/// start_url = "/boodschappen"
+/// product_container = "article.product-card"
+/// pagination = { type = "page_param", param = "page", start = 1, max_pages = 200 }
+///
+/// [fields]
+/// name = { selectors = ["h2.title"] }
+/// url = { selectors = ["a"], extract = { attr = "href" } }
+/// quantity = { selectors = ["span.subtitle"], regex = "(\\d+(?:,\\d+)? ?(?:g|kg|ml|l|stuks?))" }
+/// category = { selectors = ["nav.breadcrumb li"] }
+///
+/// [fields.price]
+/// field = { selectors = ["span.price-large"] }
+/// format = "parts"
+/// fraction = { selectors = ["span.price-small"] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StoreDefinition {
+    /// Stored as the store of the scraped products
+    pub name: String,
+    pub base_url: String,
+    /// Absolute, or relative to `base_url`
+    pub start_url: String,
+    #[serde(default)]
+    pub pagination: Pagination,
+    /// CSS selector of the element that holds a single product
+    pub product_container: String,
+    pub fields: Fields,
+}
+
+/// How the pages of the product listing are found
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Pagination {
+    /// All products are on the start page
+    #[default]
+    None,
+    /// The number of pages is read from the start page, pages are requested with `param`
+    /// set to the page number times `step`
+    Offset { param: String, step: usize, pages: FieldRule },
+    /// Pages are requested with `param` counting up from `start` until a page has no products
+    PageParam { param: String, start: usize, max_pages: usize },
+    /// The link to the next page is followed until there is none
+    NextLink { next: FieldRule, max_pages: usize },
+}
+
+/// What is extracted from an element matched by a `FieldRule`
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Extract {
+    /// The text of the element and its children, with whitespace collapsed
+    #[default]
+    Text,
+    /// The value of an attribute, e.g. `{ attr = "href" }`
+    Attr(String),
+}
+
+/// Where a field is found within a product container
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct FieldRule {
+    /// Tried in order, the first selector that gives a value is used. Without selectors the
+    /// container itself is used
+    pub selectors: Vec<String>,
+    pub extract: Extract,
+    /// Applied to the extracted value, the first capture group is used or the whole match without groups
+    pub regex: Option<String>,
+    /// Splits a single value into a list, only used for the category, e.g. " > "
+    pub separator: Option<String>,
+}
+
+/// How the price text of a store is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceFormat {
+    /// An amount like "€ 1,29" or "1.29"
+    #[default]
+    Decimal,
+    /// A whole number of cents like "129"
+    Cents,
+    /// Euros and cents in separate elements, the cents are taken from `fraction`
+    Parts,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriceRule {
+    pub field: FieldRule,
+    #[serde(default)]
+    pub format: PriceFormat,
+    pub fraction: Option<FieldRule>,
+}
+
+/// Product fields, `name`, `price` and `url` are required, the others are left empty when not set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fields {
+    pub name: FieldRule,
+    pub price: PriceRule,
+    /// Relative URLs are resolved against `base_url`
+    pub url: FieldRule,
+    pub quantity: Option<FieldRule>,
+    pub unit_price: Option<FieldRule>,
+    pub brand: Option<FieldRule>,
+    pub image_url: Option<FieldRule>,
+    /// Used as the product key when found
+    pub sku: Option<FieldRule>,
+    pub ean: Option<FieldRule>,
+    /// Every matched element is a level of the path, or a single value split on `separator`
+    pub category: Option<FieldRule>,
+    /// Promotion label, e.g. "2e halve prijs"
+    pub promotion: Option<FieldRule>,
+    /// Period the promotion is valid, e.g. "t/m zondag 14 april"
+    pub promotion_validity: Option<FieldRule>,
+}
+
+impl StoreDefinition {
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError::FailedToRead { path: path.to_owned(), err: e.to_string() })?;
+        StoreDefinition::from_toml(&content)
+            .map_err(|e| ConfigError::FailedToParse { path: path.to_owned(), err: e.to_string() })
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// The start URL made absolute
+    pub fn start_url(&self) -> Result<String, ConfigError> {
+        resolve_url(&self.base_url, &self.start_url).ok_or_else(|| ConfigError::InvalidValue {
+            field: format!("{}.start_url", self.name),
+            reason: format!("'{}' can't be resolved against '{}'", self.start_url, self.base_url),
+        })
+    }
+}
+
+/// Resolve a possibly relative URL against the base URL of a store
+pub fn resolve_url(base_url: &str, url: &str) -> Option<String> {
+    let base = reqwest::Url::parse(base_url).ok()?;
+    base.join(url.trim()).ok().map(String::from)
+}
@@ -32,6 +32,30 @@ pub enum ScrapeError {
         url: String,
         err: String,
     },
+    #[error("Listing {url} of the '{src}' scraper is incomplete, pages {missing_pages:?} of {pages} failed, found {found} of {expected} products")]
+    IncompleteListing {
+        src: String,
+        url: String,
+        pages: usize,
+        missing_pages: Vec<usize>,
+        found: usize,
+        expected: usize,
+    },
+    #[error("Pagination of the '{src}' scraper stopped at the limit of {max_pages} pages, later pages weren't requested")]
+    PageLimitReached {
+        src: String,
+        max_pages: usize,
+    },
+    #[error("Stage '{stage}' timed out after {timeout_ms} ms")]
+    StageTimedOut {
+        stage: String,
+        timeout_ms: u128,
+    },
+    #[error("Stage '{stage}' aborted the pipeline after {errors} errors")]
+    StageAborted {
+        stage: String,
+        errors: usize,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -45,4 +69,84 @@ pub enum DbError {
         query: String,
         err: String,
     },
-}
\ No newline at end of file
+}
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file '{path}'. Message: {err}")]
+    FailedToRead {
+        path: String,
+        err: String,
+    },
+    #[error("Failed to parse config file '{path}'. Message: {err}")]
+    FailedToParse {
+        path: String,
+        err: String,
+    },
+    #[error("Invalid value '{value}' for environment variable '{var}'. Message: {err}")]
+    InvalidEnvValue {
+        var: String,
+        value: String,
+        err: String,
+    },
+    #[error("Invalid config value for '{field}'. Reason: {reason}")]
+    InvalidValue {
+        field: String,
+        reason: String,
+    },
+    #[error("Unknown store '{store}' in config, known stores are: {known}")]
+    UnknownStore {
+        store: String,
+        known: String,
+    },
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MoneyError {
+    #[error("Failed to parse '{value}' as an amount of money. Reason: {reason}")]
+    InvalidFormat {
+        value: String,
+        reason: String,
+    },
+    #[error("Can't combine amounts in {left} and {right}")]
+    CurrencyMismatch {
+        left: String,
+        right: String,
+    },
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum QuantityError {
+    #[error("Failed to parse '{value}' as a quantity. Reason: {reason}")]
+    InvalidFormat {
+        value: String,
+        reason: String,
+    },
+}
+
+impl ScrapeError {
+    /// Name of the variant, used to group errors in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScrapeError::CSSSelectorFailed { .. } => "CSSSelectorFailed",
+            ScrapeError::NoProductsFound { .. } => "NoProductsFound",
+            ScrapeError::InvalidStructureAssumed { .. } => "InvalidStructureAssumed",
+            ScrapeError::FailedToParseStringValue { .. } => "FailedToParseStringValue",
+            ScrapeError::FailedToConnect { .. } => "FailedToConnect",
+            ScrapeError::FailedToParseHtml { .. } => "FailedToParseHtml",
+            ScrapeError::IncompleteListing { .. } => "IncompleteListing",
+            ScrapeError::PageLimitReached { .. } => "PageLimitReached",
+            ScrapeError::StageTimedOut { .. } => "StageTimedOut",
+            ScrapeError::StageAborted { .. } => "StageAborted",
+        }
+    }
+}
+
+impl DbError {
+    /// Name of the variant, used to group errors in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DbError::FailedToConnect { .. } => "DbFailedToConnect",
+            DbError::QueryFailed { .. } => "QueryFailed",
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::ResultCollector;
 use super::ProductInfo;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::future::Future;
 
 pub trait AsyncExecutor {
@@ -13,4 +13,10 @@ pub trait Scraper {
 
 pub trait HtmlLoader {
     fn load(&self, url: String) -> impl Future<Output = Result<scraper::Html>> + Send + Sync;
+
+    /// The body as it was sent, for documents that aren't HTML like a gzipped sitemap.
+    /// Loaders that only serve HTML don't support it
+    fn load_bytes(&self, url: String) -> impl Future<Output = Result<Vec<u8>>> + Send + Sync {
+        async move { Err(anyhow!("Loading {} as bytes isn't supported", url)) }
+    }
 }
\ No newline at end of file
@@ -5,14 +5,90 @@ mod interface;
 mod rate_limiter;
 mod connector;
 mod result_collector;
+mod summary;
+mod pipeline;
+mod money;
+mod units;
+mod promotion;
+mod taxonomy;
+mod validation;
+mod anomaly;
+mod declarative;
+mod category_tree;
+mod paginator;
+mod crawl_frontier;
+mod sitemap;
 pub mod scrape_utils;
+pub mod text;
 mod constants;
+#[cfg(test)]
+mod test_utils;
 
 pub use reqwest::{Client as RequestClient, ClientBuilder as RequestClientBuilder, header as request_header};
-pub use error::{ScrapeError, DbError};
+pub use error::{ScrapeError, DbError, ConfigError, MoneyError, QuantityError};
 pub use interface::{Scraper, HtmlLoader, AsyncExecutor};
-pub use config::{ConfigBuilder, ScrapeConfig};
-pub use data::{ProductInfo, InDbProduct, InDbError};
-pub use rate_limiter::{SimpleRateLimiter, RandomDelayRateLimiter};
+pub use config::{
+    ConfigBuilder,
+    ScrapeConfig,
+    StoreConfig,
+    OutputSink,
+    DelayRange,
+    RetryPolicy,
+    UserAgentStrategy,
+    DetailPagesConfig,
+    Discovery,
+    CONFIG_PATH_VAR,
+    DEFAULT_CONFIG_PATH,
+    ValidationConfig,
+    Severity,
+    RuleSeverities,
+    AnomalyConfig,
+};
+pub use data::{
+    ProductInfo,
+    ProductDetails,
+    NutritionValue,
+    InDbProduct,
+    InDbError,
+    InDbProductMatch,
+    MatchRelation,
+    Availability,
+    UnitPrice,
+};
+pub use money::{Money, Currency};
+pub use promotion::{Promotion, Offer, parse_validity};
+pub use taxonomy::{Taxonomy, TaxonomyReport};
+pub use validation::{Validator, ValidationReport, ValidationIssue, Rule};
+pub use declarative::{
+    DeclarativeScraper,
+    StoreDefinition,
+    Pagination,
+    Fields,
+    FieldRule,
+    Extract,
+    PriceRule,
+    PriceFormat,
+};
+pub use category_tree::{CategoryTree, CategoryPage, child_links};
+pub use crawl_frontier::{CrawlFrontier, Visit, canonical_crawl_url, page_links};
+pub use sitemap::{Sitemap, SitemapEntry, SitemapContent, parse_sitemap, parse_lastmod};
+pub use paginator::{
+    Paginator,
+    PageRequest,
+    PagePosition,
+    PageResponse,
+    Page,
+    PagedResults,
+    StopReason,
+    OffsetWithTotal,
+    PageUntilExhausted,
+    NextLink,
+    Cursor,
+};
+pub use anomaly::{AnomalyDetector, AnomalyReport, Anomaly, AnomalyKind, PreviousProduct, Acknowledged};
+pub use units::{Quantity, Unit, ComparableUnit, ComparablePrice};
+pub use rate_limiter::{SimpleRateLimiter, RandomDelayRateLimiter, RateLimiter};
 pub use connector::ReqwestHtmlLoader;
-pub use result_collector::{ResultCollector, Transform, AsyncTransform};
\ No newline at end of file
+pub use result_collector::{ResultCollector, Transform, AsyncTransform};
+pub use pipeline::{Pipeline, Stage, ErrorPolicy, StageStatus, StageReport, PipelineReport, IntoCollector};
+pub use summary::{CollectorSummary, ErrorRecord, error_kind};
\ No newline at end of file
@@ -0,0 +1,319 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Mul, Sub};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use super::MoneyError;
+
+const CENTS_PER_UNIT: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Currency {
+    #[default]
+    EUR,
+}
+
+impl Currency {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::EUR => "EUR",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Currency::EUR => "€",
+        }
+    }
+}
+
+/// An exact amount of money, stored as an integer number of cents.
+///
+/// # Example
+/// ```
+/// use scrape_core::Money;
+///
+/// let price: Money = "€ 1,29".parse().unwrap();
+/// let total = price * 3 + Money::from_cents(13);
+///
+/// assert_eq!(total.cents(), 400);
+/// assert_eq!(total.to_string(), "€ 4,00");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Money {
+    cents: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(cents: i64, currency: Currency) -> Self {
+        Money { cents, currency }
+    }
+
+    /// An amount in euros
+    pub fn from_cents(cents: i64) -> Self {
+        Money::new(cents, Currency::EUR)
+    }
+
+    pub fn zero() -> Self {
+        Money::from_cents(0)
+    }
+
+    pub fn cents(&self) -> i64 {
+        self.cents
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.cents == 0
+    }
+
+    /// Parse prices as they are shown on Dutch websites, e.g. "1,29", "€ 1.29",
+    /// "1.299,00", "2,-" or "0.5". The amount is assumed to be in euros.
+    ///
+    /// A separator followed by one or two digits is a decimal separator, followed by
+    /// three digits it's a thousands separator. Fractions of a cent are rejected.
+    pub fn parse(value: &str) -> Result<Money, MoneyError> {
+        let invalid = |reason: &str| MoneyError::InvalidFormat { value: value.to_owned(), reason: reason.to_owned() };
+
+        let mut cleaned: String = value
+            .replace(Currency::EUR.symbol(), "")
+            .replace(Currency::EUR.code(), "")
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+
+        let negative = cleaned.starts_with('-');
+        if negative {
+            cleaned.remove(0);
+        }
+        // "2,-" and "2.–" are whole amounts
+        for suffix in [",-", ".-", ",–", ".–"] {
+            if let Some(stripped) = cleaned.strip_suffix(suffix) {
+                cleaned = format!("{},00", stripped);
+            }
+        }
+
+        if cleaned.is_empty() {
+            return Err(invalid("no amount found"));
+        }
+        if !cleaned.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',') {
+            return Err(invalid("unexpected characters"));
+        }
+
+        let (whole, fraction) = match cleaned.rfind(['.', ',']) {
+            Some(idx) if cleaned.len() - idx - 1 <= 2 => (&cleaned[..idx], &cleaned[idx + 1..]),
+            Some(idx) if cleaned.len() - idx - 1 == 3 => (&cleaned[..], ""),
+            Some(_) => return Err(invalid("fractions of a cent are not supported")),
+            None => (&cleaned[..], ""),
+        };
+        if !has_valid_grouping(whole) {
+            return Err(invalid("invalid digit grouping"));
+        }
+        let cents = Money::from_parts(whole, fraction)?.cents;
+
+        Ok(Money::from_cents(if negative { -cents } else { cents }))
+    }
+
+    /// Combine the whole and fractional part of a price that are shown separately,
+    /// e.g. `<span>1</span><sup>29</sup>`. Thousands separators in the whole part are ignored
+    pub fn from_parts(whole: &str, fraction: &str) -> Result<Money, MoneyError> {
+        let invalid = |reason: &str| MoneyError::InvalidFormat {
+            value: format!("{}.{}", whole, fraction),
+            reason: reason.to_owned(),
+        };
+
+        let whole: String = whole.trim().chars().filter(|c| *c != '.' && *c != ',').collect();
+        let fraction = fraction.trim();
+        if fraction.len() > 2 {
+            return Err(invalid("fractions of a cent are not supported"));
+        }
+        if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid("unexpected characters"));
+        }
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(invalid("no amount found"));
+        }
+
+        let whole_cents = match whole.is_empty() {
+            true => 0,
+            false => whole.parse::<i64>().map_err(|e| invalid(&e.to_string()))? * CENTS_PER_UNIT,
+        };
+        let fraction_cents = match fraction.len() {
+            0 => 0,
+            1 => fraction.parse::<i64>().map_err(|e| invalid(&e.to_string()))? * 10,
+            _ => fraction.parse::<i64>().map_err(|e| invalid(&e.to_string()))?,
+        };
+        Ok(Money::from_cents(whole_cents + fraction_cents))
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.check_currency(&other)?;
+        Ok(Money::new(self.cents + other.cents, self.currency))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.check_currency(&other)?;
+        Ok(Money::new(self.cents - other.cents, self.currency))
+    }
+
+    /// Multiply by `numerator / denominator`, rounding half away from zero to whole cents
+    pub fn mul_ratio(self, numerator: i64, denominator: i64) -> Money {
+        assert!(denominator != 0, "Can't divide money by zero");
+        let product = self.cents as i128 * numerator as i128;
+        let denominator = denominator as i128;
+        let half = denominator.abs() / 2;
+        let rounded = match (product < 0) == (denominator < 0) {
+            true => (product.abs() + half) / denominator.abs(),
+            false => -((product.abs() + half) / denominator.abs()),
+        };
+        Money::new(rounded as i64, self.currency)
+    }
+
+    /// The amount as a plain decimal string, e.g. "1.29", as used by databases
+    pub fn to_decimal_string(&self) -> String {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        format!("{}{}.{:02}", sign, self.cents.abs() / CENTS_PER_UNIT, self.cents.abs() % CENTS_PER_UNIT)
+    }
+
+    fn check_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        match self.currency == other.currency {
+            true => Ok(()),
+            false => Err(MoneyError::CurrencyMismatch {
+                left: self.currency.code().to_owned(),
+                right: other.currency.code().to_owned(),
+            }),
+        }
+    }
+}
+
+fn has_valid_grouping(whole: &str) -> bool {
+    // Thousands separators should split the whole part into groups of three digits
+    let groups: Vec<&str> = whole.split(['.', ',']).collect();
+    match groups.split_first() {
+        Some((_, [])) => true,
+        Some((first, rest)) => {
+            !first.is_empty() && first.len() <= 3 && rest.iter().all(|g| g.len() == 3)
+        },
+        None => true,
+    }
+}
+
+impl Default for Money {
+    fn default() -> Self {
+        Money::zero()
+    }
+}
+
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Money::parse(s)
+    }
+}
+
+/// Formats the amount the Dutch way, e.g. "€ 1,29"
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.currency.symbol(), self.to_decimal_string().replace('.', ","))
+    }
+}
+
+/// Panics when the currencies differ, use `checked_add` when that's possible
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        self.checked_add(other).expect("Added money in different currencies")
+    }
+}
+
+/// Panics when the currencies differ, use `checked_sub` when that's possible
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        self.checked_sub(other).expect("Subtracted money in different currencies")
+    }
+}
+
+impl Mul<i64> for Money {
+    type Output = Money;
+
+    fn mul(self, quantity: i64) -> Money {
+        Money::new(self.cents * quantity, self.currency)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::zero(), |total, m| total + m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Money;
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            ("1.29", 129),
+            ("1,29", 129),
+            ("€ 1,29", 129),
+            ("€1.5", 150),
+            ("0.99", 99),
+            (",99", 99),
+            ("2", 200),
+            ("2,-", 200),
+            ("1.299,00", 129900),
+            ("1,299.00", 129900),
+            ("1.299", 129900),
+            (" 12,05 EUR ", 1205),
+            ("-0,50", -50),
+        ];
+
+        for (input, cents) in cases {
+            assert_eq!(Money::parse(input).unwrap().cents(), cents, "parsing '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in ["", "€", "abc", "1.2345", "1,2a", "1..29"] {
+            assert!(Money::parse(input).is_err(), "parsing '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_from_parts() {
+        assert_eq!(Money::from_parts("1", "29").unwrap().cents(), 129);
+        assert_eq!(Money::from_parts("1", "5").unwrap().cents(), 150);
+        assert_eq!(Money::from_parts(" 12 ", "").unwrap().cents(), 1200);
+        assert!(Money::from_parts("1", "299").is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let price = Money::from_cents(129);
+
+        assert_eq!((price + price).cents(), 258);
+        assert_eq!((price - Money::from_cents(29)).cents(), 100);
+        assert_eq!((price * 3).cents(), 387);
+        assert_eq!(vec![price, price, price].into_iter().sum::<Money>().cents(), 387);
+        assert_eq!(price.mul_ratio(1, 2).cents(), 65);
+        assert_eq!(Money::from_cents(-129).mul_ratio(1, 2).cents(), -65);
+        assert_eq!(Money::from_cents(300).mul_ratio(2, 3).cents(), 200);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Money::from_cents(129).to_string(), "€ 1,29");
+        assert_eq!(Money::from_cents(5).to_decimal_string(), "0.05");
+        assert_eq!(Money::from_cents(-1005).to_decimal_string(), "-10.05");
+    }
+}
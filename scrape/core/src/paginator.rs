@@ -0,0 +1,474 @@
+use std::collections::HashSet;
+use std::future::Future;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use super::{AsyncExecutor, ResultCollector, ScrapeError};
+
+const DEFAULT_MAX_PAGES: usize = 500;
+
+/// Where a page is found, each strategy asks for pages in its own way
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PagePosition {
+    /// Number of items before the page
+    Offset(usize),
+    /// Page number as the store counts them
+    Number(usize),
+    /// URL of the page, the first one is the start URL
+    Link(String),
+    /// Cursor returned by the previous page, `None` for the first page
+    Cursor(Option<String>),
+}
+
+/// A page to fetch, `index` counts the pages of a pagination from 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRequest {
+    pub index: usize,
+    pub position: PagePosition,
+}
+
+/// Items of a fetched page and what it tells about the other pages
+#[derive(Debug)]
+pub struct PageResponse<T> {
+    pub items: Vec<T>,
+    /// Number of items over all pages, when the page shows it
+    pub total: Option<usize>,
+    /// Link or cursor of the next page
+    pub next: Option<String>,
+}
+
+impl<T> PageResponse<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        PageResponse { items, total: None, next: None }
+    }
+
+    pub fn with_total(mut self, total: Option<usize>) -> Self {
+        self.total = total;
+        self
+    }
+
+    pub fn with_next(mut self, next: Option<String>) -> Self {
+        self.next = next;
+        self
+    }
+}
+
+/// Why a pagination stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The last page was reached: an empty or short page, no next link or cursor, or the total was covered
+    Exhausted,
+    /// A page failed to load, later pages are unknown
+    Failed,
+    /// The next link or cursor pointed at a page that was already fetched
+    Repeated,
+    /// There were more pages than the safety cap allows
+    Capped,
+}
+
+/// A fetched page with its items, a page that failed holds the error
+pub struct Page<T: Send + Sync> {
+    pub request: PageRequest,
+    pub items: ResultCollector<T>,
+}
+
+pub struct PagedResults<T: Send + Sync> {
+    pub pages: Vec<Page<T>>,
+    pub stop: StopReason,
+    pub max_pages: usize,
+}
+
+impl<T: Send + Sync> PagedResults<T> {
+    /// The items of all pages, a pagination that hit the cap adds a `PageLimitReached` error
+    pub fn into_collector(self, src: &str) -> ResultCollector<T> {
+        let mut collector = ResultCollector::new();
+        for page in self.pages {
+            collector.extend(page.items);
+        }
+        if self.stop == StopReason::Capped {
+            collector.errors.push(ScrapeError::PageLimitReached { src: src.to_owned(), max_pages: self.max_pages }.into());
+        }
+        collector
+    }
+}
+
+/// Requests the pages of a listing until one of its stop conditions is met. Every strategy stops at
+/// an empty page, a page that fails, or after `max_pages` pages.
+///
+/// # Example
+/// ```
+/// use scrape_core::{NextLink, PagePosition, PageRequest, PageResponse, Paginator, SimpleRateLimiter};
+///
+/// async fn fetch_page(request: PageRequest) -> anyhow::Result<PageResponse<usize>> {
+///     let next = (request.index < 2).then(|| format!("https://www.example.com/producten?page={}", request.index + 1));
+///     Ok(PageResponse::new(vec![request.index]).with_next(next))
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let rate_limiter = SimpleRateLimiter::default();
+/// let results = NextLink::new("https://www.example.com/producten")
+///     .with_max_pages(100)
+///     .paginate(&rate_limiter, fetch_page)
+///     .await;
+/// let products = results.into_collector("example");
+///
+/// assert_eq!(products.successes, vec![0, 1, 2]);
+/// # }
+/// ```
+pub trait Paginator {
+    fn paginate<T, F, Fut, R>(&self, executor: &R, fetch: F) -> impl Future<Output = PagedResults<T>> + Send
+    where
+        T: Send + Sync,
+        F: Fn(PageRequest) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<PageResponse<T>>> + Send + Sync,
+        R: AsyncExecutor + Send + Sync;
+}
+
+/// The first page shows the total number of items, the other pages are requested at once. Without
+/// a total the pages are requested one by one until a page is short
+pub struct OffsetWithTotal {
+    page_size: usize,
+    start: usize,
+    max_pages: usize,
+}
+
+impl OffsetWithTotal {
+    pub fn new(page_size: usize) -> Self {
+        OffsetWithTotal { page_size: page_size.max(1), start: 0, max_pages: DEFAULT_MAX_PAGES }
+    }
+
+    /// Offset of the first page
+    pub fn with_start(mut self, start: usize) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+}
+
+impl Paginator for OffsetWithTotal {
+    async fn paginate<T, F, Fut, R>(&self, executor: &R, fetch: F) -> PagedResults<T>
+    where
+        T: Send + Sync,
+        F: Fn(PageRequest) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<PageResponse<T>>> + Send + Sync,
+        R: AsyncExecutor + Send + Sync,
+    {
+        let offset = |index: usize| PagePosition::Offset(self.start + index * self.page_size);
+        let mut pages = Vec::new();
+        let first = PageRequest { index: 0, position: offset(0) };
+        let total = match fetch_one(executor, &fetch, first.clone()).await {
+            Err(e) => {
+                pages.push(Page::failed(first, e));
+                return self.results(pages, StopReason::Failed);
+            },
+            Ok(response) if response.items.is_empty() => {
+                pages.push(Page::fetched(first, response.items));
+                return self.results(pages, StopReason::Exhausted);
+            },
+            Ok(response) => {
+                let total = response.total;
+                let short = response.items.len() < self.page_size;
+                pages.push(Page::fetched(first, response.items));
+                match total {
+                    Some(total) => total,
+                    None if short => return self.results(pages, StopReason::Exhausted),
+                    None => {
+                        warn!("No total on the first page, requesting pages until one is short");
+                        let stop = sequential(executor, &fetch, &mut pages, offset(1), self.max_pages, |request, response| {
+                            if response.items.len() < self.page_size {
+                                return Err(StopReason::Exhausted);
+                            }
+                            Ok(offset(request.index + 1))
+                        }).await;
+                        return self.results(pages, stop);
+                    },
+                }
+            },
+        };
+
+        let nr_pages = total.saturating_sub(self.start).div_ceil(self.page_size);
+        let capped = nr_pages > self.max_pages;
+        let requests: Vec<PageRequest> = (1..nr_pages.min(self.max_pages))
+            .map(|index| PageRequest { index, position: offset(index) })
+            .collect();
+        info!("Found {} items on {} pages", total, nr_pages);
+
+        let results = executor.run(requests.iter().cloned().map(&fetch).collect()).await;
+        let mut failed = false;
+        for (request, result) in requests.into_iter().zip(results) {
+            match result.and_then(|r| r) {
+                Ok(response) => pages.push(Page::fetched(request, response.items)),
+                Err(e) => {
+                    failed = true;
+                    pages.push(Page::failed(request, e));
+                },
+            }
+        }
+
+        let stop = match (capped, failed) {
+            (true, _) => StopReason::Capped,
+            (_, true) => StopReason::Failed,
+            _ => StopReason::Exhausted,
+        };
+        self.results(pages, stop)
+    }
+}
+
+impl OffsetWithTotal {
+    fn results<T: Send + Sync>(&self, pages: Vec<Page<T>>, stop: StopReason) -> PagedResults<T> {
+        PagedResults { pages, stop, max_pages: self.max_pages }
+    }
+}
+
+/// Page numbers counting up from `start` until a page is empty, or shorter than the page size when it is set
+pub struct PageUntilExhausted {
+    start: usize,
+    page_size: Option<usize>,
+    max_pages: usize,
+}
+
+impl PageUntilExhausted {
+    pub fn new(start: usize) -> Self {
+        PageUntilExhausted { start, page_size: None, max_pages: DEFAULT_MAX_PAGES }
+    }
+
+    /// A page with less items is the last one
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+}
+
+impl Paginator for PageUntilExhausted {
+    async fn paginate<T, F, Fut, R>(&self, executor: &R, fetch: F) -> PagedResults<T>
+    where
+        T: Send + Sync,
+        F: Fn(PageRequest) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<PageResponse<T>>> + Send + Sync,
+        R: AsyncExecutor + Send + Sync,
+    {
+        let mut pages = Vec::new();
+        let stop = sequential(executor, &fetch, &mut pages, PagePosition::Number(self.start), self.max_pages, |request, response| {
+            if self.page_size.is_some_and(|size| response.items.len() < size) {
+                return Err(StopReason::Exhausted);
+            }
+            Ok(PagePosition::Number(self.start + request.index + 1))
+        }).await;
+        PagedResults { pages, stop, max_pages: self.max_pages }
+    }
+}
+
+/// Follows the link to the next page from the start URL until a page has none
+pub struct NextLink {
+    start_url: String,
+    max_pages: usize,
+}
+
+impl NextLink {
+    pub fn new(start_url: &str) -> Self {
+        NextLink { start_url: start_url.to_owned(), max_pages: DEFAULT_MAX_PAGES }
+    }
+
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+}
+
+impl Paginator for NextLink {
+    async fn paginate<T, F, Fut, R>(&self, executor: &R, fetch: F) -> PagedResults<T>
+    where
+        T: Send + Sync,
+        F: Fn(PageRequest) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<PageResponse<T>>> + Send + Sync,
+        R: AsyncExecutor + Send + Sync,
+    {
+        let mut pages = Vec::new();
+        let stop = sequential(executor, &fetch, &mut pages, PagePosition::Link(self.start_url.clone()), self.max_pages, |_, response| {
+            response.next.clone().map(PagePosition::Link).ok_or(StopReason::Exhausted)
+        }).await;
+        PagedResults { pages, stop, max_pages: self.max_pages }
+    }
+}
+
+/// Each page returns the cursor of the next one, e.g. an API with `after=<cursor>`, until there is none
+pub struct Cursor {
+    max_pages: usize,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor::new()
+    }
+}
+
+impl Cursor {
+    pub fn new() -> Self {
+        Cursor { max_pages: DEFAULT_MAX_PAGES }
+    }
+
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+}
+
+impl Paginator for Cursor {
+    async fn paginate<T, F, Fut, R>(&self, executor: &R, fetch: F) -> PagedResults<T>
+    where
+        T: Send + Sync,
+        F: Fn(PageRequest) -> Fut + Send + Sync,
+        Fut: Future<Output = Result<PageResponse<T>>> + Send + Sync,
+        R: AsyncExecutor + Send + Sync,
+    {
+        let mut pages = Vec::new();
+        let stop = sequential(executor, &fetch, &mut pages, PagePosition::Cursor(None), self.max_pages, |_, response| {
+            response.next.clone().map(|cursor| PagePosition::Cursor(Some(cursor))).ok_or(StopReason::Exhausted)
+        }).await;
+        PagedResults { pages, stop, max_pages: self.max_pages }
+    }
+}
+
+impl<T: Send + Sync> Page<T> {
+    fn fetched(request: PageRequest, items: Vec<T>) -> Self {
+        Page { request, items: ResultCollector::from(items) }
+    }
+
+    fn failed(request: PageRequest, error: anyhow::Error) -> Self {
+        let mut items = ResultCollector::new();
+        items.errors.push(error);
+        Page { request, items }
+    }
+}
+
+async fn fetch_one<T, F, Fut, R>(executor: &R, fetch: &F, request: PageRequest) -> Result<PageResponse<T>>
+where
+    T: Send + Sync,
+    F: Fn(PageRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<PageResponse<T>>> + Send + Sync,
+    R: AsyncExecutor + Send + Sync,
+{
+    executor.run(vec![fetch(request)])
+        .await
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| Err(anyhow!("The executor returned no result")))
+        .and_then(|r| r)
+}
+
+/// Pages one after the other from `position`, `next` gives the position of the page after a
+/// non-empty page or the reason to stop
+async fn sequential<T, F, Fut, R>(
+    executor: &R,
+    fetch: &F,
+    pages: &mut Vec<Page<T>>,
+    mut position: PagePosition,
+    max_pages: usize,
+    next: impl Fn(&PageRequest, &PageResponse<T>) -> Result<PagePosition, StopReason>,
+) -> StopReason
+where
+    T: Send + Sync,
+    F: Fn(PageRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<PageResponse<T>>> + Send + Sync,
+    R: AsyncExecutor + Send + Sync,
+{
+    let mut visited: HashSet<PagePosition> = pages.iter().map(|page| page.request.position.clone()).collect();
+    loop {
+        if pages.len() >= max_pages {
+            return StopReason::Capped;
+        }
+        visited.insert(position.clone());
+        let request = PageRequest { index: pages.len(), position };
+        let response = match fetch_one(executor, fetch, request.clone()).await {
+            Ok(response) => response,
+            Err(e) => {
+                pages.push(Page::failed(request, e));
+                return StopReason::Failed;
+            },
+        };
+        let step = match response.items.is_empty() {
+            true => Err(StopReason::Exhausted),
+            false => next(&request, &response),
+        };
+        pages.push(Page::fetched(request, response.items));
+        match step {
+            Ok(next_position) if visited.contains(&next_position) => return StopReason::Repeated,
+            Ok(next_position) => position = next_position,
+            Err(stop) => return stop,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Result};
+    use crate::SimpleRateLimiter;
+    use super::{Cursor, NextLink, OffsetWithTotal, PagePosition, PageRequest, PageResponse, PageUntilExhausted, Paginator, StopReason};
+
+    const ITEMS: usize = 25;
+
+    // 25 items, page positions are interpreted as item offsets or page numbers of 10 items
+    async fn fetch(request: PageRequest) -> Result<PageResponse<usize>> {
+        let first = match &request.position {
+            PagePosition::Offset(offset) => *offset,
+            PagePosition::Number(number) => number * 10,
+            PagePosition::Link(url) | PagePosition::Cursor(Some(url)) => url.trim_start_matches("/p").parse::<usize>()? * 10,
+            PagePosition::Cursor(None) => 0,
+        };
+        let items: Vec<usize> = (first..(first + 10).min(ITEMS)).collect();
+        let next = (first + 10 < ITEMS).then(|| format!("/p{}", first / 10 + 1));
+        Ok(PageResponse::new(items).with_total(Some(ITEMS)).with_next(next))
+    }
+
+    #[tokio::test]
+    async fn test_strategies() {
+        let executor = SimpleRateLimiter::default();
+
+        let offset = OffsetWithTotal::new(10).paginate(&executor, fetch).await;
+        let pages = PageUntilExhausted::new(0).with_page_size(10).paginate(&executor, fetch).await;
+        let links = NextLink::new("/p0").paginate(&executor, fetch).await;
+        let cursor = Cursor::new().paginate(&executor, fetch).await;
+
+        for results in [offset, pages, links, cursor] {
+            assert_eq!(results.stop, StopReason::Exhausted);
+            assert_eq!(results.pages.len(), 3);
+            assert_eq!(results.into_collector("test").successes, (0..ITEMS).collect::<Vec<usize>>());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_conditions() {
+        let executor = SimpleRateLimiter::default();
+        let failing = |request: PageRequest| async move {
+            match request.index {
+                1 => Err(anyhow!("page {} failed", request.index)),
+                _ => Ok(PageResponse::new(vec![request.index]).with_next(Some("/same".to_owned()))),
+            }
+        };
+
+        let capped = PageUntilExhausted::new(1).with_max_pages(2).paginate(&executor, fetch).await;
+        let failed = PageUntilExhausted::new(0).paginate(&executor, failing).await;
+        let repeated = NextLink::new("/start").paginate(&executor, |request: PageRequest| async move {
+            Ok(PageResponse::new(vec![request.index]).with_next(Some("/start".to_owned())))
+        }).await;
+        let empty = PageUntilExhausted::new(3).paginate(&executor, fetch).await;
+
+        assert_eq!(capped.stop, StopReason::Capped);
+        assert_eq!(capped.into_collector("test").errors.len(), 1);
+        assert_eq!(failed.stop, StopReason::Failed);
+        assert_eq!(failed.pages.len(), 2);
+        assert_eq!(repeated.stop, StopReason::Repeated);
+        assert_eq!(repeated.pages.len(), 1);
+        assert_eq!(empty.stop, StopReason::Exhausted);
+        assert!(empty.into_collector("test").successes.is_empty());
+    }
+}
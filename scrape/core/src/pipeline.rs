@@ -0,0 +1,389 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use anyhow::Result;
+use super::{AsyncExecutor, ResultCollector, ScrapeError};
+
+type BoxedFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+type StageRunner<'a, I, O> = Box<dyn FnOnce(Vec<I>, Option<Duration>) -> BoxedFuture<'a, Vec<Result<ResultCollector<O>>>> + Send + 'a>;
+type PipelineRunner<'a, I, O> = Box<dyn FnOnce(ResultCollector<I>) -> BoxedFuture<'a, (ResultCollector<O>, PipelineReport)> + Send + 'a>;
+
+/// Values a stage function may return, they are turned into a `ResultCollector`
+pub trait IntoCollector<T: Send + Sync> {
+    fn into_collector(self) -> ResultCollector<T>;
+}
+
+impl<T: Send + Sync> IntoCollector<T> for ResultCollector<T> {
+    fn into_collector(self) -> ResultCollector<T> {
+        self
+    }
+}
+
+impl<T: Send + Sync> IntoCollector<T> for Result<Vec<T>> {
+    fn into_collector(self) -> ResultCollector<T> {
+        match self {
+            Ok(values) => ResultCollector::from(values),
+            Err(e) => ResultCollector::from(e),
+        }
+    }
+}
+
+/// Determines what happens with the pipeline when a stage produces errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Collect the errors and continue with the successes
+    #[default]
+    Continue,
+    /// Abort the pipeline if the stage produced any error
+    FailFast,
+    /// Abort the pipeline if the stage produced more than the given number of errors
+    MaxErrors(usize),
+}
+
+impl ErrorPolicy {
+    fn should_abort(&self, errors: usize) -> bool {
+        match self {
+            ErrorPolicy::Continue => false,
+            ErrorPolicy::FailFast => errors > 0,
+            ErrorPolicy::MaxErrors(max) => errors > *max,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum StageStatus {
+    Completed,
+    Aborted,
+    Skipped,
+}
+
+/// Timing and counts of a single stage run. `errors` only counts
+/// the errors produced by the stage itself
+#[derive(Debug, Serialize)]
+pub struct StageReport {
+    pub name: String,
+    pub status: StageStatus,
+    pub inputs: usize,
+    pub successes: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PipelineReport {
+    pub name: String,
+    pub stages: Vec<StageReport>,
+}
+
+impl PipelineReport {
+    fn new(name: &str) -> Self {
+        PipelineReport { name: name.to_owned(), stages: Vec::new() }
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.stages.iter().any(|s| s.status == StageStatus::Aborted)
+    }
+}
+
+impl fmt::Display for PipelineReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pipeline '{}'", self.name)?;
+        for stage in self.stages.iter() {
+            write!(
+                f,
+                "\n  {} [{:?}]: {} in, {} ok, {} errors, {} ms",
+                stage.name, stage.status, stage.inputs, stage.successes, stage.errors, stage.elapsed_ms,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A named step in a `Pipeline`. A stage applies a function to every success of
+/// its input collector, using its own executor, timeout and error policy.
+///
+/// Stages can be run on their own, which allows testing them with fixture inputs
+///
+/// # Example
+/// ```
+/// use scrape_core::{ResultCollector, SimpleRateLimiter, Stage};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let executor = SimpleRateLimiter::default();
+/// let stage = Stage::new("double", &executor, |v: i32| async move { Ok(vec![v * 2]) });
+/// let (result, report) = stage.run(ResultCollector::from(vec![1, 2])).await;
+///
+/// assert_eq!(result.successes, vec![2, 4]);
+/// assert_eq!(report.successes, 2);
+/// # }
+/// ```
+pub struct Stage<'a, I: Send + Sync, O: Send + Sync> {
+    name: String,
+    timeout: Option<Duration>,
+    error_policy: ErrorPolicy,
+    runner: StageRunner<'a, I, O>,
+}
+
+impl<'a, I: Send + Sync + 'a, O: Send + Sync + 'a> Stage<'a, I, O> {
+    pub fn new<R, F, Fut, C>(name: &str, executor: &'a R, func: F) -> Self
+    where
+        R: AsyncExecutor + Send + Sync,
+        F: Fn(I) -> Fut + Send + Sync + 'a,
+        Fut: Future<Output = C> + Send + Sync + 'a,
+        C: IntoCollector<O> + Send + Sync + 'a,
+    {
+        let stage_name = name.to_owned();
+        let runner: StageRunner<'a, I, O> = Box::new(move |inputs, timeout| {
+            Box::pin(async move {
+                let futures: Vec<_> = inputs
+                    .into_iter()
+                    .map(|input| run_with_timeout(func(input), timeout, stage_name.clone()))
+                    .collect();
+                executor.run(futures).await
+            })
+        });
+
+        Stage {
+            name: name.to_owned(),
+            timeout: None,
+            error_policy: ErrorPolicy::default(),
+            runner,
+        }
+    }
+
+    /// Limit the time a single invocation of the stage function may take
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run the stage on the successes of `input`. Errors already present in `input`
+    /// are passed on to the output.
+    pub async fn run(self, input: ResultCollector<I>) -> (ResultCollector<O>, StageReport) {
+        let start = Instant::now();
+        let inputs = input.successes.len();
+
+        let mut output = ResultCollector::new();
+        output.errors = input.errors;
+        let previous_errors = output.errors.len();
+
+        for result in (self.runner)(input.successes, self.timeout).await.into_iter() {
+            match result {
+                Ok(collector) => output.extend(collector),
+                Err(e) => output.errors.push(e),
+            };
+        }
+
+        let errors = output.errors.len() - previous_errors;
+        let mut status = StageStatus::Completed;
+        if self.error_policy.should_abort(errors) {
+            status = StageStatus::Aborted;
+            output.successes.clear();
+            output.errors.push(ScrapeError::StageAborted { stage: self.name.clone(), errors }.into());
+        }
+
+        let report = StageReport {
+            name: self.name,
+            status,
+            inputs,
+            successes: output.successes.len(),
+            errors,
+            elapsed_ms: start.elapsed().as_millis(),
+        };
+        (output, report)
+    }
+
+    fn skip(self, input: ResultCollector<I>) -> (ResultCollector<O>, StageReport) {
+        let mut output = ResultCollector::new();
+        output.errors = input.errors;
+
+        let report = StageReport {
+            name: self.name,
+            status: StageStatus::Skipped,
+            inputs: input.successes.len(),
+            successes: 0,
+            errors: 0,
+            elapsed_ms: 0,
+        };
+        (output, report)
+    }
+}
+
+async fn run_with_timeout<O, C>(future: impl Future<Output = C>, timeout: Option<Duration>, stage: String) -> ResultCollector<O>
+where
+    O: Send + Sync,
+    C: IntoCollector<O>,
+{
+    let Some(duration) = timeout else {
+        return future.await.into_collector();
+    };
+
+    match tokio::time::timeout(duration, future).await {
+        Ok(result) => result.into_collector(),
+        Err(_) => ResultCollector::from(anyhow::Error::from(
+            ScrapeError::StageTimedOut { stage, timeout_ms: duration.as_millis() }
+        )),
+    }
+}
+
+/// Chain multiple `Stage`s, the output of a stage is the input of the next.
+/// Running the pipeline gives the collected results and a `PipelineReport`
+/// containing timing and counts for each stage.
+///
+/// When a stage aborts according to its `ErrorPolicy`, all following stages are skipped
+/// and only the collected errors are returned.
+///
+/// # Example
+/// ```
+/// use scrape_core::{Pipeline, ResultCollector, SimpleRateLimiter, Stage};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let executor = SimpleRateLimiter::default();
+/// let (result, report) = Pipeline::new("example")
+///     .stage(Stage::new("split", &executor, |v: i32| async move { Ok(vec![v, v + 1]) }))
+///     .stage(Stage::new("stringify", &executor, |v: i32| async move { Ok(vec![v.to_string()]) }))
+///     .run(ResultCollector::from(vec![1, 3]))
+///     .await;
+///
+/// assert_eq!(result.successes, vec!["1", "2", "3", "4"]);
+/// # }
+/// ```
+pub struct Pipeline<'a, I: Send + Sync, O: Send + Sync> {
+    runner: PipelineRunner<'a, I, O>,
+}
+
+impl<'a, I: Send + Sync + 'a> Pipeline<'a, I, I> {
+    pub fn new(name: &str) -> Self {
+        let report = PipelineReport::new(name);
+        Pipeline {
+            runner: Box::new(move |input| Box::pin(async move { (input, report) })),
+        }
+    }
+}
+
+impl<'a, I: Send + Sync + 'a, O: Send + Sync + 'a> Pipeline<'a, I, O> {
+    pub fn stage<N: Send + Sync + 'a>(self, stage: Stage<'a, O, N>) -> Pipeline<'a, I, N> {
+        let previous = self.runner;
+        Pipeline {
+            runner: Box::new(move |input| Box::pin(async move {
+                let (collected, mut report) = previous(input).await;
+                let (output, stage_report) = match report.aborted() {
+                    true => stage.skip(collected),
+                    false => stage.run(collected).await,
+                };
+                report.stages.push(stage_report);
+                (output, report)
+            })),
+        }
+    }
+
+    pub async fn run(self, input: ResultCollector<I>) -> (ResultCollector<O>, PipelineReport) {
+        (self.runner)(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use anyhow::{anyhow, Result};
+    use crate::{ResultCollector, SimpleRateLimiter};
+    use super::{ErrorPolicy, Pipeline, Stage, StageStatus};
+
+    async fn split(val: i32) -> Result<Vec<i32>> {
+        if val < 0 {
+            return Err(anyhow!("negative: {}", val));
+        }
+        Ok(vec![val, val + 1])
+    }
+
+    async fn to_string(val: i32) -> ResultCollector<String> {
+        ResultCollector::from_value(val.to_string())
+    }
+
+    async fn sleep(val: i32) -> Result<Vec<i32>> {
+        tokio::time::sleep(Duration::from_millis(val as u64)).await;
+        Ok(vec![val])
+    }
+
+    #[tokio::test]
+    async fn test_stage_with_fixture() {
+        let executor = SimpleRateLimiter::default();
+        let mut input = ResultCollector::from(vec![1, -1]);
+        input.collect(Err(anyhow!("earlier")));
+
+        let (result, report) = Stage::new("split", &executor, split).run(input).await;
+
+        assert_eq!(result.successes, vec![1, 2]);
+        assert_eq!(result.list_error_messages(), vec!["earlier".to_owned(), "negative: -1".to_owned()]);
+        assert_eq!(report.inputs, 2);
+        assert_eq!(report.successes, 2);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.status, StageStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_stage_timeout() {
+        let executor = SimpleRateLimiter::default();
+        let stage = Stage::new("sleep", &executor, sleep).timeout(Duration::from_millis(50));
+
+        let (result, report) = stage.run(ResultCollector::from(vec![1, 500])).await;
+
+        assert_eq!(result.successes, vec![1]);
+        assert_eq!(result.list_error_messages(), vec!["Stage 'sleep' timed out after 50 ms".to_owned()]);
+        assert_eq!(report.errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let executor = SimpleRateLimiter::default();
+        let (result, report) = Pipeline::new("test")
+            .stage(Stage::new("split", &executor, split))
+            .stage(Stage::new("to_string", &executor, to_string))
+            .run(ResultCollector::from(vec![1, 3]))
+            .await;
+
+        assert_eq!(result.successes, vec!["1", "2", "3", "4"]);
+        assert_eq!(report.stages.len(), 2);
+        assert_eq!(report.stages[1].inputs, 4);
+        assert!(!report.aborted());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_fail_fast() {
+        let executor = SimpleRateLimiter::default();
+        let (result, report) = Pipeline::new("test")
+            .stage(Stage::new("split", &executor, split).error_policy(ErrorPolicy::FailFast))
+            .stage(Stage::new("to_string", &executor, to_string))
+            .run(ResultCollector::from(vec![1, -1]))
+            .await;
+
+        assert!(result.successes.is_empty());
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(report.stages[0].status, StageStatus::Aborted);
+        assert_eq!(report.stages[1].status, StageStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn test_max_errors_policy() {
+        let executor = SimpleRateLimiter::default();
+        let stage = Stage::new("split", &executor, split).error_policy(ErrorPolicy::MaxErrors(1));
+
+        let (result, report) = stage.run(ResultCollector::from(vec![1, -1])).await;
+
+        assert_eq!(result.successes, vec![1, 2]);
+        assert_eq!(report.status, StageStatus::Completed);
+    }
+}
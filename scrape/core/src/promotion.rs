@@ -0,0 +1,308 @@
+use std::fmt;
+use std::sync::OnceLock;
+use chrono::{Datelike, Local, NaiveDate};
+use regex::Regex;
+use serde::Serialize;
+use super::Money;
+
+// Compile a regex once and reuse it on later calls
+macro_rules! regex {
+    ($pattern:expr) => {{
+        static REGEX: OnceLock<Regex> = OnceLock::new();
+        REGEX.get_or_init(|| Regex::new(&$pattern).expect("Invalid promotion regex"))
+    }};
+}
+
+// Amounts in promotion labels, e.g. "€ 3", "3,-" or "2.99"
+const AMOUNT: &str = r"€?\s*(\d+(?:[.,]\d{1,2}|,-)?)";
+const MONTHS: [&str; 12] = ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"];
+// Dates without a year further in the past than this are assumed to be in the next year
+const MAX_DAYS_IN_PAST: i64 = 180;
+
+/// An offer as shown next to a product
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Promotion {
+    /// "1+1 gratis", "2+1 gratis"
+    BuyGetFree { buy: u32, free: u32 },
+    /// "2e halve prijs", "2e gratis", "3e 25% korting"
+    NthItemDiscount { nth: u32, percent: u32 },
+    /// "2 voor € 3"
+    MultiBuy { count: u32, price: Money },
+    /// "25% korting"
+    PercentOff { percent: u32 },
+    /// A reduced price for a single item, e.g. an AH Bonus price or "nu € 1,99"
+    FixedPrice { price: Money },
+}
+
+impl Promotion {
+    /// Parse the Dutch promotion labels used by the stores, returns `None` for labels that
+    /// don't describe a price reduction
+    pub fn parse(label: &str) -> Option<Promotion> {
+        let text = label.to_lowercase().split_whitespace().collect::<Vec<&str>>().join(" ");
+        let number = |caps: &regex::Captures, i: usize| caps.get(i)?.as_str().parse::<u32>().ok();
+        let amount = |caps: &regex::Captures, i: usize| Money::parse(caps.get(i)?.as_str()).ok();
+
+        if let Some(caps) = regex!(r"(\d+)\s*\+\s*(\d+)\s*(?:gratis|free)").captures(&text) {
+            let (buy, free) = (number(&caps, 1)?, number(&caps, 2)?);
+            return (buy > 0 && free > 0).then_some(Promotion::BuyGetFree { buy, free });
+        }
+        if let Some(caps) = regex!(r"(\d+)e\s+(?:artikel\s+)?(halve prijs|gratis|(\d+)\s*% korting)").captures(&text) {
+            let nth = number(&caps, 1)?;
+            let percent = match caps.get(2)?.as_str() {
+                "halve prijs" => 50,
+                "gratis" => 100,
+                _ => number(&caps, 3)?,
+            };
+            return (nth > 1 && (1..=100).contains(&percent)).then_some(Promotion::NthItemDiscount { nth, percent });
+        }
+        if let Some(caps) = regex!(&format!(r"(\d+)\s+(?:stuks\s+)?voor\s+{}", AMOUNT)).captures(&text) {
+            let count = number(&caps, 1)?;
+            return (count > 0).then_some(Promotion::MultiBuy { count, price: amount(&caps, 2)? });
+        }
+        if let Some(caps) = regex!(r"(\d+)\s*%\s*(?:korting|off)").captures(&text) {
+            let percent = number(&caps, 1)?;
+            return (1..=100).contains(&percent).then_some(Promotion::PercentOff { percent });
+        }
+        if let Some(caps) = regex!(&format!(r"(?:^|\s)(?:nu|voor)\s+{}", AMOUNT)).captures(&text) {
+            return Some(Promotion::FixedPrice { price: amount(&caps, 1)? });
+        }
+        None
+    }
+
+    /// Short name of the variant, used for storage
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Promotion::BuyGetFree { .. } => "buy_get_free",
+            Promotion::NthItemDiscount { .. } => "nth_item_discount",
+            Promotion::MultiBuy { .. } => "multi_buy",
+            Promotion::PercentOff { .. } => "percent_off",
+            Promotion::FixedPrice { .. } => "fixed_price",
+        }
+    }
+
+    /// Number of items that have to be bought to get the full discount
+    pub fn min_quantity(&self) -> u32 {
+        match self {
+            Promotion::BuyGetFree { buy, free } => buy + free,
+            Promotion::NthItemDiscount { nth, .. } => *nth,
+            Promotion::MultiBuy { count, .. } => *count,
+            Promotion::PercentOff { .. } | Promotion::FixedPrice { .. } => 1,
+        }
+    }
+
+    /// Total price for `quantity` items that normally cost `regular` each. Items that
+    /// don't complete a group are charged the regular price
+    pub fn total_price(&self, regular: Money, quantity: u32) -> Money {
+        let quantity = quantity as i64;
+        let group = self.min_quantity() as i64;
+        let (groups, rest) = (quantity / group, quantity % group);
+
+        match *self {
+            Promotion::BuyGetFree { buy, .. } => regular * (groups * buy as i64 + rest),
+            Promotion::NthItemDiscount { percent, .. } => {
+                let discounted = regular.mul_ratio(100 - percent as i64, 100);
+                (regular * (group - 1) + discounted) * groups + regular * rest
+            },
+            Promotion::MultiBuy { price, .. } => price * groups + regular * rest,
+            Promotion::PercentOff { percent } => regular.mul_ratio(100 - percent as i64, 100) * quantity,
+            Promotion::FixedPrice { price } => price * quantity,
+        }
+    }
+
+    /// Average price per item when buying `quantity` items that normally cost `regular` each
+    pub fn effective_price(&self, regular: Money, quantity: u32) -> Money {
+        match quantity {
+            0 => regular,
+            _ => self.total_price(regular, quantity).mul_ratio(1, quantity as i64),
+        }
+    }
+}
+
+impl fmt::Display for Promotion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Promotion::BuyGetFree { buy, free } => write!(f, "{}+{} gratis", buy, free),
+            Promotion::NthItemDiscount { nth, percent: 50 } => write!(f, "{}e halve prijs", nth),
+            Promotion::NthItemDiscount { nth, percent: 100 } => write!(f, "{}e gratis", nth),
+            Promotion::NthItemDiscount { nth, percent } => write!(f, "{}e {}% korting", nth, percent),
+            Promotion::MultiBuy { count, price } => write!(f, "{} voor {}", count, price),
+            Promotion::PercentOff { percent } => write!(f, "{}% korting", percent),
+            Promotion::FixedPrice { price } => write!(f, "nu {}", price),
+        }
+    }
+}
+
+/// A promotion together with the label it was parsed from and the period it's valid in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Offer {
+    pub promotion: Promotion,
+    pub label: String,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_until: Option<NaiveDate>,
+}
+
+impl Offer {
+    pub fn new(promotion: Promotion, label: &str) -> Self {
+        Offer { promotion, label: label.trim().to_owned(), valid_from: None, valid_until: None }
+    }
+
+    /// Parse an offer from its label and, when the store shows it, a text with the validity period
+    pub fn parse(label: &str, validity: Option<&str>) -> Option<Offer> {
+        Offer::parse_on(label, validity, Local::now().date_naive())
+    }
+
+    /// Same as `parse`, dates without a year are placed relative to `today`
+    pub fn parse_on(label: &str, validity: Option<&str>, today: NaiveDate) -> Option<Offer> {
+        let offer = Offer::new(Promotion::parse(label)?, label);
+        match validity {
+            Some(validity) => Some(offer.with_validity_on(validity, today)),
+            None => Some(offer),
+        }
+    }
+
+    /// Set the validity period from a text like "t/m 18 okt"
+    pub fn with_validity(self, validity: &str) -> Self {
+        self.with_validity_on(validity, Local::now().date_naive())
+    }
+
+    pub fn with_validity_on(mut self, validity: &str, today: NaiveDate) -> Self {
+        (self.valid_from, self.valid_until) = parse_validity(validity, today);
+        self
+    }
+
+    pub fn is_valid_on(&self, date: NaiveDate) -> bool {
+        !matches!(self.valid_from, Some(from) if from > date) && !matches!(self.valid_until, Some(until) if until < date)
+    }
+
+    /// Average price per item when buying `quantity` items on `date`, the regular price
+    /// when the offer isn't valid on that date
+    pub fn effective_price(&self, regular: Money, quantity: u32, date: NaiveDate) -> Money {
+        match self.is_valid_on(date) {
+            true => self.promotion.effective_price(regular, quantity),
+            false => regular,
+        }
+    }
+}
+
+/// Parse validity texts such as "t/m 18 okt", "geldig van 12-10 t/m 18-10-2026" or "vanaf 1 november"
+pub fn parse_validity(text: &str, today: NaiveDate) -> (Option<NaiveDate>, Option<NaiveDate>) {
+    let text = text.to_lowercase();
+    let dates: Vec<(usize, NaiveDate)> = find_dates(&text, today);
+    let until_marker = ["t/m", "tot en met", "tot "].iter().filter_map(|m| text.find(m)).min();
+
+    match (dates.as_slice(), until_marker) {
+        ([(_, from), (_, until), ..], _) => (Some(*from), Some(*until)),
+        ([(pos, date)], Some(marker)) if marker < *pos => (None, Some(*date)),
+        ([(_, date)], _) if text.contains("van") => (Some(*date), None),
+        ([(_, date)], _) => (None, Some(*date)),
+        ([], _) => (None, None),
+    }
+}
+
+fn find_dates(text: &str, today: NaiveDate) -> Vec<(usize, NaiveDate)> {
+    let numeric = regex!(r"\b(\d{1,2})[-/](\d{1,2})(?:[-/](\d{4}|\d{2}))?\b");
+    let written = regex!(r"\b(\d{1,2})\s+([a-z]{3})[a-z]*\.?(?:\s+(\d{4}))?");
+
+    let numeric_dates = numeric.captures_iter(text).filter_map(|caps| {
+        let day = caps.get(1)?.as_str().parse().ok()?;
+        let month = caps.get(2)?.as_str().parse().ok()?;
+        let year = caps.get(3).and_then(|y| y.as_str().parse::<i32>().ok()).map(|y| if y < 100 { 2000 + y } else { y });
+        Some((caps.get(0)?.start(), resolve_date(day, month, year, today)?))
+    });
+    let written_dates = written.captures_iter(text).filter_map(|caps| {
+        let day = caps.get(1)?.as_str().parse().ok()?;
+        let month_name = caps.get(2)?.as_str().replace("maa", "mrt");
+        let month = MONTHS.iter().position(|m| *m == month_name)? as u32 + 1;
+        let year = caps.get(3).and_then(|y| y.as_str().parse().ok());
+        Some((caps.get(0)?.start(), resolve_date(day, month, year, today)?))
+    });
+
+    let mut dates: Vec<(usize, NaiveDate)> = numeric_dates.chain(written_dates).collect();
+    dates.sort_by_key(|(pos, _)| *pos);
+    dates
+}
+
+fn resolve_date(day: u32, month: u32, year: Option<i32>, today: NaiveDate) -> Option<NaiveDate> {
+    if let Some(year) = year {
+        return NaiveDate::from_ymd_opt(year, month, day);
+    }
+    let date = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+    match (today - date).num_days() > MAX_DAYS_IN_PAST {
+        true => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+        false => Some(date),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::Money;
+    use super::{parse_validity, Offer, Promotion};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            ("1+1 gratis", Promotion::BuyGetFree { buy: 1, free: 1 }),
+            ("2 + 1 GRATIS", Promotion::BuyGetFree { buy: 2, free: 1 }),
+            ("2e halve prijs", Promotion::NthItemDiscount { nth: 2, percent: 50 }),
+            ("2e gratis", Promotion::NthItemDiscount { nth: 2, percent: 100 }),
+            ("3e artikel 25% korting", Promotion::NthItemDiscount { nth: 3, percent: 25 }),
+            ("2 voor €3", Promotion::MultiBuy { count: 2, price: Money::from_cents(300) }),
+            ("3 voor 5,49", Promotion::MultiBuy { count: 3, price: Money::from_cents(549) }),
+            ("25% korting", Promotion::PercentOff { percent: 25 }),
+            ("Nu € 1,99", Promotion::FixedPrice { price: Money::from_cents(199) }),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(Promotion::parse(input), Some(expected), "parsing '{}'", input);
+        }
+        for input in ["", "bonus", "nieuw", "0+1 gratis", "150% korting"] {
+            assert_eq!(Promotion::parse(input), None, "parsing '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_effective_price() {
+        let regular = Money::from_cents(200);
+
+        let one_plus_one = Promotion::BuyGetFree { buy: 1, free: 1 };
+        assert_eq!(one_plus_one.effective_price(regular, 2).cents(), 100);
+        assert_eq!(one_plus_one.total_price(regular, 3).cents(), 400);
+
+        let half_price = Promotion::NthItemDiscount { nth: 2, percent: 50 };
+        assert_eq!(half_price.effective_price(regular, 2).cents(), 150);
+        assert_eq!(half_price.effective_price(regular, 1).cents(), 200);
+
+        let multi_buy = Promotion::MultiBuy { count: 2, price: Money::from_cents(300) };
+        assert_eq!(multi_buy.effective_price(regular, 2).cents(), 150);
+        assert_eq!(multi_buy.total_price(regular, 3).cents(), 500);
+
+        assert_eq!(Promotion::PercentOff { percent: 25 }.effective_price(regular, 1).cents(), 150);
+        assert_eq!(Promotion::FixedPrice { price: Money::from_cents(179) }.effective_price(regular, 4).cents(), 179);
+    }
+
+    #[test]
+    fn test_parse_validity() {
+        let today = date(2026, 10, 15);
+
+        assert_eq!(parse_validity("t/m 18 okt", today), (None, Some(date(2026, 10, 18))));
+        assert_eq!(parse_validity("Geldig van 12-10 t/m 18-10-2026", today), (Some(date(2026, 10, 12)), Some(date(2026, 10, 18))));
+        assert_eq!(parse_validity("vanaf 1 november", today), (Some(date(2026, 11, 1)), None));
+        assert_eq!(parse_validity("t/m 4 jan", today), (None, Some(date(2027, 1, 4))));
+        assert_eq!(parse_validity("deze week", today), (None, None));
+    }
+
+    #[test]
+    fn test_offer_validity() {
+        let offer = Offer::parse_on("2e halve prijs", Some("t/m 18 okt"), date(2026, 10, 15)).unwrap();
+        let regular = Money::from_cents(200);
+
+        assert_eq!(offer.effective_price(regular, 2, date(2026, 10, 18)).cents(), 150);
+        assert_eq!(offer.effective_price(regular, 2, date(2026, 10, 19)).cents(), 200);
+    }
+}
@@ -60,7 +60,7 @@ impl RandomDelayRateLimiter {
         let delay_seconds;
         {
             let rnd = rand::thread_rng().gen_range(self.min_delay_ms..self.max_delay_ms + 1);
-            delay_seconds = rnd.clone();
+            delay_seconds = rnd;
         }
         let permit = self.semaphore.acquire().await?;
         tokio::time::sleep(tokio::time::Duration::from_millis(delay_seconds as u64)).await;
@@ -79,4 +79,19 @@ impl AsyncExecutor for RandomDelayRateLimiter {
         )
         .await
     }
+}
+
+/// Either of the available rate limiters, allows picking one at runtime
+pub enum RateLimiter {
+    Simple(SimpleRateLimiter),
+    RandomDelay(RandomDelayRateLimiter),
+}
+
+impl AsyncExecutor for RateLimiter {
+    async fn run<T: Send + Sync>(&self, futures: Vec<impl Future<Output = T> + Send + Sync>) -> Vec<Result<T>> {
+        match self {
+            RateLimiter::Simple(limiter) => limiter.run(futures).await,
+            RateLimiter::RandomDelay(limiter) => limiter.run(futures).await,
+        }
+    }
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
+use std::collections::HashSet;
+use std::hash::Hash;
 use std::iter::FromIterator;
 use std::future::Future;
-use super::AsyncExecutor;
+use serde::{Serialize, Serializer};
+use super::{AsyncExecutor, CollectorSummary, ErrorRecord};
 use anyhow::Result;
 
 pub trait Transform<T: Send + Sync, I: Send + Sync> {
@@ -41,12 +44,26 @@ where
 /// assert_eq!(result.successes, vec![1, 2, 3, 4]);
 /// assert_eq!(result.list_error_messages(), vec!["-1".to_owned(), "0".to_owned()]);
 /// ```
-#[derive(Debug)]
+/// 
+/// When `T: Serialize` the collector can be serialized, errors are
+/// rendered as structured `ErrorRecord`s.
+#[derive(Debug, Serialize)]
 pub struct ResultCollector<T: Send + Sync> {
     pub successes: Vec<T>,
+    #[serde(serialize_with = "serialize_errors")]
     pub errors: Vec<anyhow::Error>,
 }
 
+fn serialize_errors<S: Serializer>(errors: &[anyhow::Error], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(errors.iter().map(ErrorRecord::from))
+}
+
+impl<T: Send + Sync> Default for ResultCollector<T> {
+    fn default() -> Self {
+        ResultCollector::new()
+    }
+}
+
 impl<T: Send + Sync> ResultCollector<T> {
     pub fn new() -> Self {
         ResultCollector {
@@ -73,6 +90,12 @@ impl<T: Send + Sync> ResultCollector<T> {
         self.errors.iter().map(|e| e.to_string()).collect()
     }
 
+    /// Summarize the collected results: counts, errors grouped by kind
+    /// and a few example messages per kind
+    pub fn summary(&self) -> CollectorSummary {
+        CollectorSummary::new(self.successes.len(), self.errors.iter())
+    }
+
     pub fn collect(&mut self, result: Result<T, anyhow::Error>) {
         match result {
             Ok(success) => self.successes.push(success),
@@ -94,6 +117,14 @@ impl<T: Send + Sync> ResultCollector<T> {
         }
     }
 
+    /// Keep only the first of the `Ok` values that have the same key, returns how many were removed
+    pub fn dedupe_by_key<K: Eq + Hash>(&mut self, key: impl Fn(&T) -> K) -> usize {
+        let mut seen: HashSet<K> = HashSet::new();
+        let before = self.successes.len();
+        self.successes.retain(|success| seen.insert(key(success)));
+        before - self.successes.len()
+    }
+
     /// Create an Iterator over the Ok variants
     pub fn iter_ok(&self) -> impl Iterator<Item = &T> {
         self.successes.iter()
@@ -213,6 +244,7 @@ impl<T: Send + Sync, I: Send + Sync> Transform<T, Result<I, anyhow::Error>> for
     /// assert_eq!(transformed.successes, Vec::<&str>::default());
     /// ```
     fn transform(self, func: impl Fn(T) -> Result<I, anyhow::Error>) -> ResultCollector<Self::Collected> {
+        #[allow(clippy::redundant_closure)]
         let mut results: ResultCollector<I> = self.successes.into_iter().map(|inp| (func)(inp)).collect();
         results.errors.extend(self.errors);
         results
@@ -235,6 +267,7 @@ AsyncTransform<T, Result<I, anyhow::Error>, F> for ResultCollector<T>
     /// AsyncExecutor is used to expose control over the execution of the futures. E.g. to limit the
     /// number of concurrent requests.
     async fn transform_async<R: AsyncExecutor + Send + Sync>(self, func: impl Fn(T) -> F, executor: &R) -> ResultCollector<Self::Collected> {
+        #[allow(clippy::redundant_closure)]
         let mut results: ResultCollector<I> = executor
             .run(
             self.successes
@@ -268,6 +301,7 @@ AsyncTransform<T, ResultCollector<I>, F> for ResultCollector<T> {
     /// number of concurrent requests.
     async fn transform_async<R: AsyncExecutor + Send + Sync>(self, func: impl Fn(T) -> F + Send + Sync, executor: &R) -> ResultCollector<Self::Collected> {
         let mut new_collector = ResultCollector::new();
+        #[allow(clippy::redundant_closure)]
         let results = executor
             .run(
             self.successes
@@ -291,7 +325,7 @@ AsyncTransform<T, ResultCollector<I>, F> for ResultCollector<T> {
 mod tests {
     use std::vec;
     use anyhow::{anyhow, Result};
-    use crate::{AsyncTransform, SimpleRateLimiter, Transform};
+    use crate::{AsyncTransform, ScrapeError, SimpleRateLimiter, Transform};
     use super::ResultCollector;
 
     fn test_func(val: i32) -> Result<Vec<i32>> {
@@ -316,6 +350,7 @@ mod tests {
     async fn test_transform_async_result_collector() {
         let rate_limiter = SimpleRateLimiter::default();
         let collector = ResultCollector::from(vec![1, 2, 3]);
+        #[allow(clippy::redundant_closure)]
         let result = collector.transform_async(|v| test_async_return_result_collector(v), &rate_limiter).await;
 
         assert_eq!(result.successes, vec![2, 3, 4]);
@@ -331,6 +366,7 @@ mod tests {
     async fn test_transform_async() {
         let rate_limiter = SimpleRateLimiter::new(None);
         let collector = ResultCollector::from(vec![-1, 0, 1, 3]);
+        #[allow(clippy::redundant_closure)]
         let result = collector.transform_async(|e| test_async_returns_result(e), &rate_limiter).await.flatten();
         assert_eq!(result.successes, vec![1, 2, 3, 4]);
         assert_eq!(result.list_error_messages(), vec!["-1".to_owned(), "0".to_owned()]);
@@ -339,6 +375,7 @@ mod tests {
     #[test]
     fn test_combination() {
         let collector = ResultCollector::from(vec![-1, 0, 1, 3]);
+        #[allow(clippy::redundant_closure)]
         let result = collector.transform(|e| test_func(e)).flatten();
         assert_eq!(result.successes, vec![1, 2, 3, 4]);
         assert_eq!(result.list_error_messages(), vec!["-1".to_owned(), "0".to_owned()]);
@@ -412,4 +449,43 @@ mod tests {
         assert_eq!(collector.successes, vec![1, 2, 3, 4]);
         assert_eq!(collector.list_error_messages(), vec!["oops".to_owned(), "oops2".to_owned()]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_dedupe_by_key() {
+        let mut collector = ResultCollector::from(vec![("melk", 1), ("kaas", 2), ("melk", 3)]);
+        collector.collect(Err(anyhow!("oops")));
+
+        assert_eq!(collector.dedupe_by_key(|(name, _)| *name), 1);
+        assert_eq!(collector.successes, vec![("melk", 1), ("kaas", 2)]);
+        assert_eq!(collector.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut collector = ResultCollector::from(vec![1, 2, 3]);
+        collector.collect(Err(anyhow!("oops")));
+        collector.collect(Err(ScrapeError::FailedToConnect { url: "a".to_owned(), err: "timeout".to_owned() }.into()));
+        collector.collect(Err(ScrapeError::FailedToConnect { url: "b".to_owned(), err: "timeout".to_owned() }.into()));
+
+        let summary = collector.summary();
+
+        assert_eq!(summary.successes, 3);
+        assert_eq!(summary.errors, 3);
+        assert_eq!(summary.error_kinds.get("FailedToConnect"), Some(&2));
+        assert_eq!(summary.error_kinds.get("Other"), Some(&1));
+        assert_eq!(summary.examples.get("Other"), Some(&vec!["oops".to_owned()]));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut collector = ResultCollector::from(vec![1]);
+        collector.collect(Err(anyhow!("root").context("oops")));
+
+        let json = serde_json::to_value(&collector).unwrap();
+
+        assert_eq!(json, serde_json::json!({
+            "successes": [1],
+            "errors": [{"kind": "Other", "message": "oops", "causes": ["root"]}],
+        }));
+    }
+}
@@ -1,20 +1,42 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
 use scraper::{selector::ToCss, ElementRef, Selector};
+use log::{debug, warn};
 use super::ScrapeError;
 use anyhow::Result;
 
+mod structured_data;
+
+pub use structured_data::{
+    json_ld,
+    json_ld_products,
+    script_json,
+    next_data,
+    script_assignment,
+    find_objects,
+    has_type,
+    product_from_json_ld,
+    structured_products,
+    merge_structured,
+    merge_page_products,
+};
+
+// Which selector of each chain matched, by (src, field) and position in the chain. `None` counts misses
+type UsageMap = BTreeMap<(String, String), BTreeMap<Option<usize>, usize>>;
+static CHAIN_USAGE: OnceLock<Mutex<UsageMap>> = OnceLock::new();
+
 pub fn build_selector<'a>(selector_string: &'a str, src: &'a str) -> Result<Selector> {
     // Build a scraper::Selector from a CSS selector (e.g. "div.classname")
-    Ok(
-        scraper::Selector::parse(selector_string)
-        .map_err(|e| ScrapeError::CSSSelectorFailed{ src: src.to_string(), err: e.to_string()})?
-    )
+    scraper::Selector::parse(selector_string)
+        .map_err(|e| ScrapeError::CSSSelectorFailed{ src: src.to_string(), err: e.to_string()}.into())
 }
 
 pub fn build_selectors(selector_strings: &[&str], src: &str) -> Result<Vec<Selector>> {
     // Build multiple scraper::Selector instances from a slice of CSS selectors
-    Ok(selector_strings.iter().map(|s| {
+    selector_strings.iter().map(|s| {
         build_selector(s, src)
-    }).collect::<Result<Vec<Selector>>>()?)
+    }).collect::<Result<Vec<Selector>>>()
 }
 
 pub fn walk_selectors<'a>(mut element: ElementRef<'a>, selectors: &[Selector], src: &'a str) -> Result<ElementRef<'a>> {
@@ -22,9 +44,242 @@ pub fn walk_selectors<'a>(mut element: ElementRef<'a>, selectors: &[Selector], s
     // then the next selector is applied
     for selector in selectors.iter() {
         element = element
-        .select(&selector)
+        .select(selector)
         .next()
         .ok_or(ScrapeError::InvalidStructureAssumed{ src: src.to_string(), info: selector.to_css_string() })?;
     }
     Ok(element)
-}
\ No newline at end of file
+}
+
+pub fn find_text(element: ElementRef, selectors: &[Selector]) -> Option<String> {
+    // Take the trimmed text of the first element matched by any of the selectors, for optional fields
+    selectors.iter()
+        .flat_map(|selector| element.select(selector))
+        .map(|found| found.text().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" "))
+        .find(|text| !text.is_empty())
+}
+
+pub fn find_attr(element: ElementRef, selectors: &[Selector], attr: &str) -> Option<String> {
+    // Take the value of `attr` of the first element matched by any of the selectors that has it
+    selectors.iter()
+        .flat_map(|selector| element.select(selector))
+        .find_map(|found| found.value().attr(attr))
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+pub fn normalize_url(url: &str) -> String {
+    // Strip the query, fragment and trailing slash so the same page always gets the same URL
+    match reqwest::Url::parse(url.trim()) {
+        Ok(mut parsed) => {
+            parsed.set_query(None);
+            parsed.set_fragment(None);
+            parsed.as_str().trim_end_matches('/').to_owned()
+        },
+        Err(_) => url.trim().trim_end_matches('/').to_lowercase(),
+    }
+}
+
+/// Selector for elements of type `tag` with a class starting with `prefix`. Meant for generated class
+/// names like "price-amount_root__Sa88q", where the hash after the prefix changes with every deploy
+///
+/// # Example
+/// ```
+/// use scrape_core::scrape_utils::class_prefix;
+///
+/// assert_eq!(class_prefix("div", "price-amount_root"), "div[class^='price-amount_root'], div[class*=' price-amount_root']");
+/// ```
+pub fn class_prefix(tag: &str, prefix: &str) -> String {
+    // The class can be anywhere in the class attribute, not only first
+    format!("{tag}[class^='{prefix}'], {tag}[class*=' {prefix}']")
+}
+
+/// Ordered selectors for a single field, the first selector that matches is used. When the primary
+/// selector stops matching, e.g. after a front-end deploy, a fallback takes over and this is logged
+///
+/// # Example
+/// ```
+/// use scrape_core::scrape_utils::{class_prefix, SelectorChain};
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let html = scraper::Html::parse_fragment("<div class='card'><div class='price-amount_root__Xy12z'>1.29</div></div>");
+/// let element = html.root_element();
+/// let price = SelectorChain::new("Albert Heijn", "price", &[
+///     "div.price-amount_root__Sa88q",
+///     &class_prefix("div", "price-amount_root"),
+///     "[data-testhook='price-amount']",
+/// ])?;
+/// let text = price.text(element);
+///
+/// assert_eq!(text.as_deref(), Some("1.29"));
+/// # Ok(())
+/// # }
+/// ```
+pub struct SelectorChain {
+    src: String,
+    field: String,
+    selectors: Vec<Selector>,
+}
+
+impl SelectorChain {
+    pub fn new(src: &str, field: &str, selector_strings: &[&str]) -> Result<Self> {
+        Ok(SelectorChain {
+            src: src.to_owned(),
+            field: field.to_owned(),
+            selectors: build_selectors(selector_strings, src)?,
+        })
+    }
+
+    /// All elements matched by the first selector of the chain that matches any
+    pub fn select_all<'a>(&self, element: ElementRef<'a>) -> Vec<ElementRef<'a>> {
+        let found = self.selectors
+            .iter()
+            .enumerate()
+            .map(|(position, selector)| (position, element.select(selector).collect::<Vec<ElementRef>>()))
+            .find(|(_, found)| !found.is_empty());
+
+        match found {
+            Some((position, found)) => {
+                self.record(Some(position));
+                found
+            },
+            None => {
+                self.record(None);
+                Vec::new()
+            },
+        }
+    }
+
+    pub fn select_first<'a>(&self, element: ElementRef<'a>) -> Option<ElementRef<'a>> {
+        self.select_all(element).into_iter().next()
+    }
+
+    /// Like `select_first`, but missing the element is an error
+    pub fn require<'a>(&self, element: ElementRef<'a>) -> Result<ElementRef<'a>> {
+        self.select_first(element).ok_or_else(|| {
+            let tried: Vec<String> = self.selectors.iter().map(|s| s.to_css_string()).collect();
+            ScrapeError::InvalidStructureAssumed {
+                src: self.src.clone(),
+                info: format!("{}, tried {}", self.field, tried.join(" | ")),
+            }.into()
+        })
+    }
+
+    /// Whitespace-normalized text of the first matched element
+    pub fn text(&self, element: ElementRef) -> Option<String> {
+        self.select_first(element)
+            .map(|found| found.text().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" "))
+            .filter(|text| !text.is_empty())
+    }
+
+    /// Value of `attr` of the first matched element
+    pub fn attr(&self, element: ElementRef, attr: &str) -> Option<String> {
+        self.select_first(element)
+            .and_then(|found| found.value().attr(attr))
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn record(&self, position: Option<usize>) {
+        let usage = CHAIN_USAGE.get_or_init(|| Mutex::new(BTreeMap::new()));
+        let Ok(mut usage) = usage.lock() else {
+            return;
+        };
+        let counts = usage.entry((self.src.clone(), self.field.clone())).or_default();
+        let count = counts.entry(position).or_insert(0);
+        *count += 1;
+
+        // Warn once per fallback, the usage report has the totals
+        if let (Some(position @ 1..), 1) = (position, *count) {
+            warn!(
+                target: self.src.as_str(),
+                "Field '{}' matched fallback selector #{} '{}', '{}' no longer matches",
+                self.field,
+                position,
+                self.selectors[position].to_css_string(),
+                self.selectors[0].to_css_string(),
+            );
+        }
+        if position.is_none() {
+            debug!(target: self.src.as_str(), "No selector matched for field '{}'", self.field);
+        }
+    }
+}
+
+/// How often a selector of a chain matched, `position` 0 is the primary selector and `None` means none matched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainUsage {
+    pub field: String,
+    pub position: Option<usize>,
+    pub matches: usize,
+}
+
+impl fmt::Display for ChainUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(0) => write!(f, "{}: primary selector {} times", self.field, self.matches),
+            Some(position) => write!(f, "{}: fallback #{} {} times", self.field, position, self.matches),
+            None => write!(f, "{}: no match {} times", self.field, self.matches),
+        }
+    }
+}
+
+/// Which selectors of the chains of `src` matched so far
+pub fn chain_usage(src: &str) -> Vec<ChainUsage> {
+    let Some(Ok(usage)) = CHAIN_USAGE.get().map(|usage| usage.lock()) else {
+        return Vec::new();
+    };
+    usage
+        .iter()
+        .filter(|((usage_src, _), _)| usage_src == src)
+        .flat_map(|((_, field), counts)| counts.iter().map(|(position, matches)| ChainUsage {
+            field: field.clone(),
+            position: *position,
+            matches: *matches,
+        }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+    use super::{chain_usage, class_prefix, normalize_url, SelectorChain};
+
+    const HTML: &str = r#"
+        <article>
+            <div class="price-amount_root__Xy12z price-amount_bonus">1.29</div>
+            <a href="/producten/product/wi1" title="AH Halfvolle melk">Melk</a>
+        </article>
+    "#;
+
+    #[test]
+    fn test_selector_chain() {
+        let document = Html::parse_fragment(HTML);
+        let price = SelectorChain::new("test", "price", &["div.price-amount_root__Sa88q", &class_prefix("div", "price-amount_root")]).unwrap();
+        let link = SelectorChain::new("test", "link", &["a"]).unwrap();
+        let brand = SelectorChain::new("test", "brand", &["[data-testhook='product-brand']"]).unwrap();
+
+        assert_eq!(price.text(document.root_element()).as_deref(), Some("1.29"));
+        assert_eq!(link.attr(document.root_element(), "title").as_deref(), Some("AH Halfvolle melk"));
+        assert_eq!(brand.text(document.root_element()), None);
+        assert!(brand.require(document.root_element()).is_err());
+
+        let usage: Vec<String> = chain_usage("test").iter().map(|u| u.to_string()).collect();
+        assert_eq!(usage, vec!["brand: no match 2 times", "link: primary selector 1 times", "price: fallback #1 1 times"]);
+    }
+
+    #[test]
+    fn test_class_prefix() {
+        let document = Html::parse_fragment(r#"<div class="shield price-amount_was__Ab12c">2.50</div>"#);
+        let chain = SelectorChain::new("prefix_test", "was", &[&class_prefix("div", "price-amount_was")]).unwrap();
+
+        assert_eq!(chain.text(document.root_element()).as_deref(), Some("2.50"));
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        assert_eq!(normalize_url(" https://www.ah.nl/producten/melk/?page=2#top "), "https://www.ah.nl/producten/melk");
+        assert_eq!(normalize_url("https://WWW.Jumbo.com/producten"), "https://www.jumbo.com/producten");
+        assert_eq!(normalize_url("/Producten/Melk/"), "/producten/melk");
+    }
+}
@@ -0,0 +1,376 @@
+use scraper::Html;
+use serde_json::Value;
+use log::warn;
+use anyhow::Result;
+use crate::{Availability, Money, ProductInfo};
+use super::build_selector;
+
+const JSON_LD_SELECTOR: &str = "script[type='application/ld+json']";
+const NEXT_DATA_ID: &str = "__NEXT_DATA__";
+const SCHEMA_PRODUCT: &str = "Product";
+const GTIN_KEYS: [&str; 5] = ["gtin13", "gtin", "gtin14", "gtin12", "gtin8"];
+
+/// All JSON-LD objects of a page. Arrays and `@graph` containers are flattened, blocks
+/// that aren't valid JSON are skipped
+pub fn json_ld(document: &Html) -> Vec<Value> {
+    let Ok(selector) = build_selector(JSON_LD_SELECTOR, "structured data") else {
+        return Vec::new();
+    };
+    document
+        .select(&selector)
+        .filter_map(|script| serde_json::from_str::<Value>(&script.text().collect::<String>()).ok())
+        .flat_map(flatten_graph)
+        .collect()
+}
+
+fn flatten_graph(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(values) => values.into_iter().flat_map(flatten_graph).collect(),
+        Value::Object(mut object) => match object.remove("@graph") {
+            Some(graph) => flatten_graph(graph),
+            None => vec![Value::Object(object)],
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// schema.org Products of a page, also the ones nested in e.g. an ItemList
+pub fn json_ld_products(document: &Html) -> Vec<Value> {
+    json_ld(document)
+        .iter()
+        .flat_map(|value| find_objects(value, &|object| has_type(object, SCHEMA_PRODUCT)))
+        .cloned()
+        .collect()
+}
+
+/// Parsed content of a `<script id="...">` holding JSON, such as Next.js' `__NEXT_DATA__`
+pub fn script_json(document: &Html, id: &str) -> Option<Value> {
+    let selector = build_selector(&format!("script[id='{}']", id), "structured data").ok()?;
+    let script = document.select(&selector).next()?;
+    serde_json::from_str(&script.text().collect::<String>()).ok()
+}
+
+/// The page state of a Next.js site
+pub fn next_data(document: &Html) -> Option<Value> {
+    script_json(document, NEXT_DATA_ID)
+}
+
+/// Page state assigned to a global in a script, e.g. `window.__INITIAL_STATE__ = {...};`
+pub fn script_assignment(document: &Html, variable: &str) -> Option<Value> {
+    let selector = build_selector("script", "structured data").ok()?;
+    document
+        .select(&selector)
+        .map(|script| script.text().collect::<String>())
+        .find_map(|text| {
+            let (_, rest) = text.split_once(variable)?;
+            let json = rest.trim_start().strip_prefix('=')?;
+            // Only the first value, the script usually continues after it
+            serde_json::Deserializer::from_str(json.trim_start())
+                .into_iter::<Value>()
+                .next()?
+                .ok()
+        })
+}
+
+/// All objects within `value`, at any depth, for which `predicate` holds. Objects within a
+/// matched object are searched as well
+pub fn find_objects<'a>(value: &'a Value, predicate: &dyn Fn(&Value) -> bool) -> Vec<&'a Value> {
+    let mut found = Vec::new();
+    if value.is_object() && predicate(value) {
+        found.push(value);
+    }
+    match value {
+        Value::Array(values) => found.extend(values.iter().flat_map(|v| find_objects(v, predicate))),
+        Value::Object(object) => found.extend(object.values().flat_map(|v| find_objects(v, predicate))),
+        _ => (),
+    };
+    found
+}
+
+/// Whether the `@type` of a JSON-LD object is, or includes, `schema_type`
+pub fn has_type(object: &Value, schema_type: &str) -> bool {
+    let matches = |t: &Value| t.as_str().is_some_and(|t| t.trim_start_matches("http://schema.org/").trim_start_matches("https://schema.org/") == schema_type);
+    match object.get("@type") {
+        Some(Value::Array(types)) => types.iter().any(matches),
+        Some(t) => matches(t),
+        None => false,
+    }
+}
+
+/// Map a schema.org Product onto `ProductInfo`. Name, price and URL are required, relative
+/// URLs are resolved against `base_url`
+pub fn product_from_json_ld(product: &Value, base_url: &str) -> Option<ProductInfo> {
+    let name = string(product.get("name")?)?;
+    let offer = product.get("offers").and_then(first_offer);
+    let price = offer.and_then(offer_price)?;
+    let url = offer
+        .and_then(|offer| offer.get("url"))
+        .or_else(|| product.get("url"))
+        .or_else(|| product.get("@id"))
+        .and_then(string)
+        .and_then(|url| absolute_url(base_url, &url))?;
+
+    let mut info = ProductInfo::new(name, price, url);
+    info.brand = product.get("brand").and_then(|brand| brand.get("name").unwrap_or(brand).as_str()).and_then(non_empty);
+    info.image_url = product.get("image").and_then(image_url).and_then(|url| absolute_url(base_url, &url));
+    info.sku = product.get("sku").and_then(string);
+    info.ean = GTIN_KEYS.iter().find_map(|key| product.get(*key).and_then(string));
+    info.category = product
+        .get("category")
+        .and_then(string)
+        .map(|path| path.split(['/', '>']).map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect())
+        .unwrap_or_default();
+    info.quantity = product.get("size").or_else(|| product.get("weight")).and_then(quantity);
+    info.availability = offer
+        .and_then(|offer| offer.get("availability"))
+        .and_then(Value::as_str)
+        .map(availability)
+        .unwrap_or_default();
+    Some(info)
+}
+
+/// Products of a page mapped from JSON-LD, empty when the page has none or none can be mapped
+pub fn structured_products(document: &Html, base_url: &str) -> Vec<ProductInfo> {
+    json_ld_products(document)
+        .iter()
+        .filter_map(|product| product_from_json_ld(product, base_url))
+        .collect()
+}
+
+/// The products parsed from the tiles of the page at `url` merged with its JSON-LD products, see
+/// `merge_structured`. JSON-LD rarely has the store's product key and SKU, `key` and `sku` derive
+/// them from the product URL. When the tiles can't be parsed the JSON-LD products are used alone
+pub fn merge_page_products(
+    document: &Html,
+    parsed: Result<Vec<ProductInfo>>,
+    base_url: &str,
+    key: impl Fn(&str) -> String,
+    sku: impl Fn(&str) -> Option<String>,
+    src: &str,
+    url: &str,
+) -> Result<Vec<ProductInfo>> {
+    let structured: Vec<ProductInfo> = structured_products(document, base_url)
+        .into_iter()
+        .map(|mut product| {
+            product.sku = product.sku.or_else(|| sku(&product.url));
+            let key = key(&product.url);
+            product.with_key(key)
+        })
+        .collect();
+
+    match (parsed, structured.is_empty()) {
+        (parsed, true) => parsed,
+        (Ok(parsed), false) => Ok(merge_structured(structured, parsed)),
+        (Err(e), false) => {
+            warn!(target: src, "Failed to parse the products of {}, using JSON-LD only: {}", url, e);
+            Ok(structured)
+        },
+    }
+}
+
+/// Merge the products of a page found in JSON-LD with the ones parsed from its tiles, matched on
+/// their key. A field is taken from JSON-LD when it has it and from the tile otherwise. Only the
+/// tile shows promotions, for a product on offer the JSON-LD price is the offer price so the tile's
+/// regular price is kept. Products found by only one of the two are kept as they are, tiles first
+pub fn merge_structured(structured: Vec<ProductInfo>, parsed: Vec<ProductInfo>) -> Vec<ProductInfo> {
+    let mut structured: Vec<Option<ProductInfo>> = structured.into_iter().map(Some).collect();
+    let mut merged: Vec<ProductInfo> = parsed
+        .into_iter()
+        .map(|tile| {
+            let found = structured.iter_mut().find(|s| s.as_ref().is_some_and(|s| s.key == tile.key));
+            match found.and_then(Option::take) {
+                Some(product) => merge_product(product, tile),
+                None => tile,
+            }
+        })
+        .collect();
+    merged.extend(structured.into_iter().flatten());
+    merged
+}
+
+fn merge_product(mut product: ProductInfo, tile: ProductInfo) -> ProductInfo {
+    if tile.offer.is_some() {
+        product.price = tile.price;
+    }
+    product.quantity = product.quantity.or(tile.quantity);
+    product.unit_price = product.unit_price.or(tile.unit_price);
+    product.brand = product.brand.or(tile.brand);
+    product.image_url = product.image_url.or(tile.image_url);
+    product.sku = product.sku.or(tile.sku);
+    product.ean = product.ean.or(tile.ean);
+    if product.category.is_empty() {
+        product.category = tile.category;
+    }
+    if product.availability == Availability::Unknown {
+        product.availability = tile.availability;
+    }
+    product.offer = product.offer.or(tile.offer);
+    product.details = product.details.or(tile.details);
+    product
+}
+
+// The first Offer, or the lowest price of an AggregateOffer
+fn first_offer(offers: &Value) -> Option<&Value> {
+    match offers {
+        Value::Array(offers) => offers.first(),
+        Value::Object(_) => match offers.get("offers") {
+            Some(nested) if offer_price(offers).is_none() => first_offer(nested),
+            _ => Some(offers),
+        },
+        _ => None,
+    }
+}
+
+fn offer_price(offer: &Value) -> Option<Money> {
+    let currency = offer.get("priceCurrency").and_then(Value::as_str).unwrap_or("EUR");
+    if !currency.eq_ignore_ascii_case("EUR") {
+        return None;
+    }
+    let price = offer.get("price").or_else(|| offer.get("lowPrice"))?;
+    // JSON-LD prices always use a point as decimal separator, so "1.299" is not a thousand euros
+    let amount = match price {
+        Value::Number(number) => number.as_f64()?,
+        Value::String(text) => text.trim().parse::<f64>().ok()?,
+        _ => return None,
+    };
+    Money::parse(&format!("{:.2}", amount)).ok()
+}
+
+fn availability(value: &str) -> Availability {
+    match value.rsplit('/').next().unwrap_or(value) {
+        "InStock" | "LimitedAvailability" | "InStoreOnly" | "OnlineOnly" => Availability::Available,
+        "OutOfStock" | "SoldOut" | "Discontinued" => Availability::Unavailable,
+        _ => Availability::Unknown,
+    }
+}
+
+fn image_url(image: &Value) -> Option<String> {
+    match image {
+        Value::Array(images) => images.first().and_then(image_url),
+        Value::Object(object) => object.get("url").or_else(|| object.get("contentUrl")).and_then(string),
+        _ => string(image),
+    }
+}
+
+// A QuantitativeValue like { "value": 500, "unitText": "g" } or plain text
+fn quantity(value: &Value) -> Option<String> {
+    match value.get("value") {
+        Some(amount) => {
+            let unit = value.get("unitText").or_else(|| value.get("unitCode")).and_then(string).unwrap_or_default();
+            non_empty(format!("{} {}", string(amount)?, unit).trim())
+        },
+        None => string(value),
+    }
+}
+
+fn absolute_url(base_url: &str, url: &str) -> Option<String> {
+    reqwest::Url::parse(base_url).ok()?.join(url.trim()).ok().map(String::from)
+}
+
+// Strings and numbers as trimmed text, e.g. a sku can be either
+fn string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => non_empty(text),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    match text.is_empty() {
+        true => None,
+        false => Some(text.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+    use crate::{Availability, Money, Offer, ProductInfo};
+    use super::{merge_structured, next_data, script_assignment, structured_products};
+
+    const PAGE: &str = r#"
+        <html><head>
+        <script type="application/ld+json">
+        {
+            "@context": "https://schema.org",
+            "@type": "ItemList",
+            "itemListElement": [{
+                "@type": "ListItem",
+                "position": 1,
+                "item": {
+                    "@type": "Product",
+                    "name": "Jumbo Halfvolle Melk 1L",
+                    "sku": "123456PAK",
+                    "gtin13": "8718452000011",
+                    "brand": { "@type": "Brand", "name": "Jumbo" },
+                    "image": ["/images/melk.png"],
+                    "category": "Zuivel > Melk",
+                    "offers": {
+                        "@type": "Offer",
+                        "price": "1.19",
+                        "priceCurrency": "EUR",
+                        "availability": "https://schema.org/InStock",
+                        "url": "/producten/halfvolle-melk-123456PAK"
+                    }
+                }
+            }]
+        }
+        </script>
+        <script type="application/ld+json">{ "@graph": [{ "@type": ["Product"], "name": "Kaas", "url": "https://www.jumbo.com/kaas",
+            "offers": { "@type": "AggregateOffer", "lowPrice": 4.5, "availability": "OutOfStock" } }] }</script>
+        <script type="application/ld+json">not json</script>
+        <script id="__NEXT_DATA__" type="application/json">{ "props": { "pageProps": { "total": 2 } } }</script>
+        <script>window.__INITIAL_STATE__ = { "page": 3 }; window.other = 1;</script>
+        </head><body></body></html>
+    "#;
+
+    #[test]
+    fn test_structured_products() {
+        let document = Html::parse_document(PAGE);
+        let products = structured_products(&document, "https://www.jumbo.com");
+
+        assert_eq!(products.len(), 2);
+        let melk = &products[0];
+        assert_eq!(melk.name, "Jumbo Halfvolle Melk 1L");
+        assert_eq!(melk.price, Money::from_cents(119));
+        assert_eq!(melk.url, "https://www.jumbo.com/producten/halfvolle-melk-123456PAK");
+        assert_eq!(melk.brand.as_deref(), Some("Jumbo"));
+        assert_eq!(melk.image_url.as_deref(), Some("https://www.jumbo.com/images/melk.png"));
+        assert_eq!(melk.sku.as_deref(), Some("123456PAK"));
+        assert_eq!(melk.ean.as_deref(), Some("8718452000011"));
+        assert_eq!(melk.category, vec!["Zuivel", "Melk"]);
+        assert_eq!(melk.availability, Availability::Available);
+
+        assert_eq!(products[1].price, Money::from_cents(450));
+        assert_eq!(products[1].availability, Availability::Unavailable);
+    }
+
+    #[test]
+    fn test_page_state() {
+        let document = Html::parse_document(PAGE);
+
+        assert_eq!(next_data(&document).unwrap()["props"]["pageProps"]["total"], 2);
+        assert_eq!(script_assignment(&document, "window.__INITIAL_STATE__").unwrap()["page"], 3);
+        assert_eq!(script_assignment(&document, "window.__MISSING__"), None);
+    }
+
+    #[test]
+    fn test_merge_structured() {
+        let document = Html::parse_document(PAGE);
+        let structured = structured_products(&document, "https://www.jumbo.com");
+        let mut melk = ProductInfo::new("Halfvolle melk".to_owned(), Money::from_cents(139), "https://www.jumbo.com/producten/halfvolle-melk-123456PAK".to_owned());
+        melk.offer = Offer::parse("2e halve prijs", None);
+        melk.category = vec!["Melk".to_owned()];
+        let brood = ProductInfo::new("Brood".to_owned(), Money::from_cents(229), "https://www.jumbo.com/brood".to_owned());
+
+        let merged = merge_structured(structured, vec![melk, brood]);
+
+        assert_eq!(merged.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>(), vec!["Jumbo Halfvolle Melk 1L", "Brood", "Kaas"]);
+        assert_eq!(merged[0].price, Money::from_cents(139));
+        assert!(merged[0].offer.is_some());
+        assert_eq!(merged[0].category, vec!["Zuivel", "Melk"]);
+        assert_eq!(merged[0].ean.as_deref(), Some("8718452000011"));
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+use std::io::Read;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use log::info;
+use regex::Regex;
+use scraper::Html;
+use super::{AsyncExecutor, ConfigError, HtmlLoader, ResultCollector, ScrapeError};
+use super::scrape_utils::build_selector;
+
+const SRC: &str = "sitemap";
+const DEFAULT_MAX_DEPTH: usize = 3;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A URL listed in a sitemap or sitemap index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitemapEntry {
+    pub url: String,
+    /// When the page or sub-sitemap last changed, if the sitemap tells
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+/// A sitemap either lists pages or, as an index, other sitemaps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SitemapContent {
+    Index(Vec<SitemapEntry>),
+    Urls(Vec<SitemapEntry>),
+}
+
+/// Finds the page URLs in a store's sitemaps. Sitemap indexes are followed to their sub-sitemaps,
+/// level by level through the executor, and gzipped sitemaps are unpacked. With `modified_since`
+/// pages and sub-sitemaps that didn't change since are left out, entries without a `lastmod` are kept.
+///
+/// # Example
+/// ```no_run
+/// # use scrape_core::{ReqwestHtmlLoader, SimpleRateLimiter, Sitemap};
+/// # async fn example() -> anyhow::Result<()> {
+/// # let client = reqwest::Client::new();
+/// # let connector = ReqwestHtmlLoader::new(&client);
+/// # let rate_limiter = SimpleRateLimiter::default();
+/// let product_urls = Sitemap::new(&connector, &["https://www.example.com/sitemap.xml"])
+///     .with_include(&["/producten/[^/]+$"])?
+///     .urls(&rate_limiter)
+///     .await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Sitemap<'a, T: HtmlLoader + Send + Sync> {
+    loader: &'a T,
+    urls: Vec<String>,
+    modified_since: Option<DateTime<Utc>>,
+    include: Vec<Regex>,
+    max_depth: usize,
+}
+
+impl<'a, T: HtmlLoader + Send + Sync> Sitemap<'a, T> {
+    pub fn new(loader: &'a T, urls: &[&str]) -> Self {
+        Sitemap {
+            loader,
+            urls: urls.iter().map(|url| url.to_string()).collect(),
+            modified_since: None,
+            include: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    pub fn with_modified_since(mut self, modified_since: Option<DateTime<Utc>>) -> Self {
+        self.modified_since = modified_since;
+        self
+    }
+
+    /// Regexes of which at least one must match a page URL, e.g. to keep only product pages
+    pub fn with_include(mut self, patterns: &[&str]) -> Result<Self, ConfigError> {
+        self.include = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|e| ConfigError::InvalidValue { field: "sitemap.include".to_owned(), reason: e.to_string() }))
+            .collect::<Result<Vec<Regex>, ConfigError>>()?;
+        Ok(self)
+    }
+
+    /// Levels of sitemap indexes that are followed
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Page URLs of all sitemaps, without duplicates
+    pub async fn urls<R: AsyncExecutor + Send + Sync>(&self, executor: &R) -> ResultCollector<String> {
+        let entries = self.entries(executor).await;
+        ResultCollector { successes: entries.successes.into_iter().map(|entry| entry.url).collect(), errors: entries.errors }
+    }
+
+    /// Pages of all sitemaps, a sitemap that fails to load or parse ends up as an error
+    pub async fn entries<R: AsyncExecutor + Send + Sync>(&self, executor: &R) -> ResultCollector<SitemapEntry> {
+        let mut pages = ResultCollector::new();
+        let mut seen_pages: HashSet<String> = HashSet::new();
+        let mut visited: HashSet<String> = self.urls.iter().cloned().collect();
+        let mut level = self.urls.clone();
+
+        for depth in 0..=self.max_depth {
+            let futures: Vec<_> = level.iter().map(|url| self.load(url)).collect();
+            let results = executor.run(futures).await;
+            let mut next_level = Vec::new();
+
+            for result in results {
+                match result.and_then(|r| r) {
+                    Ok(SitemapContent::Index(sitemaps)) if depth < self.max_depth => next_level.extend(
+                        sitemaps
+                            .into_iter()
+                            .filter(|sitemap| self.is_modified(sitemap) && visited.insert(sitemap.url.clone()))
+                            .map(|sitemap| sitemap.url)
+                    ),
+                    Ok(SitemapContent::Index(_)) => info!("Sitemap index below depth {} isn't followed", self.max_depth),
+                    Ok(SitemapContent::Urls(entries)) => pages.successes.extend(
+                        entries
+                            .into_iter()
+                            .filter(|entry| self.is_modified(entry) && self.is_included(&entry.url) && seen_pages.insert(entry.url.clone()))
+                    ),
+                    Err(e) => pages.errors.push(e),
+                };
+            }
+            info!("Loaded {} sitemaps at depth {}, {} pages so far", level.len(), depth, pages.successes.len());
+            if next_level.is_empty() {
+                break;
+            }
+            level = next_level;
+        }
+        pages
+    }
+
+    async fn load(&self, url: &str) -> Result<SitemapContent> {
+        let bytes = self.loader.load_bytes(url.to_owned()).await?;
+        parse_sitemap(&bytes, url)
+    }
+
+    fn is_modified(&self, entry: &SitemapEntry) -> bool {
+        match (self.modified_since, entry.lastmod) {
+            (Some(since), Some(lastmod)) => lastmod >= since,
+            _ => true,
+        }
+    }
+
+    fn is_included(&self, url: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|pattern| pattern.is_match(url))
+    }
+}
+
+/// Parse a sitemap or sitemap index, gzipped or not. `url` is only used in errors
+pub fn parse_sitemap(bytes: &[u8], url: &str) -> Result<SitemapContent> {
+    let invalid = |info: String| ScrapeError::InvalidStructureAssumed { src: SRC.to_owned(), info: format!("{}: {}", url, info) };
+    let mut xml = String::new();
+    if bytes.starts_with(&GZIP_MAGIC) {
+        GzDecoder::new(bytes).read_to_string(&mut xml).map_err(|e| invalid(e.to_string()))?;
+    } else {
+        xml = String::from_utf8_lossy(bytes).into_owned();
+    }
+
+    // The HTML parser keeps unknown elements, which is all a sitemap has
+    let document = Html::parse_document(&xml);
+    let index = entries(&document, "sitemap")?;
+    if !index.is_empty() {
+        return Ok(SitemapContent::Index(index));
+    }
+    let pages = entries(&document, "url")?;
+    let is_urlset = document.select(&build_selector("urlset", SRC)?).next().is_some();
+    match pages.is_empty() && !is_urlset {
+        true => Err(invalid("not a sitemap".to_owned()).into()),
+        false => Ok(SitemapContent::Urls(pages)),
+    }
+}
+
+// The loc and lastmod of each <sitemap> or <url>, other elements like <image:loc> are ignored
+fn entries(document: &Html, tag: &str) -> Result<Vec<SitemapEntry>> {
+    let entry_selector = build_selector(tag, SRC)?;
+    let loc_selector = build_selector(&format!("{} > loc", tag), SRC)?;
+    let lastmod_selector = build_selector(&format!("{} > lastmod", tag), SRC)?;
+    let text = |element: scraper::ElementRef| element.text().collect::<String>().trim().to_owned();
+
+    Ok(document
+        .select(&entry_selector)
+        .filter_map(|entry| {
+            let url = entry.select(&loc_selector).next().map(text).filter(|url| !url.is_empty())?;
+            let lastmod = entry.select(&lastmod_selector).next().and_then(|lastmod| parse_lastmod(&text(lastmod)));
+            Some(SitemapEntry { url, lastmod })
+        })
+        .collect())
+}
+
+/// A W3C datetime as used by sitemaps, e.g. "2024-03-01" or "2024-03-01T10:15:00+01:00".
+/// A date without a time is the end of that day, a page changed on the day of a run is then still
+/// modified since that run
+pub fn parse_lastmod(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    DateTime::parse_from_rfc3339(text)
+        .or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%dT%H:%M%:z"))
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_hms_opt(23, 59, 59).map(|datetime| datetime.and_utc()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use chrono::{TimeZone, Utc};
+    use flate2::{write::GzEncoder, Compression};
+    use crate::SimpleRateLimiter;
+    use crate::test_utils::FixtureLoader;
+    use super::{parse_lastmod, Sitemap};
+
+    fn gzip(content: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parse_lastmod() {
+        assert_eq!(parse_lastmod("2024-03-01"), Some(Utc.with_ymd_and_hms(2024, 3, 1, 23, 59, 59).unwrap()));
+        assert_eq!(parse_lastmod("2024-03-01T10:15:00+01:00"), Some(Utc.with_ymd_and_hms(2024, 3, 1, 9, 15, 0).unwrap()));
+        assert_eq!(parse_lastmod("2024-03-01T10:15+01:00"), Some(Utc.with_ymd_and_hms(2024, 3, 1, 9, 15, 0).unwrap()));
+        assert_eq!(parse_lastmod("gisteren"), None);
+    }
+
+    #[tokio::test]
+    async fn test_urls() {
+        let loader = FixtureLoader::new([
+            ("https://www.example.com/sitemap.xml", r#"<?xml version="1.0" encoding="UTF-8"?>
+                <sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                    <sitemap><loc>https://www.example.com/products-1.xml.gz</loc><lastmod>2024-03-02</lastmod></sitemap>
+                    <sitemap><loc>https://www.example.com/products-2.xml</loc><lastmod>2024-01-01</lastmod></sitemap>
+                    <sitemap><loc>https://www.example.com/recipes.xml</loc></sitemap>
+                </sitemapindex>"#.as_bytes().to_vec()),
+            ("https://www.example.com/products-1.xml.gz", gzip(r#"<urlset xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">
+                <url><loc>https://www.example.com/producten/melk-1</loc><lastmod>2024-03-02T08:00:00Z</lastmod>
+                    <image:image><image:loc>https://www.example.com/melk.jpg</image:loc></image:image></url>
+                <url><loc>https://www.example.com/producten/kaas-2</loc><lastmod>2024-02-01</lastmod></url>
+                <url><loc>https://www.example.com/producten/brood-3?a=1&amp;b=2</loc></url>
+                <url><loc>https://www.example.com/producten/melk-1</loc></url>
+            </urlset>"#)),
+            ("https://www.example.com/recipes.xml", b"<urlset><url><loc>https://www.example.com/recepten/pannenkoeken</loc></url></urlset>".to_vec()),
+        ]);
+
+        let urls = Sitemap::new(&loader, &["https://www.example.com/sitemap.xml"])
+            .with_modified_since(Some(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()))
+            .with_include(&["/producten/"])
+            .unwrap()
+            .urls(&SimpleRateLimiter::default())
+            .await;
+
+        assert!(urls.errors.is_empty());
+        assert_eq!(urls.successes, vec!["https://www.example.com/producten/melk-1", "https://www.example.com/producten/brood-3?a=1&b=2"]);
+    }
+
+    #[tokio::test]
+    async fn test_urls_modified_same_day() {
+        let loader = FixtureLoader::new([
+            ("https://www.example.com/sitemap.xml", r#"<urlset>
+                <url><loc>https://www.example.com/producten/melk-1</loc><lastmod>2024-03-01</lastmod></url>
+                <url><loc>https://www.example.com/producten/kaas-2</loc><lastmod>2024-02-29</lastmod></url>
+                <url><loc>https://www.example.com/producten/brood-3</loc><lastmod>2024-03-01T09:00:00Z</lastmod></url>
+            </urlset>"#.as_bytes().to_vec()),
+        ]);
+
+        // A run that started during the day of a date-only lastmod still visits the page
+        let urls = Sitemap::new(&loader, &["https://www.example.com/sitemap.xml"])
+            .with_modified_since(Some(Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap()))
+            .urls(&SimpleRateLimiter::default())
+            .await;
+
+        assert!(urls.errors.is_empty());
+        assert_eq!(urls.successes, vec!["https://www.example.com/producten/melk-1"]);
+    }
+}
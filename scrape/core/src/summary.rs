@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+use super::{ScrapeError, DbError};
+
+const EXAMPLES_PER_KIND: usize = 3;
+const OTHER_KIND: &str = "Other";
+
+/// Determine the kind of an `anyhow::Error` by downcasting it
+/// to one of the known error types. Unknown errors are of kind "Other"
+pub fn error_kind(error: &anyhow::Error) -> &'static str {
+    if let Some(e) = error.downcast_ref::<ScrapeError>() {
+        return e.kind();
+    }
+    if let Some(e) = error.downcast_ref::<DbError>() {
+        return e.kind();
+    }
+    OTHER_KIND
+}
+
+/// Structured representation of an `anyhow::Error`
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ErrorRecord {
+    pub kind: String,
+    pub message: String,
+    pub causes: Vec<String>,
+}
+
+impl From<&anyhow::Error> for ErrorRecord {
+    fn from(error: &anyhow::Error) -> Self {
+        ErrorRecord {
+            kind: error_kind(error).to_owned(),
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+/// Compact overview of the contents of a `ResultCollector`.
+/// Errors are counted per kind, and a few example messages are kept for each kind
+#[derive(Debug, Serialize, PartialEq, Eq, Default)]
+pub struct CollectorSummary {
+    pub successes: usize,
+    pub errors: usize,
+    pub error_kinds: BTreeMap<String, usize>,
+    pub examples: BTreeMap<String, Vec<String>>,
+}
+
+impl CollectorSummary {
+    pub fn new<'a>(successes: usize, errors: impl Iterator<Item = &'a anyhow::Error>) -> Self {
+        let mut summary = CollectorSummary { successes, ..Default::default() };
+
+        for error in errors {
+            let kind = error_kind(error).to_owned();
+            summary.errors += 1;
+            *summary.error_kinds.entry(kind.clone()).or_insert(0) += 1;
+
+            let examples = summary.examples.entry(kind).or_default();
+            if examples.len() < EXAMPLES_PER_KIND {
+                examples.push(error.to_string());
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for CollectorSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} successes, {} errors", self.successes, self.errors)?;
+        if self.error_kinds.is_empty() {
+            return Ok(());
+        }
+
+        let kinds: Vec<String> = self.error_kinds
+            .iter()
+            .map(|(kind, count)| format!("{}: {}", kind, count))
+            .collect();
+        write!(f, " ({})", kinds.join(", "))?;
+
+        for (kind, examples) in self.examples.iter() {
+            for example in examples.iter() {
+                write!(f, "\n  [{}] {}", kind, example)?;
+            }
+        }
+        Ok(())
+    }
+}
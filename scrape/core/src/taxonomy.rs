@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::{fmt, fs};
+use serde::{Deserialize, Serialize};
+use super::{ConfigError, InDbProduct};
+use super::text::normalize_text;
+
+const BUILTIN_PATH: &str = "data/taxonomy.toml";
+const BUILTIN_TAXONOMY: &str = include_str!("../data/taxonomy.toml");
+const SEPARATOR: &str = " > ";
+// Number of unmapped categories listed when the report is displayed
+const REPORTED_CATEGORIES: usize = 20;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaxonomyFile {
+    mapping: Vec<MappingEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MappingEntry {
+    taxonomy: String,
+    #[serde(default)]
+    keywords: Vec<String>,
+    /// Store category paths by store, e.g. `jumbo = ["Zuivel, eieren, boter > Melk"]`
+    #[serde(flatten)]
+    stores: BTreeMap<String, Vec<String>>,
+}
+
+/// Maps the category paths of the stores onto one shared taxonomy, see `data/taxonomy.toml`
+#[derive(Debug)]
+pub struct Taxonomy {
+    // Normalized store category path segments and the taxonomy path, per store
+    store_paths: BTreeMap<String, Vec<(Vec<String>, String)>>,
+    // Normalized keywords and the taxonomy path, in order of the file
+    keywords: Vec<(String, String)>,
+}
+
+impl Taxonomy {
+    /// The taxonomy that is compiled into the binary
+    pub fn builtin() -> Self {
+        Taxonomy::from_toml(BUILTIN_TAXONOMY)
+            .map_err(|err| ConfigError::FailedToParse { path: BUILTIN_PATH.to_owned(), err })
+            .expect("The builtin taxonomy is invalid")
+    }
+
+    /// Load a taxonomy from the TOML file at `path`, in the format of `data/taxonomy.toml`
+    pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError::FailedToRead { path: path.to_owned(), err: e.to_string() })?;
+        Taxonomy::from_toml(&content)
+            .map_err(|err| ConfigError::FailedToParse { path: path.to_owned(), err })
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        let file: TaxonomyFile = toml::from_str(content).map_err(|e| e.to_string())?;
+
+        let mut store_paths: BTreeMap<String, Vec<(Vec<String>, String)>> = BTreeMap::new();
+        let mut keywords: Vec<(String, String)> = Vec::new();
+        for entry in file.mapping {
+            for (store, paths) in entry.stores {
+                let known = store_paths.entry(store_id(&store)).or_default();
+                known.extend(paths.iter().map(|p| (segments(p), entry.taxonomy.clone())));
+            }
+            keywords.extend(entry.keywords.iter().map(|k| (normalize_text(k), entry.taxonomy.clone())));
+        }
+        // The most specific store path has to be tried first
+        for paths in store_paths.values_mut() {
+            paths.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+        }
+        Ok(Taxonomy { store_paths, keywords })
+    }
+
+    /// Taxonomy path for a category path of `store`, `None` when it isn't mapped
+    pub fn map_category(&self, store: &str, category: &[String]) -> Option<&str> {
+        let category: Vec<String> = category.iter().map(|c| normalize_text(c)).collect();
+        self.store_paths
+            .get(&store_id(store))?
+            .iter()
+            .find(|(path, _)| !path.is_empty() && category.starts_with(path))
+            .map(|(_, taxonomy)| taxonomy.as_str())
+    }
+
+    /// Taxonomy path based on the words in a product name
+    pub fn map_name(&self, name: &str) -> Option<&str> {
+        let name = format!(" {} ", normalize_text(name));
+        self.keywords
+            .iter()
+            .find(|(keyword, _)| name.contains(&format!(" {} ", keyword)))
+            .map(|(_, taxonomy)| taxonomy.as_str())
+    }
+
+    /// Assign a taxonomy path to each product, by store category or else by name.
+    /// Returns which store categories couldn't be mapped
+    pub fn assign(&self, products: &mut [InDbProduct]) -> TaxonomyReport {
+        let mut report = TaxonomyReport::default();
+
+        for product in products.iter_mut() {
+            let by_category = self.map_category(&product.store, &product.info.category);
+            if by_category.is_none() && !product.info.category.is_empty() {
+                let unmapped = format!("{}: {}", product.store, product.info.category.join(SEPARATOR));
+                *report.unmapped.entry(unmapped).or_insert(0) += 1;
+            }
+
+            product.taxonomy = by_category
+                .or_else(|| self.map_name(&product.info.name))
+                .map(str::to_owned);
+            match product.taxonomy {
+                Some(_) => report.mapped += 1,
+                None => report.unassigned += 1,
+            };
+        }
+        report
+    }
+}
+
+/// Outcome of assigning the taxonomy to the products of a run
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct TaxonomyReport {
+    pub mapped: usize,
+    /// Products that got no taxonomy path at all
+    pub unassigned: usize,
+    /// Store categories without a mapping, as "store: path", with the number of products in them
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+impl fmt::Display for TaxonomyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} products mapped, {} unassigned, {} unmapped store categories",
+            self.mapped,
+            self.unassigned,
+            self.unmapped.len(),
+        )?;
+
+        let mut unmapped: Vec<(&String, &usize)> = self.unmapped.iter().collect();
+        unmapped.sort_by(|a, b| b.1.cmp(a.1));
+        for (category, count) in unmapped.iter().take(REPORTED_CATEGORIES) {
+            write!(f, "\n  {} ({} products)", category, count)?;
+        }
+        if unmapped.len() > REPORTED_CATEGORIES {
+            write!(f, "\n  ... and {} more", unmapped.len() - REPORTED_CATEGORIES)?;
+        }
+        Ok(())
+    }
+}
+
+// Stores are referred to like in the config, e.g. "Albert Heijn" becomes "albert_heijn"
+fn store_id(store: &str) -> String {
+    store.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+fn segments(path: &str) -> Vec<String> {
+    path.split('>').map(normalize_text).filter(|s| !s.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InDbProduct, Money, ProductInfo};
+    use super::Taxonomy;
+
+    const TAXONOMY: &str = r#"
+        [[mapping]]
+        taxonomy = "Zuivel > Melk"
+        jumbo = ["Zuivel, eieren, boter > Melk"]
+        keywords = ["halfvolle melk"]
+
+        [[mapping]]
+        taxonomy = "Zuivel"
+        jumbo = ["Zuivel, eieren, boter"]
+        albert_heijn = ["Zuivel, plantaardig en eieren"]
+    "#;
+
+    fn product(store: &str, name: &str, category: &[&str]) -> InDbProduct {
+        let mut info = ProductInfo::new(name.to_owned(), Money::from_cents(100), "url".to_owned());
+        info.category = category.iter().map(|c| c.to_string()).collect();
+        InDbProduct::new(store.to_owned(), info)
+    }
+
+    fn path(category: &[&str]) -> Vec<String> {
+        category.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_map_category() {
+        let taxonomy = Taxonomy::from_toml(TAXONOMY).unwrap();
+
+        assert_eq!(taxonomy.map_category("Jumbo", &path(&["Zuivel, eieren, boter", "Melk", "Halfvolle melk"])), Some("Zuivel > Melk"));
+        assert_eq!(taxonomy.map_category("Jumbo", &path(&["Zuivel, eieren, boter", "Yoghurt"])), Some("Zuivel"));
+        assert_eq!(taxonomy.map_category("Albert Heijn", &path(&["zuivel, plantaardig en eieren"])), Some("Zuivel"));
+        assert_eq!(taxonomy.map_category("Albert Heijn", &path(&["Zuivel, eieren, boter"])), None);
+        assert_eq!(taxonomy.map_category("Jumbo", &[]), None);
+    }
+
+    #[test]
+    fn test_assign() {
+        let taxonomy = Taxonomy::from_toml(TAXONOMY).unwrap();
+        let mut products = vec![
+            product("Jumbo", "Jumbo Halfvolle Melk 1L", &["Zuivel, eieren, boter", "Melk"]),
+            product("Albert Heijn", "AH Halfvolle melk", &[]),
+            product("Albert Heijn", "AH Pindakaas", &["Ontbijtgranen en beleg", "Beleg"]),
+        ];
+
+        let report = taxonomy.assign(&mut products);
+
+        assert_eq!(products[0].taxonomy.as_deref(), Some("Zuivel > Melk"));
+        assert_eq!(products[1].taxonomy.as_deref(), Some("Zuivel > Melk"));
+        assert_eq!(products[2].taxonomy, None);
+        assert_eq!((report.mapped, report.unassigned), (2, 1));
+        assert_eq!(report.unmapped.get("Albert Heijn: Ontbijtgranen en beleg > Beleg"), Some(&1));
+    }
+
+    #[test]
+    fn test_builtin() {
+        let taxonomy = Taxonomy::builtin();
+        assert_eq!(taxonomy.map_name("Coca-Cola Zero 1,5L"), Some("Dranken > Frisdrank"));
+    }
+}
@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use scraper::Html;
+use super::HtmlLoader;
+
+/// Serves fixture pages by URL instead of requesting them, loading a URL without a fixture fails
+pub struct FixtureLoader {
+    pages: HashMap<String, Vec<u8>>,
+}
+
+impl FixtureLoader {
+    pub fn new<U: Into<String>, P: Into<Vec<u8>>>(pages: impl IntoIterator<Item = (U, P)>) -> Self {
+        FixtureLoader { pages: pages.into_iter().map(|(url, page)| (url.into(), page.into())).collect() }
+    }
+
+    pub fn insert(&mut self, url: &str, page: impl Into<Vec<u8>>) {
+        self.pages.insert(url.to_owned(), page.into());
+    }
+}
+
+impl HtmlLoader for FixtureLoader {
+    async fn load(&self, url: String) -> Result<Html> {
+        let page = self.pages.get(&url).ok_or_else(|| anyhow!("no fixture for {}", url))?;
+        Ok(Html::parse_document(&String::from_utf8_lossy(page)))
+    }
+
+    async fn load_bytes(&self, url: String) -> Result<Vec<u8>> {
+        self.pages.get(&url).cloned().ok_or_else(|| anyhow!("no fixture for {}", url))
+    }
+}
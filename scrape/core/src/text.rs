@@ -0,0 +1,171 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// Abbreviations used in product names and what they stand for
+const ABBREVIATIONS: [(&str, &str); 15] = [
+    ("hal.", "halfvolle"),
+    ("halfv.", "halfvolle"),
+    ("hv", "halfvolle"),
+    ("vol.", "volle"),
+    ("mag.", "magere"),
+    ("zv", "zonder vet"),
+    ("z.v.", "zonder vet"),
+    ("gesn.", "gesneden"),
+    ("ongez.", "ongezouten"),
+    ("gez.", "gezouten"),
+    ("ger.", "gerookte"),
+    ("gekr.", "gekruide"),
+    ("bl.", "blik"),
+    ("fl.", "fles"),
+    ("st.", "stuk"),
+];
+// Different ways stores write the same unit
+const UNIT_ALIASES: [(&str, &str); 14] = [
+    ("gram", "g"),
+    ("gr", "g"),
+    ("grams", "g"),
+    ("kilo", "kg"),
+    ("kilogram", "kg"),
+    ("liter", "l"),
+    ("litre", "l"),
+    ("ltr", "l"),
+    ("lt", "l"),
+    ("milliliter", "ml"),
+    ("centiliter", "cl"),
+    ("stuks", "stuk"),
+    ("st", "stuk"),
+    ("stk", "stuk"),
+];
+// Words that say something about the offer instead of the product
+const NOISE_WORDS: [&str; 8] = ["nieuw", "new", "actie", "aanbieding", "bonus", "online", "exclusief", "voordeel"];
+// Store brands put in front of their product names, longest first
+const STORE_PREFIXES: [&str; 3] = ["albert heijn", "jumbo", "ah"];
+const SYMBOLS: [char; 4] = ['™', '®', '©', '℗'];
+
+/// Normalize a product name for comparing and searching: lowercase, fold diacritics, expand
+/// abbreviations like "hal." and "zv", split sizes like "1,5l" into "1,5 l", normalize units and
+/// drop symbols, punctuation and marketing words.
+///
+/// # Example
+/// ```
+/// use scrape_core::text::normalize_text;
+///
+/// assert_eq!(normalize_text("Campina Hal. Melk 1,5Ltr™"), "campina halfvolle melk 1,5 l");
+/// ```
+pub fn normalize_text(value: &str) -> String {
+    let folded: String = fold_diacritics(&value.to_lowercase())
+        .chars()
+        .map(|c| if SYMBOLS.contains(&c) { ' ' } else { c })
+        .collect();
+
+    folded
+        .split_whitespace()
+        .flat_map(expand_abbreviation)
+        .flat_map(|word| split_word(&word))
+        .map(normalize_unit)
+        .filter(|word| !NOISE_WORDS.contains(&word.as_str()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// The string products are searched on, the normalized name without the store brand in front.
+/// The API normalizes search queries with a copy of these rules in api/v1/controller/search.go
+pub fn search_string(name: &str) -> String {
+    strip_store_prefix(&normalize_text(name)).to_owned()
+}
+
+/// Remove a store brand like "AH" or "Jumbo" from the start of a normalized name
+pub fn strip_store_prefix(normalized: &str) -> &str {
+    STORE_PREFIXES
+        .iter()
+        .find_map(|prefix| normalized.strip_prefix(prefix)?.strip_prefix(' '))
+        .unwrap_or(normalized)
+}
+
+/// Replace letters with diacritics by the plain letter, e.g. "café" becomes "cafe"
+pub fn fold_diacritics(value: &str) -> String {
+    value.nfd().filter(|c| !is_combining_mark(*c)).collect()
+}
+
+fn expand_abbreviation(word: &str) -> Vec<String> {
+    let trimmed = word.trim_matches(|c: char| !c.is_alphanumeric() && c != '.');
+    let expanded = ABBREVIATIONS
+        .iter()
+        .find(|(abbreviation, _)| *abbreviation == trimmed)
+        .map(|(_, expansion)| *expansion);
+
+    match expanded {
+        Some(expansion) => expansion.split(' ').map(str::to_owned).collect(),
+        None => vec![word.to_owned()],
+    }
+}
+
+// Split a word on punctuation and between numbers and letters, "6x330ml" becomes ["6", "x", "330", "ml"].
+// Decimal separators between digits are kept
+fn split_word(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for (i, c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let next = chars.get(i + 1);
+        let is_decimal_separator = (*c == ',' || *c == '.')
+            && prev.is_some_and(|p| p.is_ascii_digit())
+            && next.is_some_and(|n| n.is_ascii_digit());
+
+        if !c.is_alphanumeric() && !is_decimal_separator {
+            parts.extend((!current.is_empty()).then(|| std::mem::take(&mut current)));
+            continue;
+        }
+        let boundary = current.chars().last().is_some_and(|last| {
+            c.is_alphanumeric() && last.is_alphanumeric() && last.is_ascii_digit() != c.is_ascii_digit()
+        });
+        if boundary {
+            parts.push(std::mem::take(&mut current));
+        }
+        current.push(*c);
+    }
+    parts.extend((!current.is_empty()).then_some(current));
+    parts
+}
+
+fn normalize_unit(word: String) -> String {
+    UNIT_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == word)
+        .map_or(word, |(_, unit)| unit.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold_diacritics, normalize_text, search_string};
+
+    #[test]
+    fn test_normalize_text() {
+        let cases = [
+            ("Coca-Cola Zero Sugar 1,5L", "coca cola zero sugar 1,5 l"),
+            ("Hal. melk", "halfvolle melk"),
+            ("Yoghurt ZV 500gram", "yoghurt zonder vet 500 g"),
+            ("Heineken® Pils 6x330ml", "heineken pils 6 x 330 ml"),
+            ("NIEUW! Crème fraîche", "creme fraiche"),
+            ("Eieren 10 stuks", "eieren 10 stuk"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(normalize_text(input), expected, "normalizing '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_search_string() {
+        assert_eq!(search_string("AH Halfvolle melk"), "halfvolle melk");
+        assert_eq!(search_string("Jumbo Hal. Melk 1L"), "halfvolle melk 1 l");
+        assert_eq!(search_string("Albert Heijn Halfvolle Melk"), "halfvolle melk");
+        assert_eq!(search_string("Ahoy koekjes"), "ahoy koekjes");
+    }
+
+    #[test]
+    fn test_fold_diacritics() {
+        assert_eq!(fold_diacritics("café naïef Calvé"), "cafe naief Calve");
+    }
+}
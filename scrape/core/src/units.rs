@@ -0,0 +1,288 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::Serialize;
+use super::{Money, QuantityError};
+
+// Prefixes stores put in front of a weight that isn't exact, e.g. "ca. 1 kg"
+const APPROXIMATE_PREFIXES: [&str; 4] = ["ca.", "ca ", "circa", "±"];
+
+/// The unit a quantity is normalized to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Unit {
+    Gram,
+    Millilitre,
+    Piece,
+}
+
+impl Unit {
+    // Unit names as used in Dutch package descriptions and the factor to the base unit
+    fn from_name(name: &str) -> Option<(Unit, u64)> {
+        let unit = match name {
+            "g" | "gr" | "gr." | "gram" | "grams" => (Unit::Gram, 1),
+            "kg" | "kilo" | "kilogram" => (Unit::Gram, 1000),
+            "ml" | "milliliter" => (Unit::Millilitre, 1),
+            "cl" | "centiliter" => (Unit::Millilitre, 10),
+            "dl" | "deciliter" => (Unit::Millilitre, 100),
+            "l" | "lt" | "ltr" | "liter" | "litre" => (Unit::Millilitre, 1000),
+            "st" | "st." | "stuk" | "stuks" | "stk" => (Unit::Piece, 1),
+            _ => return None,
+        };
+        Some(unit)
+    }
+
+    /// The unit prices are compared in
+    pub fn comparable_unit(&self) -> ComparableUnit {
+        match self {
+            Unit::Gram => ComparableUnit::Kilogram,
+            Unit::Millilitre => ComparableUnit::Litre,
+            Unit::Piece => ComparableUnit::Piece,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Gram => "g",
+            Unit::Millilitre => "ml",
+            Unit::Piece => "stuks",
+        }
+    }
+}
+
+/// The unit a comparable price is expressed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum ComparableUnit {
+    Kilogram,
+    Litre,
+    Piece,
+}
+
+impl ComparableUnit {
+    /// Number of base units (grams, millilitres or pieces) in one comparable unit
+    pub fn base_units(&self) -> u64 {
+        match self {
+            ComparableUnit::Kilogram | ComparableUnit::Litre => 1000,
+            ComparableUnit::Piece => 1,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ComparableUnit::Kilogram => "kg",
+            ComparableUnit::Litre => "l",
+            ComparableUnit::Piece => "stuk",
+        }
+    }
+}
+
+/// Price per kilogram, litre or piece, used to compare products of different sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ComparablePrice {
+    pub price: Money,
+    pub per: ComparableUnit,
+}
+
+impl fmt::Display for ComparablePrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} per {}", self.price, self.per.symbol())
+    }
+}
+
+/// A package size, normalized to grams, millilitres or pieces.
+///
+/// # Example
+/// ```
+/// use scrape_core::{Quantity, Unit, Money};
+///
+/// let quantity = Quantity::parse("6 x 330 ml").unwrap();
+/// assert_eq!(quantity.unit, Unit::Millilitre);
+/// assert_eq!(quantity.total(), 1980);
+///
+/// let per_litre = quantity.comparable_price(Money::from_cents(495)).unwrap();
+/// assert_eq!(per_litre.to_string(), "€ 2,50 per l");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Quantity {
+    /// Number of items in a multipack, 1 for a single item
+    pub count: u64,
+    /// Size of a single item in the base unit
+    pub amount: u64,
+    pub unit: Unit,
+    /// The store only gives an estimate, e.g. "ca. 1 kg" for fruit sold by weight
+    pub approximate: bool,
+}
+
+impl Quantity {
+    pub fn new(count: u64, amount: u64, unit: Unit) -> Self {
+        Quantity { count, amount, unit, approximate: false }
+    }
+
+    /// Parse package descriptions such as "500 g", "1,5 l", "6 x 330 ml", "per stuk",
+    /// "ca. 1 kg" and "2 stuks"
+    pub fn parse(value: &str) -> Result<Quantity, QuantityError> {
+        let invalid = |reason: &str| QuantityError::InvalidFormat { value: value.to_owned(), reason: reason.to_owned() };
+
+        let mut text = value.trim().to_lowercase().replace('×', "x");
+        let mut approximate = false;
+        for prefix in APPROXIMATE_PREFIXES {
+            if let Some(stripped) = text.strip_prefix(prefix) {
+                text = stripped.trim_start().to_owned();
+                approximate = true;
+            }
+        }
+        if let Some(stripped) = text.strip_prefix("per ") {
+            text = format!("1 {}", stripped.trim_start());
+        }
+
+        let (count, size) = match text.split_once('x') {
+            Some((count, size)) if is_number(count.trim()) => {
+                let count = count.trim().parse::<u64>().map_err(|_| invalid("multipack count is not a whole number"))?;
+                (count, size.trim())
+            },
+            _ => (1, text.trim()),
+        };
+        if count == 0 {
+            return Err(invalid("multipack count is zero"));
+        }
+
+        let split = size
+            .find(|c: char| !(c.is_ascii_digit() || c == ',' || c == '.'))
+            .ok_or_else(|| invalid("no unit found"))?;
+        let (number, unit_name) = size.split_at(split);
+        let unit_name = unit_name.trim();
+        let (unit, factor) = Unit::from_name(unit_name).ok_or_else(|| invalid("unknown unit"))?;
+
+        // A unit without a number, like "stuk", means a single one
+        let amount = match number.is_empty() {
+            true if unit == Unit::Piece => Some(1),
+            true => return Err(invalid("no amount found")),
+            false => parse_scaled(number, factor),
+        }.ok_or_else(|| invalid("invalid amount"))?;
+        if amount == 0 {
+            return Err(invalid("amount is zero"));
+        }
+
+        Ok(Quantity { count, amount, unit, approximate })
+    }
+
+    /// The total amount of all items in the package in the base unit
+    pub fn total(&self) -> u64 {
+        self.count * self.amount
+    }
+
+    /// Price per kilogram, litre or piece for a package of this size costing `price`
+    pub fn comparable_price(&self, price: Money) -> Option<ComparablePrice> {
+        let per = self.unit.comparable_unit();
+        let total = self.total();
+        match total {
+            0 => None,
+            _ => Some(ComparablePrice {
+                price: price.mul_ratio(per.base_units() as i64, total as i64),
+                per,
+            }),
+        }
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = QuantityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Quantity::parse(s)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.approximate {
+            write!(f, "ca. ")?;
+        }
+        if self.count > 1 {
+            write!(f, "{} x ", self.count)?;
+        }
+        write!(f, "{} {}", self.amount, self.unit.symbol())
+    }
+}
+
+fn is_number(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+}
+
+// Parse a decimal number with a comma or dot as decimal separator and multiply it by `factor`,
+// rounding to a whole number. "1.000" with a factor of 1 is read as a thousand
+fn parse_scaled(number: &str, factor: u64) -> Option<u64> {
+    let (whole, fraction) = match number.rfind(['.', ',']) {
+        Some(idx) if factor == 1 && number.len() - idx - 1 == 3 => (number.replace(['.', ','], ""), String::new()),
+        Some(idx) => (number[..idx].to_owned(), number[idx + 1..].to_owned()),
+        None => (number.to_owned(), String::new()),
+    };
+    if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) || (whole.is_empty() && fraction.is_empty()) {
+        return None;
+    }
+
+    let whole: u64 = match whole.is_empty() {
+        true => 0,
+        false => whole.parse().ok()?,
+    };
+    let fraction_scaled = match fraction.is_empty() {
+        true => 0,
+        false => {
+            let divisor = 10u64.checked_pow(fraction.len() as u32)?;
+            (fraction.parse::<u64>().ok()? * factor + divisor / 2) / divisor
+        },
+    };
+    whole.checked_mul(factor)?.checked_add(fraction_scaled)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Money;
+    use super::{ComparableUnit, Quantity, Unit};
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            ("500 g", Quantity::new(1, 500, Unit::Gram)),
+            ("500g", Quantity::new(1, 500, Unit::Gram)),
+            ("1,5 l", Quantity::new(1, 1500, Unit::Millilitre)),
+            ("0.75 liter", Quantity::new(1, 750, Unit::Millilitre)),
+            ("25 cl", Quantity::new(1, 250, Unit::Millilitre)),
+            ("6 x 330 ml", Quantity::new(6, 330, Unit::Millilitre)),
+            ("4 × 1,5 L", Quantity::new(4, 1500, Unit::Millilitre)),
+            ("per stuk", Quantity::new(1, 1, Unit::Piece)),
+            ("2 stuks", Quantity::new(1, 2, Unit::Piece)),
+            ("1.000 g", Quantity::new(1, 1000, Unit::Gram)),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(Quantity::parse(input).unwrap(), expected, "parsing '{}'", input);
+        }
+
+        let approximate = Quantity::parse("ca. 1 kg").unwrap();
+        assert_eq!(approximate.total(), 1000);
+        assert!(approximate.approximate);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in ["", "g", "500", "500 pakjes", "0 x 330 ml", "0 g", "1,2,3 l"] {
+            assert!(Quantity::parse(input).is_err(), "parsing '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_comparable_price() {
+        let price = Money::from_cents(129);
+
+        let per_kg = Quantity::parse("500 g").unwrap().comparable_price(price).unwrap();
+        assert_eq!(per_kg.price.cents(), 258);
+        assert_eq!(per_kg.per, ComparableUnit::Kilogram);
+
+        let per_litre = Quantity::parse("6 x 330 ml").unwrap().comparable_price(Money::from_cents(495)).unwrap();
+        assert_eq!(per_litre.price.cents(), 250);
+        assert_eq!(per_litre.per, ComparableUnit::Litre);
+
+        let per_piece = Quantity::parse("2 stuks").unwrap().comparable_price(price).unwrap();
+        assert_eq!(per_piece.price.cents(), 65);
+        assert_eq!(per_piece.to_string(), "€ 0,65 per stuk");
+    }
+}
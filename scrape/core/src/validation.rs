@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use serde::Serialize;
+use super::{InDbProduct, Quantity, ValidationConfig, Severity};
+
+/// A sanity check on a scraped product
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// The price is within the configured bounds
+    PriceBounds,
+    /// The name isn't empty after trimming
+    EmptyName,
+    /// The product URL is on the store's own host
+    UrlHost,
+    /// No other product of the store has the same key
+    DuplicateKey,
+    /// The unit price is within bounds and agrees with the price and package size
+    UnitPrice,
+}
+
+impl Rule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rule::PriceBounds => "price_bounds",
+            Rule::EmptyName => "empty_name",
+            Rule::UrlHost => "url_host",
+            Rule::DuplicateKey => "duplicate_key",
+            Rule::UnitPrice => "unit_price",
+        }
+    }
+}
+
+/// A rule a product didn't pass
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationIssue {
+    pub store: String,
+    pub product_key: String,
+    pub url: String,
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed for {} ({}): {}", self.rule.as_str(), self.product_key, self.url, self.message)
+    }
+}
+
+/// Outcome of validating the products of a store
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub checked: usize,
+    /// Products that were left out because of an error
+    pub rejected: usize,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut per_rule: BTreeMap<Rule, usize> = BTreeMap::new();
+        for issue in self.issues.iter() {
+            *per_rule.entry(issue.rule).or_insert(0) += 1;
+        }
+        let per_rule: Vec<String> = per_rule
+            .iter()
+            .map(|(rule, count)| format!("{}: {}", rule.as_str(), count))
+            .collect();
+
+        write!(
+            f,
+            "checked {} products, {} rejected, {} errors, {} warnings",
+            self.checked,
+            self.rejected,
+            self.errors().count(),
+            self.warnings().count(),
+        )?;
+        match per_rule.is_empty() {
+            true => Ok(()),
+            false => write!(f, " ({})", per_rule.join(", ")),
+        }
+    }
+}
+
+/// Checks the products of a single store against the rules in `ValidationConfig`,
+/// run between scraping and writing the products.
+///
+/// # Example
+/// ```
+/// use scrape_core::{InDbProduct, Money, ProductInfo, ValidationConfig, Validator};
+///
+/// let products = vec![
+///     InDbProduct::new("Jumbo".to_owned(), ProductInfo::new("Melk".to_owned(), Money::from_cents(99), "https://www.jumbo.com/melk".to_owned())),
+///     InDbProduct::new("Jumbo".to_owned(), ProductInfo::new(" ".to_owned(), Money::zero(), "https://www.jumbo.com/leeg".to_owned())),
+/// ];
+/// let (valid, report) = Validator::new(ValidationConfig::default(), "https://www.jumbo.com").validate(products);
+///
+/// assert_eq!(valid.len(), 1);
+/// assert_eq!(report.rejected, 1);
+/// ```
+pub struct Validator {
+    config: ValidationConfig,
+    host: Option<String>,
+}
+
+impl Validator {
+    pub fn new(config: ValidationConfig, base_url: &str) -> Self {
+        Validator { config, host: host(base_url) }
+    }
+
+    /// Split the products in the ones that can be written and a report of the issues found
+    pub fn validate(&self, products: Vec<InDbProduct>) -> (Vec<InDbProduct>, ValidationReport) {
+        let mut report = ValidationReport { checked: products.len(), ..Default::default() };
+        let mut seen_keys: HashSet<(String, String)> = HashSet::new();
+        let mut valid = Vec::with_capacity(products.len());
+
+        for product in products {
+            let duplicate = !seen_keys.insert((product.store.clone(), product.info.key.clone()));
+            let issues: Vec<ValidationIssue> = self
+                .check(&product, duplicate)
+                .into_iter()
+                .filter(|(rule, _)| self.severity(*rule) != Severity::Off)
+                .map(|(rule, message)| ValidationIssue {
+                    store: product.store.clone(),
+                    product_key: product.info.key.clone(),
+                    url: product.info.url.clone(),
+                    rule,
+                    severity: self.severity(rule),
+                    message,
+                })
+                .collect();
+
+            match issues.iter().any(|issue| issue.severity == Severity::Error) {
+                true => report.rejected += 1,
+                false => valid.push(product),
+            };
+            report.issues.extend(issues);
+        }
+        (valid, report)
+    }
+
+    fn severity(&self, rule: Rule) -> Severity {
+        let rules = &self.config.rules;
+        match rule {
+            Rule::PriceBounds => rules.price_bounds,
+            Rule::EmptyName => rules.empty_name,
+            Rule::UrlHost => rules.url_host,
+            Rule::DuplicateKey => rules.duplicate_key,
+            Rule::UnitPrice => rules.unit_price,
+        }
+    }
+
+    fn check(&self, product: &InDbProduct, duplicate: bool) -> Vec<(Rule, String)> {
+        let info = &product.info;
+        let mut failed = Vec::new();
+
+        let cents = info.price.cents();
+        if cents < self.config.min_price_cents || cents > self.config.max_price_cents {
+            failed.push((Rule::PriceBounds, format!("price {} is outside the allowed range", info.price)));
+        }
+        if info.name.trim().is_empty() {
+            failed.push((Rule::EmptyName, "name is empty".to_owned()));
+        }
+        if let Some(expected) = &self.host {
+            match host(&info.url) {
+                Some(found) if found == *expected => {},
+                Some(found) => failed.push((Rule::UrlHost, format!("host '{}' isn't the store's host '{}'", found, expected))),
+                None => failed.push((Rule::UrlHost, "URL can't be parsed".to_owned())),
+            };
+        }
+        if duplicate {
+            failed.push((Rule::DuplicateKey, format!("key '{}' was already seen", info.key)));
+        }
+        if let Some(message) = self.check_unit_price(product) {
+            failed.push((Rule::UnitPrice, message));
+        }
+        failed
+    }
+
+    fn check_unit_price(&self, product: &InDbProduct) -> Option<String> {
+        let info = &product.info;
+        let unit_price = info.unit_price.as_ref()?;
+        let cents = unit_price.price.cents();
+        if cents <= 0 || cents > self.config.max_unit_price_cents {
+            return Some(format!("unit price {} per {} is outside the allowed range", unit_price.price, unit_price.unit));
+        }
+
+        // A unit like "kg" or "100 g" is compared with the package size when both can be parsed
+        let quantity = Quantity::parse(info.quantity.as_deref()?).ok()?;
+        let unit = match unit_price.unit.starts_with(|c: char| c.is_ascii_digit()) {
+            true => Quantity::parse(&unit_price.unit).ok()?,
+            false => Quantity::parse(&format!("1 {}", unit_price.unit)).ok()?,
+        };
+        if unit.unit != quantity.unit || quantity.total() == 0 {
+            return None;
+        }
+
+        let expected = info.price.mul_ratio(unit.total() as i64, quantity.total() as i64).cents();
+        let tolerance = expected * self.config.unit_price_tolerance_percent as i64 / 100;
+        match (cents - expected).abs() > tolerance {
+            true => Some(format!(
+                "unit price {} per {} doesn't match {} for {}",
+                unit_price.price, unit_price.unit, info.price, quantity,
+            )),
+            false => None,
+        }
+    }
+}
+
+// Host without "www.", so "jumbo.com" and "www.jumbo.com" are the same store
+fn host(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    Some(host.strip_prefix("www.").map(str::to_owned).unwrap_or(host))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InDbProduct, Money, ProductInfo, UnitPrice, ValidationConfig, Severity};
+    use super::{Rule, Validator};
+
+    const BASE_URL: &str = "https://www.ah.nl";
+
+    fn product(name: &str, cents: i64, url: &str) -> InDbProduct {
+        InDbProduct::new("Albert Heijn".to_owned(), ProductInfo::new(name.to_owned(), Money::from_cents(cents), url.to_owned()))
+    }
+
+    fn failed_rules(product: InDbProduct) -> Vec<Rule> {
+        let (_, report) = Validator::new(ValidationConfig::default(), BASE_URL).validate(vec![product]);
+        report.issues.iter().map(|issue| issue.rule).collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!(failed_rules(product("AH Halfvolle melk", 119, "https://www.ah.nl/producten/product/wi1")), vec![]);
+        assert_eq!(failed_rules(product("  ", 119, "https://ah.nl/producten/product/wi1")), vec![Rule::EmptyName]);
+        assert_eq!(failed_rules(product("AH Melk", 0, "https://www.ah.nl/melk")), vec![Rule::PriceBounds]);
+        assert_eq!(failed_rules(product("AH Melk", 119, "https://www.jumbo.com/melk")), vec![Rule::UrlHost]);
+        assert_eq!(failed_rules(product("AH Melk", 119, "/producten/melk")), vec![Rule::UrlHost]);
+    }
+
+    #[test]
+    fn test_unit_price() {
+        let mut plausible = product("AH Halfvolle melk", 119, "https://www.ah.nl/melk");
+        plausible.info.quantity = Some("1,5 l".to_owned());
+        plausible.info.unit_price = Some(UnitPrice { price: Money::from_cents(79), unit: "liter".to_owned() });
+        assert_eq!(failed_rules(plausible), vec![]);
+
+        let mut per_100g = product("AH Kaas", 450, "https://www.ah.nl/kaas");
+        per_100g.info.quantity = Some("500 g".to_owned());
+        per_100g.info.unit_price = Some(UnitPrice { price: Money::from_cents(90), unit: "100 g".to_owned() });
+        assert_eq!(failed_rules(per_100g), vec![]);
+
+        let mut shifted = product("AH Halfvolle melk", 119, "https://www.ah.nl/melk");
+        shifted.info.quantity = Some("1,5 l".to_owned());
+        shifted.info.unit_price = Some(UnitPrice { price: Money::from_cents(790), unit: "liter".to_owned() });
+        assert_eq!(failed_rules(shifted), vec![Rule::UnitPrice]);
+    }
+
+    #[test]
+    fn test_severity() {
+        let products = vec![
+            product("AH Melk", 119, "https://www.ah.nl/melk"),
+            product("AH Melk", 119, "https://www.ah.nl/melk?page=2"),
+            product("", 119, "https://www.ah.nl/leeg"),
+        ];
+        let mut config = ValidationConfig::default();
+        config.rules.empty_name = Severity::Off;
+
+        let (valid, report) = Validator::new(config, BASE_URL).validate(products);
+
+        assert_eq!(valid.len(), 3);
+        assert_eq!(report.rejected, 0);
+        assert_eq!(report.warnings().map(|issue| issue.rule).collect::<Vec<Rule>>(), vec![Rule::DuplicateKey]);
+        assert_eq!(report.to_string(), "checked 3 products, 0 rejected, 0 errors, 1 warnings (duplicate_key: 1)");
+    }
+}
@@ -0,0 +1,193 @@
+use scraper::{Html, ElementRef};
+use anyhow::Result;
+use scrape_core::{ProductDetails, NutritionValue};
+use scrape_core::scrape_utils::{build_selector, build_selectors, find_text, json_ld_products, product_from_json_ld};
+use super::jumbo_scraper::SRC;
+use super::parse::get_breadcrumbs;
+
+// Labels in front of the texts on a product page
+const INGREDIENTS_LABELS: [&str; 2] = ["ingrediënten:", "ingredienten:"];
+const ALLERGENS_LABELS: [&str; 3] = ["allergie-informatie:", "bevat:", "allergenen:"];
+
+/// Description, ingredients, allergens and nutrition table of a product page
+pub fn get_details(document: &Html) -> Result<ProductDetails> {
+    let root = document.root_element();
+    Ok(ProductDetails {
+        description: get_description(document)?,
+        ingredients: get_ingredients(root)?,
+        allergens: get_allergens(root)?,
+        nutrition: get_nutrition(root)?,
+    })
+}
+
+pub fn get_description(document: &Html) -> Result<Option<String>> {
+    let selectors = build_selectors(&[
+        "[data-testid='product-description']",
+        "div.product-description",
+        "section.product-summary div.description",
+    ], SRC)?;
+    let description = find_text(document.root_element(), &selectors).or_else(|| {
+        json_ld_products(document)
+            .iter()
+            .find_map(|product| product.get("description")?.as_str().map(|d| d.trim().to_owned()))
+            .filter(|d| !d.is_empty())
+    });
+    Ok(description)
+}
+
+pub fn get_ingredients(element: ElementRef) -> Result<Option<String>> {
+    let selectors = build_selectors(&["[data-testid='product-ingredients']", "div.ingredients", "section.ingredients p"], SRC)?;
+    Ok(find_text(element, &selectors).map(|text| strip_label(&text, &INGREDIENTS_LABELS)).filter(|text| !text.is_empty()))
+}
+
+/// Allergens as listed, or split from a text like "Bevat: melk, soja"
+pub fn get_allergens(element: ElementRef) -> Result<Vec<String>> {
+    let item_selectors = build_selectors(&["[data-testid='product-allergens'] li", "div.allergens li"], SRC)?;
+    let listed: Vec<String> = item_selectors
+        .iter()
+        .map(|selector| element.select(selector).map(text).filter(|t| !t.is_empty()).collect::<Vec<String>>())
+        .find(|items| !items.is_empty())
+        .unwrap_or_default();
+    if !listed.is_empty() {
+        return Ok(listed);
+    }
+
+    let text_selectors = build_selectors(&["[data-testid='product-allergens']", "div.allergens"], SRC)?;
+    Ok(find_text(element, &text_selectors)
+        .map(|text| {
+            strip_label(&text, &ALLERGENS_LABELS)
+                .split([',', ';'])
+                .map(|allergen| allergen.trim().trim_end_matches('.').to_owned())
+                .filter(|allergen| !allergen.is_empty())
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Rows of the nutrition table, the columns are per 100 g/ml and per portion
+pub fn get_nutrition(element: ElementRef) -> Result<Vec<NutritionValue>> {
+    let table_selectors = build_selectors(&[
+        "[data-testid='nutrition-table'] table",
+        "table.nutritional-values",
+        "table.jum-nutritional-info",
+    ], SRC)?;
+    let row_selector = build_selector("tbody tr", SRC)?;
+    let cell_selector = build_selector("th, td", SRC)?;
+
+    let Some(table) = table_selectors.iter().find_map(|selector| element.select(selector).next()) else {
+        return Ok(Vec::new());
+    };
+    Ok(table
+        .select(&row_selector)
+        .filter_map(|row| {
+            let mut cells = row.select(&cell_selector).map(text);
+            let name = cells.next().filter(|name| !name.is_empty())?;
+            let mut value = || cells.next().filter(|value| !value.is_empty());
+            Some(NutritionValue { name, per_100: value(), per_portion: value() })
+        })
+        .collect())
+}
+
+/// Package size as shown below the product name
+pub fn get_package_size(element: ElementRef) -> Result<Option<String>> {
+    let selectors = build_selectors(&["[data-testid='product-quantity']", "span.product-subtitle", "div.product-subtitle"], SRC)?;
+    Ok(find_text(element, &selectors))
+}
+
+pub fn get_ean(document: &Html, base_url: &str) -> Result<Option<String>> {
+    let from_json_ld = json_ld_products(document)
+        .iter()
+        .find_map(|product| product_from_json_ld(product, base_url)?.ean);
+    if from_json_ld.is_some() {
+        return Ok(from_json_ld);
+    }
+
+    let selectors = build_selectors(&["[data-testid='product-ean']", "span.product-ean"], SRC)?;
+    Ok(find_text(document.root_element(), &selectors)
+        .map(|text| text.chars().filter(|c| c.is_ascii_digit()).collect::<String>())
+        .filter(|ean| (8..=14).contains(&ean.len())))
+}
+
+pub fn get_category(document: &Html) -> Result<Vec<String>> {
+    get_breadcrumbs(document)
+}
+
+fn text(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn strip_label(text: &str, labels: &[&str]) -> String {
+    let lowered = text.to_lowercase();
+    labels
+        .iter()
+        .find(|label| lowered.starts_with(*label))
+        .map(|label| text[label.len()..].trim().to_owned())
+        .unwrap_or_else(|| text.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+    use scrape_core::NutritionValue;
+    use super::{get_details, get_allergens, get_ean};
+
+    const PRODUCT_PAGE: &str = r#"
+        <html><body>
+            <div data-testid="product-description">Halfvolle melk, vers van de boerderij.</div>
+            <div data-testid="product-ingredients">Ingrediënten: halfvolle melk</div>
+            <div data-testid="product-allergens">Bevat: melk, lactose.</div>
+            <div data-testid="nutrition-table">
+                <table>
+                    <thead><tr><th>Per</th><th>100 ml</th><th>250 ml</th></tr></thead>
+                    <tbody>
+                        <tr><th>Energie</th><td>195 kJ</td><td>488 kJ</td></tr>
+                        <tr><th>Vet</th><td>1,5 g</td><td></td></tr>
+                    </tbody>
+                </table>
+            </div>
+            <span data-testid="product-ean">EAN: 8718452 000011</span>
+        </body></html>
+    "#;
+
+    #[test]
+    fn test_details() {
+        let document = Html::parse_document(PRODUCT_PAGE);
+        let details = get_details(&document).unwrap();
+
+        assert_eq!(details.description.as_deref(), Some("Halfvolle melk, vers van de boerderij."));
+        assert_eq!(details.ingredients.as_deref(), Some("halfvolle melk"));
+        assert_eq!(details.allergens, vec!["melk", "lactose"]);
+        assert_eq!(details.nutrition, vec![
+            NutritionValue { name: "Energie".to_owned(), per_100: Some("195 kJ".to_owned()), per_portion: Some("488 kJ".to_owned()) },
+            NutritionValue { name: "Vet".to_owned(), per_100: Some("1,5 g".to_owned()), per_portion: None },
+        ]);
+    }
+
+    #[test]
+    fn test_listed_allergens() {
+        let document = Html::parse_document(r#"<ul data-testid="product-allergens"><li>Gluten</li><li> Soja </li></ul>"#);
+
+        assert_eq!(get_allergens(document.root_element()).unwrap(), vec!["Gluten", "Soja"]);
+    }
+
+    #[test]
+    fn test_ean() {
+        let from_text = Html::parse_document(PRODUCT_PAGE);
+        assert_eq!(get_ean(&from_text, "https://www.jumbo.com").unwrap().as_deref(), Some("8718452000011"));
+
+        let from_json_ld = Html::parse_document(r#"
+            <script type="application/ld+json">{
+                "@type": "Product",
+                "name": "Jumbo Halfvolle Melk",
+                "url": "/producten/jumbo-halfvolle-melk-1l-67649PAK",
+                "gtin13": "8718452000028",
+                "offers": {"@type": "Offer", "price": "1.19", "priceCurrency": "EUR"}
+            }</script>
+            <span data-testid="product-ean">8718452000011</span>
+        "#);
+        assert_eq!(get_ean(&from_json_ld, "https://www.jumbo.com").unwrap().as_deref(), Some("8718452000028"));
+
+        let too_short = Html::parse_document(r#"<span data-testid="product-ean">EAN: 1234</span>"#);
+        assert_eq!(get_ean(&too_short, "https://www.jumbo.com").unwrap(), None);
+    }
+}
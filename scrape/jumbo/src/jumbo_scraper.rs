@@ -1,67 +1,306 @@
 use anyhow::Result;
-use log::info;
-use scrape_core::{HtmlLoader, ProductInfo, AsyncExecutor, ResultCollector, AsyncTransform, Scraper};
-use scrape_core::scrape_utils::build_selector;
-use super::parse::{get_name, get_price, get_nr_pages, get_product_url};
+use chrono::{DateTime, Utc};
+use log::{info, debug};
+use scraper::Html;
+use scrape_core::{
+    HtmlLoader,
+    ProductInfo,
+    AsyncExecutor,
+    ResultCollector,
+    Scraper,
+    Pipeline,
+    Stage,
+    ErrorPolicy,
+    StoreConfig,
+    Discovery,
+    CategoryTree,
+    CategoryPage,
+    ScrapeError,
+    Sitemap,
+};
+use scrape_core::scrape_utils::{build_selector, merge_page_products, json_ld_products, product_from_json_ld};
+use super::parse::{
+    get_name,
+    get_price,
+    get_nr_pages,
+    get_product_url,
+    get_quantity,
+    get_unit_price,
+    get_image_url,
+    get_sku,
+    get_availability,
+    get_offer,
+    get_category,
+    product_key,
+    sku_from_url,
+};
+use super::detail::{get_details, get_package_size, get_ean, get_category as get_detail_category};
 
 const PRODUCTS_PER_PAGE: usize = 24;
 pub const BASE_URL: &str = "https://www.jumbo.com";
-const URL: &str = "https://www.jumbo.com/producten";
+const PRODUCTS_PATH: &str = "/producten";
 const OFFSET_URL: &str = "/?offSet=";
 pub const SRC: &str = "Jumbo";
+const CATEGORIES_PATH: &str = "/producten/categorieen";
+// Guards against following links in circles, the tree is about three levels deep
+const MAX_CATEGORY_DEPTH: usize = 5;
+const SITEMAP_PATH: &str = "/sitemap.xml";
+// Product pages end in the SKU, e.g. /producten/jumbo-halfvolle-melk-1l-67649PAK
+const PRODUCT_URL_PATTERN: &str = r"/producten/[^/?]+-\d+[A-Z]+$";
+/// Supported ways of finding products, the first is the default. Offset paging over all products
+/// is the cheapest, the category crawl gives every product its category path and the sitemap
+/// visits every product page, which includes the details
+pub const DISCOVERY: [Discovery; 3] = [Discovery::Listing, Discovery::Categories, Discovery::Sitemap];
+
+/// Settings the Jumbo scraper uses when nothing else is configured
+pub fn default_config() -> StoreConfig {
+    StoreConfig::default().with_base_url(BASE_URL)
+}
 
 pub struct JumboScraper<'a, T: HtmlLoader + Send + Sync> {
     connector: &'a T,
+    base_url: String,
+    discovery: Discovery,
+    modified_since: Option<DateTime<Utc>>,
 }
 
 impl<'a, T: HtmlLoader + Send + Sync> JumboScraper<'a, T> {
     pub fn new(connector: &'a T) -> Self {
-        Self { connector }
+        Self { connector, base_url: BASE_URL.to_owned(), discovery: DISCOVERY[0], modified_since: None }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// How products are found, one of `DISCOVERY`
+    pub fn with_discovery(mut self, discovery: Discovery) -> Self {
+        self.discovery = discovery;
+        self
+    }
+
+    /// With the sitemap discovery, only visit the products whose page changed since then
+    pub fn with_modified_since(mut self, modified_since: Option<DateTime<Utc>>) -> Self {
+        self.modified_since = modified_since;
+        self
+    }
+
+    fn products_url(&self) -> String {
+        format!("{}{}", self.base_url, PRODUCTS_PATH)
     }
 
     async fn scrape_page(&self, offset: String) -> Result<Vec<ProductInfo>> {
-        let url = format!("{}{}{}", URL, OFFSET_URL, offset);
+        let url = format!("{}{}{}", self.products_url(), OFFSET_URL, offset);
+        self.scrape_listing(url).await
+    }
+
+    async fn scrape_listing(&self, url: String) -> Result<Vec<ProductInfo>> {
         info!("Scraping url {}", &url);
-        
+
         let document = self.connector.load(url.clone()).await?;
         let selector = build_selector("article.product-container", SRC)?;
         let html_products = document.select(&selector);
     
-        html_products
+        let products: Result<Vec<ProductInfo>> = html_products
             .into_iter()
             .map(|html_product| -> Result<ProductInfo> {
-                Ok(ProductInfo::new(
-                get_name(html_product)?,
-                get_price(html_product)?,
-                get_product_url(html_product)?
-                ))
+                let url = get_product_url(html_product, &self.base_url)?;
+                let mut product = ProductInfo::new(
+                    get_name(html_product)?,
+                    get_price(html_product)?,
+                    url.clone(),
+                ).with_key(product_key(&url));
+                product.quantity = get_quantity(html_product)?;
+                product.unit_price = get_unit_price(html_product)?;
+                product.image_url = get_image_url(html_product)?;
+                product.sku = get_sku(html_product).or_else(|| sku_from_url(&url));
+                product.availability = get_availability(html_product)?;
+                product.offer = get_offer(html_product)?;
+                product.category = get_category(html_product, &document)?;
+                Ok(product)
+            })
+            .collect();
+        merge_page_products(&document, products, &self.base_url, product_key, sku_from_url, SRC, &url)
+    }
+
+    /// Visit the page of every product for which `filter` holds and add what is only shown there.
+    /// The other products are passed on as they are, as are products whose page fails to load.
+    /// `executor` is used for the detail requests only, so they can run at their own rate
+    pub async fn scrape_details<R, F>(&self, products: ResultCollector<ProductInfo>, executor: &R, filter: F) -> ResultCollector<ProductInfo>
+    where
+        R: AsyncExecutor + Send + Sync,
+        F: Fn(&ProductInfo) -> bool,
+    {
+        let (visit, skip): (Vec<ProductInfo>, Vec<ProductInfo>) = products.successes.into_iter().partition(|p| filter(p));
+        info!(target: SRC, "Scraping {} product pages, skipping {}", visit.len(), skip.len());
+
+        let input = ResultCollector { successes: visit, errors: products.errors };
+        let (mut detailed, report) = Pipeline::new(SRC)
+            .stage(Stage::new("details", executor, |product| self.scrape_detail_page(product)))
+            .run(input)
+            .await;
+
+        info!(target: SRC, "{}", report);
+        detailed.successes.extend(skip);
+        detailed
+    }
+
+    async fn scrape_detail_page(&self, product: ProductInfo) -> ResultCollector<ProductInfo> {
+        debug!("Scraping product page {}", &product.url);
+        match self.parse_detail_page(product.clone()).await {
+            Ok(detailed) => ResultCollector::from_value(detailed),
+            // The listing data is still good
+            Err(e) => ResultCollector { successes: vec![product], errors: vec![e] },
+        }
+    }
+
+    async fn parse_detail_page(&self, product: ProductInfo) -> Result<ProductInfo> {
+        let document = self.connector.load(product.url.clone()).await?;
+        self.add_details(product, &document)
+    }
+
+    fn add_details(&self, mut product: ProductInfo, document: &Html) -> Result<ProductInfo> {
+        let root = document.root_element();
+
+        if let Some(quantity) = get_package_size(root)? {
+            product.quantity = Some(quantity);
+        }
+        if let Some(ean) = get_ean(document, &self.base_url)? {
+            product.ean = Some(ean);
+        }
+        let category = get_detail_category(document)?;
+        if !category.is_empty() {
+            product.category = category;
+        }
+        let details = get_details(document)?;
+        product.details = (!details.is_empty()).then_some(details);
+        Ok(product)
+    }
+
+    /// A product from the JSON-LD of its page, with the details shown there
+    async fn scrape_product_page(&self, url: String) -> Result<Vec<ProductInfo>> {
+        debug!("Scraping product page {}", &url);
+        let document = self.connector.load(url.clone()).await?;
+        let mut product = json_ld_products(&document)
+            .into_iter()
+            .find_map(|mut value| {
+                // The product is the page itself, so its JSON-LD doesn't always repeat the URL
+                if let Some(object) = value.as_object_mut() {
+                    object.entry("url").or_insert_with(|| url.clone().into());
+                }
+                product_from_json_ld(&value, &self.base_url)
             })
+            .ok_or_else(|| ScrapeError::NoProductsFound { src: SRC.to_owned(), url: url.clone() })?;
+
+        product.sku = product.sku.or_else(|| sku_from_url(&url));
+        product.url = url;
+        let key = product_key(&product.url);
+        Ok(vec![self.add_details(product.with_key(key), &document)?])
+    }
+
+    async fn scrape_category_offsets(&self, category: CategoryPage) -> Result<Vec<(CategoryPage, String)>> {
+        let document = self.connector.load(format!("{}{}", self.base_url, category.url_path)).await?;
+        // Small categories fit on a single page and have no pagination
+        let nr_pages = get_nr_pages(&document).unwrap_or(1);
+        Ok(
+            (0..nr_pages)
+            .map(|e| (category.clone(), (e * PRODUCTS_PER_PAGE).to_string()))
             .collect()
+        )
     }
 
-    async fn scrape_nr_pages(&self) -> Result<usize> {
-        let document = self.connector.load(URL.to_owned()).await?;
-        Ok(get_nr_pages(&document)?)
+    async fn scrape_category_page(&self, (category, offset): (CategoryPage, String)) -> Result<Vec<ProductInfo>> {
+        let url = format!("{}{}{}{}", self.base_url, category.url_path, OFFSET_URL, offset);
+        let products = self.scrape_listing(url).await?;
+        Ok(products.into_iter().map(|mut product| {
+            product.category = category.path.clone();
+            product
+        }).collect())
     }
-}
 
-impl<'a, T: HtmlLoader + Send + Sync> Scraper for JumboScraper<'a, T> {
-    async fn scrape<R: AsyncExecutor + Send + Sync>(&self, rate_limiter: &R) -> ResultCollector<ProductInfo> {
-        info!(target: SRC, "Start scraping");
+    async fn scrape_by_listing<R: AsyncExecutor + Send + Sync>(&self, rate_limiter: &R) -> ResultCollector<ProductInfo> {
+        let (products, report) = Pipeline::new(SRC)
+            .stage(
+                Stage::new("offsets", rate_limiter, |_: ()| self.scrape_offsets())
+                .error_policy(ErrorPolicy::FailFast)
+            )
+            .stage(Stage::new("pages", rate_limiter, |offset| self.scrape_page(offset)))
+            .run(ResultCollector::from_value(()))
+            .await;
+
+        info!(target: SRC, "{}", report);
+        products
+    }
+
+    async fn scrape_by_categories<R: AsyncExecutor + Send + Sync>(&self, rate_limiter: &R) -> ResultCollector<ProductInfo> {
+        let categories = CategoryTree::new(self.connector, &self.base_url, CATEGORIES_PATH)
+            .with_max_depth(MAX_CATEGORY_DEPTH)
+            .leaves(rate_limiter)
+            .await;
+        info!(target: SRC, "Found {} leaf categories", categories.successes.len());
+
+        let (mut products, report) = Pipeline::new(SRC)
+            .stage(Stage::new("category offsets", rate_limiter, |category| self.scrape_category_offsets(category)))
+            .stage(Stage::new("category pages", rate_limiter, |page| self.scrape_category_page(page)))
+            .run(categories)
+            .await;
+
+        info!(target: SRC, "{}", report);
+        // A product can be listed in several categories, only the first is kept
+        let removed = products.dedupe_by_key(|product| product.key.clone());
+        info!(target: SRC, "Removed {} products listed more than once", removed);
+        products
+    }
 
-        let nr_pages = match self.scrape_nr_pages().await {
-            Ok(amt) => amt,
-            Err(e) => return ResultCollector::from(e),
+    async fn scrape_by_sitemap<R: AsyncExecutor + Send + Sync>(&self, rate_limiter: &R) -> ResultCollector<ProductInfo> {
+        let sitemap_url = format!("{}{}", self.base_url, SITEMAP_PATH);
+        let sitemap = Sitemap::new(self.connector, &[&sitemap_url])
+            .with_modified_since(self.modified_since)
+            .with_include(&[PRODUCT_URL_PATTERN]);
+        let urls = match sitemap {
+            Ok(sitemap) => sitemap.urls(rate_limiter).await,
+            Err(e) => return ResultCollector { successes: Vec::new(), errors: vec![e.into()] },
         };
+        info!(target: SRC, "Found {} product urls in the sitemaps", urls.successes.len());
+
+        let (mut products, report) = Pipeline::new(SRC)
+            .stage(Stage::new("product pages", rate_limiter, |url| self.scrape_product_page(url)))
+            .run(urls)
+            .await;
+
+        info!(target: SRC, "{}", report);
+        // Sitemaps can list a product under several URLs, only the first is kept
+        let removed = products.dedupe_by_key(|product| product.key.clone());
+        info!(target: SRC, "Removed {} products listed more than once", removed);
+        products
+    }
+
+    async fn scrape_nr_pages(&self) -> Result<usize> {
+        let document = self.connector.load(self.products_url()).await?;
+        get_nr_pages(&document)
+    }
+
+    async fn scrape_offsets(&self) -> Result<Vec<String>> {
+        let nr_pages = self.scrape_nr_pages().await?;
         info!("Found {} pages", &nr_pages);
 
-        let offsets = (0..nr_pages)
+        Ok(
+            (0..nr_pages)
             .map(|e| (e * PRODUCTS_PER_PAGE).to_string())
-            .collect::<Vec<String>>();
+            .collect()
+        )
+    }
+}
 
-        ResultCollector::from(offsets)
-            .transform_async(|i| self.scrape_page(i), rate_limiter)
-            .await
-            .flatten()
+impl<'a, T: HtmlLoader + Send + Sync> Scraper for JumboScraper<'a, T> {
+    async fn scrape<R: AsyncExecutor + Send + Sync>(&self, rate_limiter: &R) -> ResultCollector<ProductInfo> {
+        info!(target: SRC, "Start scraping by {}", self.discovery.as_str());
+
+        match self.discovery {
+            Discovery::Categories => self.scrape_by_categories(rate_limiter).await,
+            Discovery::Sitemap => self.scrape_by_sitemap(rate_limiter).await,
+            _ => self.scrape_by_listing(rate_limiter).await,
+        }
     }
-}
\ No newline at end of file
+}
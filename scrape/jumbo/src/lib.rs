@@ -1,4 +1,6 @@
 mod jumbo_scraper;
 mod parse;
+mod detail;
 
-pub use jumbo_scraper::JumboScraper;
\ No newline at end of file
+pub use jumbo_scraper::{JumboScraper, SRC, BASE_URL, DISCOVERY, default_config};
+pub use parse::product_key;
\ No newline at end of file
@@ -1,21 +1,31 @@
-use std::num::{ParseIntError, ParseFloatError};
+use std::num::ParseIntError;
 use scraper::{Html, ElementRef};
-use super::jumbo_scraper::{SRC, BASE_URL};
+use super::jumbo_scraper::SRC;
 use anyhow::Result;
-use scrape_core::ScrapeError;
+use scrape_core::{ScrapeError, Money, UnitPrice, Availability, Offer, Promotion};
 use scrape_core::scrape_utils::{
     walk_selectors,
     build_selector,
     build_selectors,
+    find_text,
+    find_attr,
+    normalize_url,
 };
 
-pub fn get_product_url(element: ElementRef) -> Result<String> {
+// Texts shown instead of the add to basket button when a product can't be ordered
+const UNAVAILABLE_TEXTS: [&str; 3] = ["niet beschikbaar", "uitverkocht", "niet leverbaar"];
+// Breadcrumbs above every listing that aren't a category
+const ROOT_BREADCRUMBS: [&str; 3] = ["home", "producten", "alle producten"];
+// Label of an offer that only shows a reduced price
+const OFFER_LABEL: &str = "Aanbieding";
+
+pub fn get_product_url(element: ElementRef, base_url: &str) -> Result<String> {
     let selector = build_selector("a", SRC)?;
     let link_html = walk_selectors(element, &[selector], SRC)?;
     let link = link_html
         .attr("href")
         .ok_or(ScrapeError::InvalidStructureAssumed { src: SRC.to_string(), info: String::from("href") })?;
-    let mut prod_url = BASE_URL.to_owned();
+    let mut prod_url = base_url.to_owned();
     prod_url.push_str(link);
     Ok(prod_url)
 }
@@ -68,7 +78,16 @@ pub fn get_name(html_product: ElementRef) -> Result<String> {
     )
 }
 
-pub fn get_price(html_product: ElementRef) -> Result<f32> {
+pub fn get_price(html_product: ElementRef) -> Result<Money> {
+    // Products on offer show the struck through regular price next to the offer price
+    if let Some(was_price) = get_was_price(html_product)? {
+        return Ok(was_price);
+    }
+    get_current_price(html_product)
+}
+
+/// The price the product is sold for right now, the offer price when it's on offer
+fn get_current_price(html_product: ElementRef) -> Result<Money> {
     let selector_strings = [
         "div.jum-price",
         "div.current-price",
@@ -80,19 +99,170 @@ pub fn get_price(html_product: ElementRef) -> Result<f32> {
     let whole_price_selector = build_selector("span.whole", SRC)?;
     let frac_price_selector = build_selector("sup.fractional", SRC)?;
 
-    let mut whole_price: String = html_price
+    let whole_price: String = html_price
         .select(&whole_price_selector)
         .map(|a| a.text().collect::<String>()).collect();
     let frac_price: String = html_price
         .select(&frac_price_selector)
         .map(|a| a.text().collect::<String>()).collect();
 
-    whole_price.push_str(".");
-    whole_price.push_str(frac_price.as_str());
-
     Ok(
-        whole_price.parse().map_err(|e: ParseFloatError| {
+        Money::from_parts(&whole_price, &frac_price).map_err(|e| {
             ScrapeError::FailedToParseStringValue{ src: SRC.to_string(), err: e.to_string() }
         })?
     )
-}
\ No newline at end of file
+}
+
+fn get_was_price(html_product: ElementRef) -> Result<Option<Money>> {
+    let selectors = build_selectors(&["div.jum-price-old", "span.old-price", "div.current-price s"], SRC)?;
+    Ok(find_text(html_product, &selectors).and_then(|price| Money::parse(&price).ok()))
+}
+
+pub fn get_quantity(html_product: ElementRef) -> Result<Option<String>> {
+    let selectors = build_selectors(&["div.subtitle", "span.product-subtitle"], SRC)?;
+    Ok(find_text(html_product, &selectors))
+}
+
+pub fn get_unit_price(html_product: ElementRef) -> Result<Option<UnitPrice>> {
+    let selectors = build_selectors(&["div.price-per-unit", "span.price-per-unit"], SRC)?;
+    Ok(find_text(html_product, &selectors).and_then(|text| UnitPrice::parse(&text)))
+}
+
+pub fn get_image_url(html_product: ElementRef) -> Result<Option<String>> {
+    let selectors = build_selectors(&["div.product-image img", "img"], SRC)?;
+    Ok(find_attr(html_product, &selectors, "src").or_else(|| find_attr(html_product, &selectors, "data-src")))
+}
+
+pub fn get_sku(html_product: ElementRef) -> Option<String> {
+    ["data-product-id", "data-sku"]
+        .iter()
+        .find_map(|attr| html_product.value().attr(attr))
+        .map(|sku| sku.trim().to_owned())
+}
+
+/// The Jumbo SKU at the end of a product URL, e.g. "67649PAK" for /producten/halfvolle-melk-1l-67649PAK
+pub fn sku_from_url(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?.trim_end_matches('/');
+    let (_, sku) = path.rsplit_once('-')?;
+    let digits = sku.chars().take_while(|c| c.is_ascii_digit()).count();
+    let valid = digits > 0 && sku[digits..].chars().all(|c| c.is_ascii_uppercase());
+    valid.then(|| sku.to_owned())
+}
+
+/// Key that identifies a product across scrape runs, the SKU or the normalized URL when there is none
+pub fn product_key(url: &str) -> String {
+    sku_from_url(url).unwrap_or_else(|| normalize_url(url))
+}
+
+pub fn get_availability(html_product: ElementRef) -> Result<Availability> {
+    let text = html_product.text().collect::<String>().to_lowercase();
+    if UNAVAILABLE_TEXTS.iter().any(|t| text.contains(t)) {
+        return Ok(Availability::Unavailable);
+    }
+
+    let basket_selectors = build_selectors(&["button.add-to-basket", "button[data-testid='add-to-basket']"], SRC)?;
+    match basket_selectors.iter().any(|s| html_product.select(s).next().is_some()) {
+        true => Ok(Availability::Available),
+        false => Ok(Availability::Unknown),
+    }
+}
+
+pub fn get_offer(html_product: ElementRef) -> Result<Option<Offer>> {
+    let label_selectors = build_selectors(&["div.jum-promotion-tag", "span.jum-tag.promotion", "[data-testid='promotion-tag']"], SRC)?;
+    let validity_selectors = build_selectors(&["div.promotion-date", "span.jum-promotion-date"], SRC)?;
+
+    let label = find_text(html_product, &label_selectors);
+    let validity = find_text(html_product, &validity_selectors);
+    if let Some(offer) = label.as_deref().and_then(|label| Offer::parse(label, validity.as_deref())) {
+        return Ok(Some(offer));
+    }
+
+    // A struck through price without a recognized label means the current price is the offer price
+    if get_was_price(html_product)?.is_none() {
+        return Ok(None);
+    }
+    let offer = Offer::new(Promotion::FixedPrice { price: get_current_price(html_product)? }, label.as_deref().unwrap_or(OFFER_LABEL));
+    Ok(Some(match validity.as_deref() {
+        Some(validity) => offer.with_validity(validity),
+        None => offer,
+    }))
+}
+
+/// Category path of a product tile, e.g. ["Zuivel, eieren, boter", "Melk"]. Falls back on the
+/// breadcrumbs of the page the tile is on
+pub fn get_category(html_product: ElementRef, document: &Html) -> Result<Vec<String>> {
+    let from_tile = ["data-category", "data-product-category"]
+        .iter()
+        .find_map(|attr| html_product.value().attr(attr))
+        .map(|path| path.split(['/', '>']).map(|c| c.trim().to_owned()).filter(|c| !c.is_empty()).collect::<Vec<String>>());
+    if let Some(path) = from_tile.filter(|path| !path.is_empty()) {
+        return Ok(path);
+    }
+
+    get_breadcrumbs(document)
+}
+
+/// Breadcrumbs of a page without the ones above every page, e.g. ["Zuivel, eieren, boter", "Melk"]
+pub fn get_breadcrumbs(document: &Html) -> Result<Vec<String>> {
+    let breadcrumb_selectors = build_selectors(&["nav.breadcrumbs li", "ol.jum-breadcrumbs li", "[data-testid='breadcrumb'] li"], SRC)?;
+    let breadcrumbs = breadcrumb_selectors
+        .iter()
+        .map(|selector| {
+            document
+                .select(selector)
+                .map(|crumb| crumb.text().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" "))
+                .filter(|crumb| !crumb.is_empty() && !ROOT_BREADCRUMBS.contains(&crumb.to_lowercase().as_str()))
+                .collect::<Vec<String>>()
+        })
+        .find(|path| !path.is_empty())
+        .unwrap_or_default();
+    Ok(breadcrumbs)
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::{Html, Selector};
+    use scrape_core::{Money, Promotion};
+    use super::{get_price, get_offer, sku_from_url, product_key};
+
+    fn tile(html: &str) -> Html {
+        Html::parse_fragment(html)
+    }
+
+    fn price(whole: &str, fractional: &str) -> String {
+        format!(r#"<div class="jum-price"><div class="current-price"><span class="whole">{}</span><sup class="fractional">{}</sup></div></div>"#, whole, fractional)
+    }
+
+    #[test]
+    fn test_price_without_offer() {
+        let document = tile(&format!("<article>{}</article>", price("2", "49")));
+        let article = document.select(&Selector::parse("article").unwrap()).next().unwrap();
+
+        assert_eq!(get_price(article).unwrap(), Money::parse("2,49").unwrap());
+        assert!(get_offer(article).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_price_on_offer_is_regular_price() {
+        let document = tile(&format!(r#"<article>{}<span class="old-price">2,49</span></article>"#, price("1", "99")));
+        let article = document.select(&Selector::parse("article").unwrap()).next().unwrap();
+
+        assert_eq!(get_price(article).unwrap(), Money::parse("2,49").unwrap());
+        let offer = get_offer(article).unwrap().unwrap();
+        assert_eq!(offer.promotion, Promotion::FixedPrice { price: Money::parse("1,99").unwrap() });
+    }
+
+    #[test]
+    fn test_sku_from_url() {
+        assert_eq!(sku_from_url("https://www.jumbo.com/producten/halfvolle-melk-1l-67649PAK"), Some("67649PAK".to_owned()));
+        assert_eq!(sku_from_url("https://www.jumbo.com/producten/bananen-123456STK/?utm=x#top"), Some("123456STK".to_owned()));
+        assert_eq!(sku_from_url("https://www.jumbo.com/producten/halfvolle-melk"), None);
+        assert_eq!(sku_from_url("https://www.jumbo.com/producten/melk-PAK67649"), None);
+    }
+
+    #[test]
+    fn test_product_key_falls_back_on_normalized_url() {
+        assert_eq!(product_key("https://www.jumbo.com/producten/halfvolle-melk-1l-67649PAK?x=1"), "67649PAK");
+        assert_eq!(product_key("https://www.jumbo.com/producten/halfvolle-melk/?x=1#top"), "https://www.jumbo.com/producten/halfvolle-melk");
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeSet;
+use scrape_core::{InDbProduct, Quantity};
+use scrape_core::text::normalize_text;
+
+// Words that don't say anything about what the product is
+const STOP_WORDS: [&str; 12] = ["de", "het", "een", "en", "met", "van", "voor", "per", "stuk", "stuks", "x", "ca"];
+// Product names with a size at the end have at most this many words for it, e.g. "6 x 330 ml"
+const MAX_QUANTITY_WORDS: usize = 4;
+
+/// What the matcher compares of a single product
+#[derive(Debug, Clone)]
+pub struct Features {
+    pub store: String,
+    /// Name words, without house brands, the size and stop words
+    pub tokens: BTreeSet<String>,
+    pub brand: Option<String>,
+    /// Words of the brand, these are left out when comparing products of the same brand
+    pub brand_tokens: BTreeSet<String>,
+    /// The brand is the store's own brand
+    pub house_brand: bool,
+    pub quantity: Option<Quantity>,
+    pub ean: Option<String>,
+}
+
+impl Features {
+    pub fn from_product(product: &InDbProduct, house_brands: &[String]) -> Self {
+        let name = normalize_text(&product.info.name);
+        let brand = product.info.brand.as_deref().map(normalize_text).filter(|b| !b.is_empty());
+        let house_brand = house_brands.iter().any(|house| {
+            brand.as_deref() == Some(house.as_str()) || name == *house || name.starts_with(&format!("{} ", house))
+        });
+
+        let quantity = product.info.quantity.as_deref()
+            .and_then(|q| Quantity::parse(q).ok())
+            .or_else(|| quantity_from_name(&name));
+
+        let mut ignored: BTreeSet<&str> = STOP_WORDS.iter().copied().collect();
+        ignored.extend(house_brands.iter().flat_map(|b| b.split_whitespace()));
+        let tokens = name
+            .split_whitespace()
+            .filter(|t| !ignored.contains(t) && !is_quantity_word(t))
+            .map(str::to_owned)
+            .collect();
+
+        Features {
+            store: product.store.clone(),
+            tokens,
+            brand_tokens: brand.iter().flat_map(|b| b.split_whitespace()).map(str::to_owned).collect(),
+            brand,
+            house_brand,
+            quantity,
+            ean: product.info.ean.as_deref().map(|e| e.trim_start_matches('0').to_owned()).filter(|e| !e.is_empty()),
+        }
+    }
+}
+
+// Take the size from the end of a name like "coca cola regular 1,5 l"
+fn quantity_from_name(name: &str) -> Option<Quantity> {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    (1..=MAX_QUANTITY_WORDS.min(words.len()))
+        .rev()
+        .find_map(|n| Quantity::parse(&words[words.len() - n..].join(" ")).ok())
+}
+
+fn is_quantity_word(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit()) || Quantity::parse(&format!("1 {}", word)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use scrape_core::{InDbProduct, ProductInfo, Money, Unit};
+    use super::Features;
+
+    #[test]
+    fn test_features() {
+        let info = ProductInfo::new("Jumbo Cola Regular 1,5 L".to_owned(), Money::from_cents(99), "url".to_owned());
+        let features = Features::from_product(&InDbProduct::new("Jumbo".to_owned(), info), &["jumbo".to_owned()]);
+
+        assert!(features.house_brand);
+        assert_eq!(features.tokens.iter().map(String::as_str).collect::<Vec<&str>>(), vec!["cola", "regular"]);
+        assert_eq!(features.quantity.map(|q| (q.total(), q.unit)), Some((1500, Unit::Millilitre)));
+    }
+}
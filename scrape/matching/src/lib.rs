@@ -0,0 +1,4 @@
+mod features;
+mod matcher;
+
+pub use matcher::{ProductMatcher, MatchConfig, MatchResult, ProductMatch};
@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use log::info;
+use scrape_core::{InDbProduct, InDbProductMatch, MatchRelation};
+use scrape_core::text::normalize_text;
+use super::features::Features;
+
+// How much the name, brand and size weigh in the confidence of a match
+const NAME_WEIGHT: f64 = 0.6;
+const BRAND_WEIGHT: f64 = 0.25;
+const SIZE_WEIGHT: f64 = 0.15;
+// Score for the brand or size when it's unknown for one of the products
+const UNKNOWN_SCORE: f64 = 0.5;
+
+/// Thresholds used when matching products
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    /// Minimal confidence to consider two products identical
+    pub identical_threshold: f64,
+    /// Minimal name similarity to consider a product an alternative
+    pub alternative_threshold: f64,
+    /// Number of alternatives kept per product for each other store
+    pub max_alternatives: usize,
+    /// Words that occur in more products than this are too common to find candidates with
+    pub max_token_frequency: usize,
+    /// Normalized names of the stores' own brands, products of those brands are never identical
+    pub house_brands: Vec<String>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            identical_threshold: 0.85,
+            alternative_threshold: 0.5,
+            max_alternatives: 3,
+            max_token_frequency: 500,
+            house_brands: vec!["jumbo".to_owned(), "ah".to_owned(), "albert heijn".to_owned()],
+        }
+    }
+}
+
+/// A relation between two products, referring to them by their index in the matched slice
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductMatch {
+    pub left: usize,
+    pub right: usize,
+    pub relation: MatchRelation,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct MatchResult {
+    pub matches: Vec<ProductMatch>,
+    /// Groups of identical products, by index, each group has products from at least two stores
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl MatchResult {
+    pub fn identical(&self) -> impl Iterator<Item = &ProductMatch> {
+        self.matches.iter().filter(|m| m.relation == MatchRelation::Identical)
+    }
+
+    pub fn alternatives(&self) -> impl Iterator<Item = &ProductMatch> {
+        self.matches.iter().filter(|m| m.relation == MatchRelation::ComparableAlternative)
+    }
+
+    /// Convert the matches to rows for the database, `products` has to be the slice that was matched
+    pub fn to_db(&self, products: &[InDbProduct]) -> Vec<InDbProductMatch> {
+        let mut group_keys: HashMap<usize, String> = HashMap::new();
+        for group in self.groups.iter() {
+            let key = group.iter().map(|i| product_ref(&products[*i])).min().unwrap_or_default();
+            group_keys.extend(group.iter().map(|i| (*i, key.clone())));
+        }
+
+        self.matches.iter().map(|m| InDbProductMatch {
+            store: products[m.left].store.clone(),
+            product_key: products[m.left].info.key.clone(),
+            match_store: products[m.right].store.clone(),
+            match_product_key: products[m.right].info.key.clone(),
+            relation: m.relation,
+            confidence: m.confidence,
+            group_key: match m.relation {
+                MatchRelation::Identical => group_keys.get(&m.left).cloned(),
+                MatchRelation::ComparableAlternative => None,
+            },
+        }).collect()
+    }
+}
+
+/// Finds identical products and comparable alternatives across stores
+#[derive(Debug, Default)]
+pub struct ProductMatcher {
+    config: MatchConfig,
+}
+
+impl ProductMatcher {
+    pub fn new() -> Self {
+        ProductMatcher::default()
+    }
+
+    pub fn with_config(config: MatchConfig) -> Self {
+        let house_brands = config.house_brands.iter().map(|b| normalize_text(b)).collect();
+        ProductMatcher { config: MatchConfig { house_brands, ..config } }
+    }
+
+    pub fn match_products(&self, products: &[InDbProduct]) -> MatchResult {
+        let features: Vec<Features> = products
+            .iter()
+            .map(|p| Features::from_product(p, &self.config.house_brands))
+            .collect();
+
+        let mut identical: Vec<ProductMatch> = Vec::new();
+        // Best alternatives for each product and other store, by confidence
+        let mut alternatives: BTreeMap<(usize, String), Vec<ProductMatch>> = BTreeMap::new();
+
+        for (left, candidates) in self.candidates(&features) {
+            for right in candidates {
+                let Some(found) = self.score(left, right, &features) else {
+                    continue;
+                };
+                match found.relation {
+                    MatchRelation::Identical => identical.push(found),
+                    MatchRelation::ComparableAlternative => {
+                        alternatives.entry((left, features[right].store.clone())).or_default().push(found.clone());
+                        alternatives.entry((right, features[left].store.clone())).or_default().push(ProductMatch {
+                            left: right,
+                            right: left,
+                            ..found
+                        });
+                    },
+                }
+            }
+        }
+
+        let groups = cluster(&identical, products.len());
+        let mut matches = identical;
+        let mut kept_alternatives: HashSet<(usize, usize)> = HashSet::new();
+        for (_, mut found) in alternatives {
+            found.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+            for m in found.into_iter().take(self.config.max_alternatives) {
+                // Store each pair once, from the side of the product with the lowest index
+                let pair = (m.left.min(m.right), m.left.max(m.right));
+                if kept_alternatives.insert(pair) {
+                    matches.push(ProductMatch { left: pair.0, right: pair.1, ..m });
+                }
+            }
+        }
+
+        let result = MatchResult { matches, groups };
+        info!(
+            "Matched {} products: {} identical, {} alternatives, {} groups",
+            products.len(),
+            result.identical().count(),
+            result.alternatives().count(),
+            result.groups.len(),
+        );
+        result
+    }
+
+    // Products from other stores that share at least one uncommon word, only pairs with left < right
+    fn candidates(&self, features: &[Features]) -> Vec<(usize, Vec<usize>)> {
+        let mut index: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, f) in features.iter().enumerate() {
+            for token in f.tokens.iter() {
+                index.entry(token.as_str()).or_default().push(i);
+            }
+        }
+        let mut by_ean: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, f) in features.iter().enumerate() {
+            if let Some(ean) = &f.ean {
+                by_ean.entry(ean.as_str()).or_default().push(i);
+            }
+        }
+
+        features.iter().enumerate().map(|(left, f)| {
+            let by_token = f.tokens
+                .iter()
+                .filter_map(|t| index.get(t.as_str()))
+                .filter(|products| products.len() <= self.config.max_token_frequency);
+            let same_ean = f.ean.as_deref().and_then(|e| by_ean.get(e));
+
+            let mut candidates: Vec<usize> = by_token
+                .chain(same_ean)
+                .flatten()
+                .copied()
+                .filter(|right| *right > left && features[*right].store != f.store)
+                .collect();
+            candidates.sort_unstable();
+            candidates.dedup();
+            (left, candidates)
+        }).collect()
+    }
+
+    fn score(&self, left: usize, right: usize, features: &[Features]) -> Option<ProductMatch> {
+        let (a, b) = (&features[left], &features[right]);
+        let found = |relation, confidence| Some(ProductMatch { left, right, relation, confidence });
+
+        if let (Some(ean_a), Some(ean_b)) = (&a.ean, &b.ean) {
+            if ean_a == ean_b {
+                return found(MatchRelation::Identical, 1.0);
+            }
+        }
+
+        let size_score = match (a.quantity, b.quantity) {
+            (Some(qa), Some(qb)) if qa.unit != qb.unit => return None,
+            (Some(qa), Some(qb)) if qa.total() == qb.total() => 1.0,
+            (Some(_), Some(_)) => 0.0,
+            _ => UNKNOWN_SCORE,
+        };
+        let brand_score = match (&a.brand, &b.brand) {
+            (Some(ba), Some(bb)) if ba == bb => 1.0,
+            (Some(_), Some(_)) => 0.0,
+            _ => UNKNOWN_SCORE,
+        };
+        // The brand is scored separately, so it's left out of the name for identical products
+        let without_brand = |f: &Features| f.tokens.difference(&f.brand_tokens).cloned().collect::<BTreeSet<String>>();
+        let name_score = jaccard(&without_brand(a), &without_brand(b));
+        let confidence = NAME_WEIGHT * name_score + BRAND_WEIGHT * brand_score + SIZE_WEIGHT * size_score;
+
+        let can_be_identical = !a.house_brand && !b.house_brand && brand_score > 0.0 && size_score > 0.0;
+        if can_be_identical && confidence >= self.config.identical_threshold {
+            return found(MatchRelation::Identical, confidence);
+        }
+        let name_score = jaccard(&a.tokens, &b.tokens);
+        if name_score >= self.config.alternative_threshold {
+            // Brands don't matter for alternatives
+            let confidence = (NAME_WEIGHT * name_score + SIZE_WEIGHT * size_score) / (NAME_WEIGHT + SIZE_WEIGHT);
+            return found(MatchRelation::ComparableAlternative, confidence);
+        }
+        None
+    }
+}
+
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let union = a.union(b).count();
+    match union {
+        0 => 0.0,
+        _ => a.intersection(b).count() as f64 / union as f64,
+    }
+}
+
+fn product_ref(product: &InDbProduct) -> String {
+    format!("{}:{}", product.store, product.info.key)
+}
+
+// Group products that are connected through identical matches
+fn cluster(identical: &[ProductMatch], nr_products: usize) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..nr_products).collect();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    for m in identical {
+        let (a, b) = (root(&mut parents, m.left), root(&mut parents, m.right));
+        if a != b {
+            parents[a.max(b)] = a.min(b);
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..nr_products {
+        let r = root(&mut parents, i);
+        groups.entry(r).or_default().push(i);
+    }
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use scrape_core::{InDbProduct, MatchRelation, Money, ProductInfo};
+    use super::ProductMatcher;
+
+    fn product(store: &str, name: &str, brand: Option<&str>, quantity: Option<&str>) -> InDbProduct {
+        let mut info = ProductInfo::new(name.to_owned(), Money::from_cents(100), format!("https://www.example.com/{}", name));
+        info.brand = brand.map(str::to_owned);
+        info.quantity = quantity.map(str::to_owned);
+        InDbProduct::new(store.to_owned(), info)
+    }
+
+    #[test]
+    fn test_identical() {
+        let products = vec![
+            product("Jumbo", "Coca-Cola Regular 1,5 L", Some("Coca-Cola"), None),
+            product("Albert Heijn", "Coca-Cola Regular", Some("Coca-Cola"), Some("1,5 l")),
+            product("Albert Heijn", "Coca-Cola Regular", Some("Coca-Cola"), Some("6 x 330 ml")),
+        ];
+        let result = ProductMatcher::new().match_products(&products);
+
+        let identical: Vec<(usize, usize)> = result.identical().map(|m| (m.left, m.right)).collect();
+        assert_eq!(identical, vec![(0, 1)]);
+        assert_eq!(result.groups, vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_house_brands_are_alternatives() {
+        let products = vec![
+            product("Jumbo", "Jumbo Cola 1,5 L", None, None),
+            product("Albert Heijn", "AH Cola", None, Some("1,5 l")),
+            product("Albert Heijn", "Coca-Cola", Some("Coca-Cola"), Some("1,5 l")),
+            product("Albert Heijn", "AH Halfvolle melk", None, Some("1 l")),
+        ];
+        let result = ProductMatcher::new().match_products(&products);
+
+        assert_eq!(result.identical().count(), 0);
+        let alternatives: Vec<(usize, usize)> = result.alternatives().map(|m| (m.left, m.right)).collect();
+        assert!(alternatives.contains(&(0, 1)));
+        assert!(!alternatives.iter().any(|(l, r)| *l == 3 || *r == 3));
+    }
+
+    #[test]
+    fn test_ean_match() {
+        let mut left = product("Jumbo", "Pindakaas", None, Some("350 g"));
+        let mut right = product("Albert Heijn", "Calvé pindakaas", Some("Calvé"), Some("350 g"));
+        left.info.ean = Some("08710908500125".to_owned());
+        right.info.ean = Some("8710908500125".to_owned());
+        let products = vec![left, right];
+
+        let result = ProductMatcher::new().match_products(&products);
+        let rows = result.to_db(&products);
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].relation, MatchRelation::Identical);
+        assert_eq!(rows[0].confidence, 1.0);
+        assert!(rows[0].group_key.is_some());
+    }
+}
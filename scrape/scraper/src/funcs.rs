@@ -1,82 +1,290 @@
-use log::info;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use log::{info, warn};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use sql::{tables, self, PgPool};
 use jumbo::JumboScraper;
 use albert_heijn::AlbertHeijnScraper;
+use matching::ProductMatcher;
+use serde::Serialize;
 use scrape_core::{
-    InDbProduct, 
-    InDbError, 
-    ScrapeConfig, 
-    ReqwestHtmlLoader, 
-    RequestClient, 
-    request_header, 
-    RequestClientBuilder,
-    RandomDelayRateLimiter,
+    InDbProduct,
+    Taxonomy,
+    Validator,
+    AnomalyDetector,
+    StoreDefinition,
+    DeclarativeScraper,
+    InDbError,
+    ScrapeConfig,
+    ConfigBuilder,
+    OutputSink,
+    ReqwestHtmlLoader,
     AsyncExecutor,
     Scraper,
-    SimpleRateLimiter,
+    ResultCollector,
+    ProductInfo,
+    DetailPagesConfig,
+    Discovery,
+    PreviousProduct,
 };
 
+const JUMBO: &str = "jumbo";
+const ALBERT_HEIJN: &str = "albert_heijn";
+pub const STORES: [&str; 2] = [JUMBO, ALBERT_HEIJN];
+
+/// Configuration used when the config file and environment don't override it
+pub fn default_config() -> ScrapeConfig {
+    ConfigBuilder::new()
+        .max_concurrent_requests(50)
+        .store(JUMBO, jumbo::default_config())
+        .store(ALBERT_HEIJN, albert_heijn::default_config())
+        .sink(OutputSink::Database)
+        .build()
+}
+
 pub async fn scrape(config: ScrapeConfig) -> Result<()> {
     info!("Starting scrape...");
-    info!("Setting up SqlPool connection");
-    let pool = sql::connect().await?;
-    info!("Using configuration: {:?}", &config);
+    let pool = match config.sinks.contains(&OutputSink::Database) {
+        true => {
+            info!("Setting up SqlPool connection");
+            Some(sql::connect().await?)
+        },
+        false => None,
+    };
     info!("Clearing tables");
     // tables::truncate_all(&pool).await?;  TODO: Can't truncate table with foreign key constraint, products - shopping lists relation
     info!("Scraping...");
-    run_scrapers(&config, &pool).await?;
+    run_scrapers(&config, pool.as_ref()).await?;
     info!("All done");
-    pool.close().await;
+    if let Some(pool) = pool {
+        pool.close().await;
+    }
     Ok(())
 }
 
-async fn run_scraper<R: AsyncExecutor + Send + Sync>(scraper: impl Scraper, rate_limiter: &R, scraper_name: &str) -> (Vec<InDbProduct>, Vec<InDbError>) {
+async fn run_scraper<R: AsyncExecutor + Send + Sync>(scraper: &impl Scraper, rate_limiter: &R, scraper_name: &str) -> ResultCollector<ProductInfo> {
     let results = scraper.scrape(rate_limiter).await;
-    results.map_extract(
-       |p| InDbProduct::new(scraper_name.to_string(), p),
-       |e| InDbError::new(scraper_name.to_string(), e.to_string())
-    )
+    info!(target: scraper_name, "Done, {}", results.summary());
+    results
 }
 
-async fn run_scrapers(cfg: &ScrapeConfig, pool: &PgPool) -> Result<()> {
-    let mut db_products;
-    let mut errors;
+async fn run_scrapers(cfg: &ScrapeConfig, pool: Option<&PgPool>) -> Result<()> {
+    let taxonomy = cfg.load_taxonomy()?;
+    let detector = AnomalyDetector::new(cfg.anomalies.clone());
+
+    let jumbo_cfg = cfg.store(JUMBO);
+    if jumbo_cfg.enabled && jumbo_cfg.definition.is_none() {
+        let client = jumbo_cfg.build_client()?;
+        let connector = ReqwestHtmlLoader::new(&client)
+            .with_retry_policy(jumbo_cfg.retry)
+            .with_user_agent(jumbo_cfg.user_agent.clone());
+        let discovery = jumbo_cfg.discovery_of(JUMBO, &jumbo::DISCOVERY)?;
+        // Incremental runs only visit the sitemap entries modified since the last run, the first run visits all
+        let modified_since = match (pool, jumbo_cfg.incremental && discovery == Discovery::Sitemap) {
+            (Some(pool), true) => tables::scrape_runs::last_finished(jumbo::SRC, pool).await?,
+            _ => None,
+        };
+        let scraper = JumboScraper::new(&connector)
+            .with_base_url(jumbo_cfg.base_url_or(jumbo::BASE_URL))
+            .with_discovery(discovery)
+            .with_modified_since(modified_since);
+        let rate_limiter = jumbo_cfg.rate_limiter(cfg.max_concurrent_requests);
+
+        let validator = Validator::new(cfg.validation.clone(), jumbo_cfg.base_url_or(jumbo::BASE_URL));
+
+        let started_at = Utc::now();
+        let mut results = run_scraper(&scraper, &rate_limiter, jumbo::SRC).await;
+        // Products found through the sitemap come from their own page and already have the details
+        if jumbo_cfg.details.enabled && discovery != Discovery::Sitemap {
+            let previous = previous_products(&jumbo_cfg.details, jumbo::SRC, pool).await?;
+            // Skipped products keep the breadcrumb category of their product page instead of the listing's
+            for product in results.successes.iter_mut() {
+                if let Some(category) = unchanged(&previous, product).and_then(|p| p.category.as_ref()) {
+                    product.category = category.split(" > ").map(str::to_owned).collect();
+                }
+            }
+            let detail_rate_limiter = jumbo_cfg.details.rate_limiter();
+            results = scraper.scrape_details(results, &detail_rate_limiter, |product| {
+                unchanged(&previous, product).is_none()
+            }).await;
+        }
+        let detector = AnomalyDetector::new(cfg.anomalies.clone()).with_incremental(modified_since.is_some());
+        write_results(results, started_at, jumbo::SRC, &validator, &taxonomy, &detector, &cfg.sinks, pool).await?;
+    } else {
+        info!("Skipping {}, disabled in config", jumbo::SRC);
+    }
+
+    let ah_cfg = cfg.store(ALBERT_HEIJN);
+    if ah_cfg.enabled && ah_cfg.definition.is_none() {
+        let client = ah_cfg.build_client()?;
+        let connector = ReqwestHtmlLoader::new(&client)
+            .with_retry_policy(ah_cfg.retry)
+            .with_user_agent(ah_cfg.user_agent.clone());
+        let scraper = AlbertHeijnScraper::new(&connector)
+            .with_base_url(ah_cfg.base_url_or(albert_heijn::BASE_URL))
+            .with_discovery(ah_cfg.discovery_of(ALBERT_HEIJN, &albert_heijn::DISCOVERY)?);
+        let rate_limiter = ah_cfg.rate_limiter(cfg.max_concurrent_requests);
+
+        let validator = Validator::new(cfg.validation.clone(), ah_cfg.base_url_or(albert_heijn::BASE_URL));
+
+        let started_at = Utc::now();
+        let results = run_scraper(&scraper, &rate_limiter, albert_heijn::SRC).await;
+        write_results(results, started_at, albert_heijn::SRC, &validator, &taxonomy, &detector, &cfg.sinks, pool).await?;
+    } else {
+        info!("Skipping {}, disabled in config", albert_heijn::SRC);
+    }
+
+    // Stores described by a definition file instead of a store crate
+    for (name, store_cfg) in cfg.stores.iter().filter(|(_, store)| store.enabled) {
+        let Some(path) = &store_cfg.definition else {
+            continue;
+        };
+        let mut definition = StoreDefinition::from_file(path)?;
+        if let Some(base_url) = &store_cfg.base_url {
+            definition.base_url = base_url.clone();
+        }
+        let client = store_cfg.build_client()?;
+        let connector = ReqwestHtmlLoader::new(&client)
+            .with_retry_policy(store_cfg.retry)
+            .with_user_agent(store_cfg.user_agent.clone());
+        let rate_limiter = store_cfg.rate_limiter(cfg.max_concurrent_requests);
+        let validator = Validator::new(cfg.validation.clone(), &definition.base_url);
+        let scraper = DeclarativeScraper::new(&connector, definition)?;
+        let store_name = scraper.name().to_owned();
+        info!("Scraping {} from definition {} ({})", store_name, path, name);
+
+        let started_at = Utc::now();
+        let results = run_scraper(&scraper, &rate_limiter, &store_name).await;
+        write_results(results, started_at, &store_name, &validator, &taxonomy, &detector, &cfg.sinks, pool).await?;
+    }
+
+    if let Some(pool) = pool {
+        match_products(pool).await?;
+    }
+    Ok(())
+}
 
-    let rate_limiter = SimpleRateLimiter::new(cfg.max_concurrent_requests);
-    let jumbo_client = RequestClient::new();
-    let jumbo_connector = ReqwestHtmlLoader::new(&jumbo_client);
+/// Products of the previous run by product key, to only visit the pages of new and changed products.
+/// `None` when all pages should be visited
+async fn previous_products(details: &DetailPagesConfig, store: &str, pool: Option<&PgPool>) -> Result<Option<HashMap<String, PreviousProduct>>> {
+    let Some(pool) = pool.filter(|_| details.only_changed) else {
+        return Ok(None);
+    };
+    let previous = tables::products::select_previous(store, pool).await?;
+    Ok(Some(previous.into_iter().map(|p| (p.key.clone(), p)).collect()))
+}
 
-    (db_products, errors) = run_scraper(
-        JumboScraper::new(&jumbo_connector), 
-        &rate_limiter, 
-        "Jumbo")
-        .await;
+/// The stored product when its price didn't change since the previous run
+fn unchanged<'a>(previous: &'a Option<HashMap<String, PreviousProduct>>, product: &ProductInfo) -> Option<&'a PreviousProduct> {
+    previous.as_ref()?.get(&product.key).filter(|p| p.price == product.price)
+}
 
-    info!("Done, got {} errors and {} successes", errors.len(), db_products.len());
-    info!("Writing new scrapes to db...");
-    tables::products::insert(&db_products, pool).await?;
-    tables::scrape_errors::insert(&errors, pool).await?;
+/// Write the results to the configured sinks
+#[allow(clippy::too_many_arguments)]
+async fn write_results(
+    results: ResultCollector<ProductInfo>,
+    started_at: DateTime<Utc>,
+    scraper_name: &str,
+    validator: &Validator,
+    taxonomy: &Taxonomy,
+    detector: &AnomalyDetector,
+    sinks: &[OutputSink],
+    pool: Option<&PgPool>,
+) -> Result<()> {
+    let dump_dirs: Vec<&Path> = sinks
+        .iter()
+        .filter_map(|sink| match sink {
+            OutputSink::JsonFile { dir } => Some(dir.as_path()),
+            _ => None,
+        })
+        .collect();
+    for dir in dump_dirs.iter() {
+        dump_json(&results, &file_stem(scraper_name), scraper_name, dir);
+    }
 
-    let delay_rate_limiter = RandomDelayRateLimiter::new(
-        cfg.max_concurrent_requests, 100, 5000,
+    let (db_products, mut errors) = results.map_extract(
+       |p| InDbProduct::new(scraper_name.to_string(), p),
+       |e| InDbError::new(scraper_name.to_string(), e.to_string())
     );
-    let mut headers = request_header::HeaderMap::new();
-    headers.insert(request_header::CONNECTION, request_header::HeaderValue::from_static("keep-alive"));
-    headers.insert(request_header::HOST, request_header::HeaderValue::from_static("www.ah.nl"));
-    let ah_client = RequestClientBuilder::new().default_headers(headers).gzip(true).build()?;
-    let ah_connector = ReqwestHtmlLoader::new(&ah_client);
-
-    (db_products, errors) = run_scraper(
-        AlbertHeijnScraper::new(&ah_connector), 
-        &delay_rate_limiter, 
-        "Albert Heijn")
-        .await;
-    
-    info!("Done, got {} errors and {} successes", errors.len(), db_products.len());
-    info!("Writing new scrapes to db...");
-    tables::products::insert(&db_products, pool).await?;
-    tables::scrape_errors::insert(&errors, pool).await?;
 
+    // Products that fail a rule with severity error are stored as scrape errors instead
+    let (mut db_products, validation) = validator.validate(db_products);
+    match validation.issues.is_empty() {
+        true => info!(target: scraper_name, "Validation: {}", validation),
+        false => warn!(target: scraper_name, "Validation: {}", validation),
+    };
+    for issue in validation.errors() {
+        warn!(target: scraper_name, "Rejected product, {}", issue);
+        errors.push(InDbError::new(scraper_name.to_string(), format!("Validation {}", issue)));
+    }
+    for dir in dump_dirs.iter() {
+        dump_json(&validation, &format!("{}_validation", file_stem(scraper_name)), scraper_name, dir);
+    }
+
+    let report = taxonomy.assign(&mut db_products);
+    match report.unmapped.is_empty() {
+        true => info!(target: scraper_name, "Taxonomy: {}", report),
+        false => warn!(target: scraper_name, "Taxonomy: {}", report),
+    };
+    for dir in dump_dirs.iter() {
+        dump_json(&report, &format!("{}_taxonomy", file_stem(scraper_name)), scraper_name, dir);
+    }
+
+    if let Some(pool) = pool {
+        // Products involved in an anomaly keep the values of the previous run until the anomaly is acknowledged
+        let previous = tables::products::select_previous(scraper_name, pool).await?;
+        let acknowledged = tables::scrape_anomalies::select_acknowledged(scraper_name, pool).await?;
+        let (published, anomalies) = detector.detect(scraper_name, &previous, db_products, &acknowledged);
+        match anomalies.is_clean() {
+            true => info!(target: scraper_name, "Anomalies: {}", anomalies),
+            false => warn!(target: scraper_name, "Anomalies: {}", anomalies),
+        };
+        for dir in dump_dirs.iter() {
+            dump_json(&anomalies, &format!("{}_anomalies", file_stem(scraper_name)), scraper_name, dir);
+        }
+
+        info!("Writing new scrapes to db...");
+        tables::scrape_errors::insert(&errors, pool).await?;
+        tables::scrape_anomalies::insert(&anomalies.anomalies, pool).await?;
+        // A run held back as a whole isn't finished, the next run compares with the last complete one
+        if !anomalies.is_catalogue_drop() {
+            // The start of the scrape, not of the write, so pages changed while scraping are visited next run
+            let run = tables::scrape_runs::start(scraper_name, started_at, anomalies.incremental, pool).await?;
+            tables::products::insert(&published, pool).await?;
+            tables::products::touch(scraper_name, &anomalies.held_back_keys(), pool).await?;
+            // Only what this run published, other acknowledged anomalies wait for a run that has their products
+            tables::scrape_anomalies::release(scraper_name, &anomalies.released, pool).await?;
+            tables::scrape_runs::finish(run, pool).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn match_products(pool: &PgPool) -> Result<()> {
+    // Matches are recalculated from scratch after every scrape, from every product in the database so
+    // stores that are disabled or failed this run and products that were held back keep their matches
+    info!("Matching products across stores...");
+    let products = tables::products::select_all(pool).await?;
+    let matches = ProductMatcher::new().match_products(&products).to_db(&products);
+    tables::product_matches::replace(&matches, pool).await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+fn file_stem(scraper_name: &str) -> String {
+    scraper_name.to_lowercase().replace(' ', "_")
+}
+
+fn dump_json<T: Serialize>(value: &T, name: &str, scraper_name: &str, dir: &Path) {
+    // Write the value to a JSON file, failing to do so shouldn't stop the scraper
+    let path = dir.join(format!("{}.json", name));
+    let written = File::create(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| serde_json::to_writer_pretty(file, value).map_err(anyhow::Error::from));
+
+    match written {
+        Ok(_) => info!(target: scraper_name, "Dumped to {}", path.display()),
+        Err(e) => warn!(target: scraper_name, "Failed to dump to {}: {}", path.display(), e),
+    };
+}
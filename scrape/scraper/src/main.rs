@@ -2,12 +2,12 @@ mod funcs;
 mod response;
 mod state;
 
-use log::{info, LevelFilter};
+use log::{info, error, LevelFilter};
 use serde_json::json;
 use simple_logger::SimpleLogger;
 use warp::{Filter, Rejection, Reply, http::Response};
-use funcs::scrape;
-use scrape_core::ConfigBuilder;
+use funcs::{scrape, default_config, STORES};
+use scrape_core::ScrapeConfig;
 use crate::state::StateKeeper;
 use crate::response::{ScraperState, ScraperStateResponse};
 
@@ -21,6 +21,15 @@ async fn main() {
         .map(|()| log::set_max_level(LevelFilter::Info))
         .expect("Failed to initialize logger");
 
+    let config = match ScrapeConfig::load_onto(default_config()).and_then(|c| c.validate_stores(&STORES).map(|_| c)) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            std::process::exit(1);
+        },
+    };
+    info!("Using configuration:\n{}", config.to_toml());
+
     let state_keeper = StateKeeper::default();
     let status_clone = state_keeper.clone();
     let func_clone = state_keeper.clone();
@@ -32,10 +41,10 @@ async fn main() {
     let scrape_route = 
         warp::post()
         .and(warp::path("scrape_func"))
-        .map(move || func_clone.clone())
-        .map(|state_clone| {
+        .map(move || (func_clone.clone(), config.clone()))
+        .map(|(state_clone, config)| {
             tokio::spawn(async move {
-                handler(state_clone).await
+                handler(state_clone, config).await
             });
             let scraper_state = ScraperStateResponse::new(ScraperState::Started);
             serde_json::to_string(&scraper_state).unwrap()
@@ -71,15 +80,12 @@ async fn get_handler_state(state_keeper: StateKeeper<ScraperState>) -> Result<im
     Ok(Response::builder().body(serde_json::to_string(&response).unwrap()))
 }
 
-async fn handler(state_keeper: StateKeeper<ScraperState>) {
+async fn handler(state_keeper: StateKeeper<ScraperState>, config: ScrapeConfig) {
     if state_keeper.get_state().await == ScraperState::Running {
         return
     }
 
     state_keeper.change_state(ScraperState::Running).await;
-    let config = ConfigBuilder::new()
-        .max_concurrent_requests(50)
-        .build();
 
     match scrape(config).await {
         Ok(_) => { state_keeper.change_state(ScraperState::Success).await },
@@ -4,43 +4,183 @@ use anyhow::Result;
 pub async fn truncate_all(pool: &PgPool) -> Result<()> {
     products::truncate(pool).await?;
     scrape_errors::truncate(pool).await?;
+    product_matches::truncate(pool).await?;
+    scrape_anomalies::truncate(pool).await?;
+    scrape_runs::truncate(pool).await?;
     Ok(())
 }
 
 pub mod products {
     use sqlx::PgPool;
-    use scrape_core::{InDbProduct, DbError};
+    use scrape_core::{InDbProduct, DbError, Offer, PreviousProduct, Money, ProductDetails, ProductInfo};
     use anyhow::Result;
 
-    pub async fn insert(products: &Vec<InDbProduct>, pool: &PgPool) -> Result<()>{
-        let query_str = r"
+    // Products that were scraped before are updated so their ID stays the same. Product page details
+    // aren't scraped every run, so they are only overwritten when the new row has them
+    const ON_KEY_CONFLICT: &str = r"
+            ON CONFLICT (store, productkey) DO UPDATE SET
+                name = EXCLUDED.name,
+                price = EXCLUDED.price,
+                currency = EXCLUDED.currency,
+                url = EXCLUDED.url,
+                searchstr = EXCLUDED.searchstr,
+                quantity = EXCLUDED.quantity,
+                unitprice = EXCLUDED.unitprice,
+                unitpriceunit = EXCLUDED.unitpriceunit,
+                brand = EXCLUDED.brand,
+                category = COALESCE(EXCLUDED.category, products.category),
+                imageurl = EXCLUDED.imageurl,
+                sku = EXCLUDED.sku,
+                ean = COALESCE(EXCLUDED.ean, products.ean),
+                available = EXCLUDED.available,
+                promotiontype = EXCLUDED.promotiontype,
+                promotionlabel = EXCLUDED.promotionlabel,
+                promotionprice = EXCLUDED.promotionprice,
+                promotionminquantity = EXCLUDED.promotionminquantity,
+                promotionvalidfrom = EXCLUDED.promotionvalidfrom,
+                promotionvaliduntil = EXCLUDED.promotionvaliduntil,
+                taxonomy = EXCLUDED.taxonomy,
+                description = COALESCE(EXCLUDED.description, products.description),
+                ingredients = COALESCE(EXCLUDED.ingredients, products.ingredients),
+                allergens = COALESCE(EXCLUDED.allergens, products.allergens),
+                nutrition = COALESCE(EXCLUDED.nutrition, products.nutrition),
+                lastseen = EXCLUDED.lastseen";
+
+    pub async fn insert(products: &[InDbProduct], pool: &PgPool) -> Result<()>{
+        // Prices are passed as cents to keep them exact. A product can show up more than once in
+        // a scrape, only one row per key is kept because a row can't be updated twice in one statement
+        let query_str = format!(r"
             INSERT INTO products(
                 name, 
                 price, 
+                currency,
                 store, 
                 url,
-                searchstr
+                searchstr,
+                quantity,
+                unitprice,
+                unitpriceunit,
+                brand,
+                category,
+                imageurl,
+                sku,
+                ean,
+                available,
+                promotiontype,
+                promotionlabel,
+                promotionprice,
+                promotionminquantity,
+                promotionvalidfrom,
+                promotionvaliduntil,
+                productkey,
+                taxonomy,
+                description,
+                ingredients,
+                allergens,
+                nutrition,
+                lastseen
             ) 
-            SELECT * FROM UNNEST(
+            SELECT DISTINCT ON (store, product_key)
+                name, price_cents::NUMERIC(12, 2) / 100, currency, store, url, searchstr,
+                quantity, unit_price_cents::NUMERIC(12, 2) / 100, unit_price_unit, brand, category,
+                image_url, sku, ean, available, promotion_type, promotion_label,
+                promotion_price_cents::NUMERIC(12, 2) / 100, promotion_min_quantity,
+                promotion_valid_from::DATE, promotion_valid_until::DATE, product_key, taxonomy,
+                description, ingredients, allergens, nutrition::JSONB, NOW()
+            FROM UNNEST(
                 $1::VARCHAR(255)[], 
-                $2::FLOAT4[], 
-                $3::VARCHAR(100)[], 
-                $4::VARCHAR(750)[],
-                $5::VARCHAR(255)[]
-            )";
+                $2::INT8[], 
+                $3::VARCHAR(3)[],
+                $4::VARCHAR(100)[], 
+                $5::VARCHAR(750)[],
+                $6::VARCHAR(255)[],
+                $7::VARCHAR(100)[],
+                $8::INT8[],
+                $9::VARCHAR(20)[],
+                $10::VARCHAR(255)[],
+                $11::VARCHAR(500)[],
+                $12::VARCHAR(750)[],
+                $13::VARCHAR(100)[],
+                $14::VARCHAR(14)[],
+                $15::BOOLEAN[],
+                $16::VARCHAR(30)[],
+                $17::VARCHAR(255)[],
+                $18::INT8[],
+                $19::INT4[],
+                $20::VARCHAR(10)[],
+                $21::VARCHAR(10)[],
+                $22::VARCHAR(750)[],
+                $23::VARCHAR(255)[],
+                $24::TEXT[],
+                $25::TEXT[],
+                $26::VARCHAR(500)[],
+                $27::TEXT[]
+            ) AS t(
+                name, price_cents, currency, store, url, searchstr, quantity, unit_price_cents,
+                unit_price_unit, brand, category, image_url, sku, ean, available, promotion_type,
+                promotion_label, promotion_price_cents, promotion_min_quantity, promotion_valid_from,
+                promotion_valid_until, product_key, taxonomy, description, ingredients, allergens, nutrition
+            ){}", ON_KEY_CONFLICT);
 
         let names: Vec<&str> = products.iter().map(|p| p.info.name.as_str()).collect();
-        let prices: Vec<f32> = products.iter().map(|p| p.info.price).collect();
+        let prices: Vec<i64> = products.iter().map(|p| p.info.price.cents()).collect();
+        let currencies: Vec<&str> = products.iter().map(|p| p.info.price.currency().code()).collect();
         let stores: Vec<&str> = products.iter().map(|p| p.store.as_str()).collect();
         let urls: Vec<&str> = products.iter().map(|p: &InDbProduct| p.info.url.as_str()).collect();
         let search_strings: Vec<String> = products.iter().map(|p| p.db_search_string()).collect();
+        let quantities: Vec<Option<&str>> = products.iter().map(|p| p.info.quantity.as_deref()).collect();
+        let unit_prices: Vec<Option<i64>> = products.iter().map(|p| p.info.unit_price.as_ref().map(|u| u.price.cents())).collect();
+        let unit_price_units: Vec<Option<&str>> = products.iter().map(|p| p.info.unit_price.as_ref().map(|u| u.unit.as_str())).collect();
+        let brands: Vec<Option<&str>> = products.iter().map(|p| p.info.brand.as_deref()).collect();
+        let categories: Vec<Option<String>> = products.iter().map(|p| p.info.category_path()).collect();
+        let image_urls: Vec<Option<&str>> = products.iter().map(|p| p.info.image_url.as_deref()).collect();
+        let skus: Vec<Option<&str>> = products.iter().map(|p| p.info.sku.as_deref()).collect();
+        let eans: Vec<Option<&str>> = products.iter().map(|p| p.info.ean.as_deref()).collect();
+        let available: Vec<Option<bool>> = products.iter().map(|p| p.info.availability.as_bool()).collect();
+        // Dates are passed as ISO 8601 strings
+        let offers: Vec<Option<&Offer>> = products.iter().map(|p| p.info.offer.as_ref()).collect();
+        let promotion_types: Vec<Option<&str>> = offers.iter().map(|o| o.map(|o| o.promotion.kind())).collect();
+        let promotion_labels: Vec<Option<&str>> = offers.iter().map(|o| o.map(|o| o.label.as_str())).collect();
+        let promotion_prices: Vec<Option<i64>> = products.iter().map(|p| p.info.promotion_price().map(|m| m.cents())).collect();
+        let promotion_min_quantities: Vec<Option<i32>> = offers.iter().map(|o| o.map(|o| o.promotion.min_quantity() as i32)).collect();
+        let promotion_valid_from: Vec<Option<String>> = offers.iter().map(|o| o.and_then(|o| o.valid_from).map(|d| d.to_string())).collect();
+        let promotion_valid_until: Vec<Option<String>> = offers.iter().map(|o| o.and_then(|o| o.valid_until).map(|d| d.to_string())).collect();
+        let keys: Vec<&str> = products.iter().map(|p| p.info.key.as_str()).collect();
+        let taxonomies: Vec<Option<&str>> = products.iter().map(|p| p.taxonomy.as_deref()).collect();
+        let details: Vec<Option<&ProductDetails>> = products.iter().map(|p| p.info.details.as_ref()).collect();
+        let descriptions: Vec<Option<&str>> = details.iter().map(|d| d.and_then(|d| d.description.as_deref())).collect();
+        let ingredients: Vec<Option<&str>> = details.iter().map(|d| d.and_then(|d| d.ingredients.as_deref())).collect();
+        let allergens: Vec<Option<String>> = details.iter().map(|d| d.and_then(allergens)).collect();
+        let nutrition: Vec<Option<String>> = details.iter().map(|d| d.and_then(|d| d.nutrition_json())).collect();
         
-        sqlx::query_as::<_, ()>(query_str)
+        sqlx::query_as::<_, ()>(&query_str)
             .bind(names)
             .bind(prices)
+            .bind(currencies)
             .bind(stores)
             .bind(urls)
             .bind(search_strings)
+            .bind(quantities)
+            .bind(unit_prices)
+            .bind(unit_price_units)
+            .bind(brands)
+            .bind(categories)
+            .bind(image_urls)
+            .bind(skus)
+            .bind(eans)
+            .bind(available)
+            .bind(promotion_types)
+            .bind(promotion_labels)
+            .bind(promotion_prices)
+            .bind(promotion_min_quantities)
+            .bind(promotion_valid_from)
+            .bind(promotion_valid_until)
+            .bind(keys)
+            .bind(taxonomies)
+            .bind(descriptions)
+            .bind(ingredients)
+            .bind(allergens)
+            .bind(nutrition)
             .fetch_all(pool)
             .await
             .map_err(|e| DbError::QueryFailed{ 
@@ -51,14 +191,50 @@ pub mod products {
     }
 
     pub async fn insert_one(product: &InDbProduct, pool: &PgPool) -> Result<()>{
-        let query_str = "INSERT INTO products (name, price, store, url, searchstr) VALUES ($1, $2, $3, &4)";
+        let query_str = format!(r"
+            INSERT INTO products (
+                name, price, currency, store, url, searchstr, quantity, unitprice,
+                unitpriceunit, brand, category, imageurl, sku, ean, available, promotiontype,
+                promotionlabel, promotionprice, promotionminquantity, promotionvalidfrom, promotionvaliduntil,
+                productkey, taxonomy, description, ingredients, allergens, nutrition, lastseen
+            ) 
+            VALUES (
+                $1, $2::INT8::NUMERIC(12, 2) / 100, $3, $4, $5, $6, $7, $8::INT8::NUMERIC(12, 2) / 100,
+                $9, $10, $11, $12, $13, $14, $15, $16, $17, $18::INT8::NUMERIC(12, 2) / 100, $19,
+                $20::VARCHAR::DATE, $21::VARCHAR::DATE, $22, $23, $24, $25, $26, $27::TEXT::JSONB, NOW()
+            ){}", ON_KEY_CONFLICT);
+        let offer = product.info.offer.as_ref();
+        let details = product.info.details.as_ref();
+        let info = &product.info;
     
-        sqlx::query(query_str)
-            .bind(&product.info.name)
-            .bind(&product.info.price)
+        sqlx::query(&query_str)
+            .bind(&info.name)
+            .bind(info.price.cents())
+            .bind(info.price.currency().code())
             .bind(&product.store)
-            .bind(&product.info.url)
-            .bind(&product.db_search_string())
+            .bind(&info.url)
+            .bind(product.db_search_string())
+            .bind(&info.quantity)
+            .bind(info.unit_price.as_ref().map(|u| u.price.cents()))
+            .bind(info.unit_price.as_ref().map(|u| u.unit.as_str()))
+            .bind(&info.brand)
+            .bind(info.category_path())
+            .bind(&info.image_url)
+            .bind(&info.sku)
+            .bind(&info.ean)
+            .bind(info.availability.as_bool())
+            .bind(offer.map(|o| o.promotion.kind()))
+            .bind(offer.map(|o| o.label.as_str()))
+            .bind(info.promotion_price().map(|m| m.cents()))
+            .bind(offer.map(|o| o.promotion.min_quantity() as i32))
+            .bind(offer.and_then(|o| o.valid_from).map(|d| d.to_string()))
+            .bind(offer.and_then(|o| o.valid_until).map(|d| d.to_string()))
+            .bind(&info.key)
+            .bind(&product.taxonomy)
+            .bind(details.and_then(|d| d.description.as_deref()))
+            .bind(details.and_then(|d| d.ingredients.as_deref()))
+            .bind(details.and_then(allergens))
+            .bind(details.and_then(|d| d.nutrition_json()))
             .execute(pool)
             .await
             .map_err(|e| DbError::QueryFailed{ 
@@ -68,6 +244,82 @@ pub mod products {
         Ok(())
     }   
     
+    // Allergens are stored as a single comma separated text
+    fn allergens(details: &ProductDetails) -> Option<String> {
+        match details.allergens.is_empty() {
+            true => None,
+            false => Some(details.allergens.join(", ")),
+        }
+    }
+
+    /// The products of `store` as written by its last finished run, products that weren't seen
+    /// since are delisted and left out
+    pub async fn select_previous(store: &str, pool: &PgPool) -> Result<Vec<PreviousProduct>> {
+        let query_str = r"
+            SELECT productkey, (price * 100)::INT8, category
+            FROM products
+            WHERE store = $1 AND price IS NOT NULL AND lastseen >= (
+                SELECT MAX(startedat) FROM scrape_runs WHERE store = $1 AND finishedat IS NOT NULL AND NOT incremental
+            )";
+
+        let rows = sqlx::query_as::<_, (String, i64, Option<String>)>(query_str)
+            .bind(store)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(rows
+            .into_iter()
+            .map(|(key, cents, category)| PreviousProduct { key, price: Money::from_cents(cents), category })
+            .collect())
+    }
+
+    /// Mark products as seen by the current run without changing them, for products that are held back
+    pub async fn touch(store: &str, keys: &[&str], pool: &PgPool) -> Result<()> {
+        let query_str = "UPDATE products SET lastseen = NOW() WHERE store = $1 AND productkey = ANY($2)";
+
+        sqlx::query(query_str)
+            .bind(store)
+            .bind(keys)
+            .execute(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(())
+    }
+
+    /// Every product in the table with the fields the matcher compares, so matches also cover
+    /// stores that weren't scraped in this run
+    pub async fn select_all(pool: &PgPool) -> Result<Vec<InDbProduct>> {
+        let query_str = r"
+            SELECT store, productkey, name, (price * 100)::INT8, url, quantity, brand, ean, taxonomy
+            FROM products
+            WHERE price IS NOT NULL";
+
+        let rows = sqlx::query_as::<_, (String, String, String, i64, String, Option<String>, Option<String>, Option<String>, Option<String>)>(query_str)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(rows
+            .into_iter()
+            .map(|(store, key, name, cents, url, quantity, brand, ean, taxonomy)| {
+                let mut info = ProductInfo::new(name, Money::from_cents(cents), url);
+                info.key = key;
+                info.quantity = quantity;
+                info.brand = brand;
+                info.ean = ean;
+                InDbProduct { store, info, taxonomy }
+            })
+            .collect())
+    }
+
     pub async fn truncate(pool: &PgPool) -> Result<()> {
         let query_str = "TRUNCATE TABLE products";
 
@@ -87,7 +339,7 @@ pub mod scrape_errors {
     use scrape_core::{InDbError, DbError};
     use anyhow::Result;
 
-    pub async fn insert(errors: &Vec<InDbError>, pool: &PgPool) -> Result<()>{
+    pub async fn insert(errors: &[InDbError], pool: &PgPool) -> Result<()>{
         let query_str = r"
             INSERT INTO scrape_errors(
                 scraper, 
@@ -116,6 +368,281 @@ pub mod scrape_errors {
     pub async fn truncate(pool: &PgPool) -> Result<()> {
         let query_str = "TRUNCATE TABLE scrape_errors";
 
+        sqlx::query(query_str)
+            .execute(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed { 
+                query: query_str.to_string(),
+                err: e.to_string(), 
+        })?;
+        Ok(())
+    }
+}
+
+pub mod product_matches {
+    use sqlx::{PgExecutor, PgPool};
+    use scrape_core::{InDbProductMatch, DbError};
+    use anyhow::Result;
+
+    pub async fn insert(matches: &[InDbProductMatch], pool: &PgPool) -> Result<()>{
+        insert_with(matches, pool).await
+    }
+
+    /// Replace all matches in one transaction, readers keep seeing the old matches until the new ones are in
+    pub async fn replace(matches: &[InDbProductMatch], pool: &PgPool) -> Result<()> {
+        let mut tx = pool.begin().await.map_err(|e| DbError::QueryFailed {
+            query: "BEGIN".to_string(),
+            err: e.to_string(),
+        })?;
+
+        // DELETE instead of TRUNCATE, which would lock out readers until the commit
+        let query_str = "DELETE FROM product_matches";
+        sqlx::query(query_str)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DbError::QueryFailed { 
+                query: query_str.to_string(),
+                err: e.to_string(), 
+        })?;
+        insert_with(matches, &mut *tx).await?;
+
+        tx.commit().await.map_err(|e| DbError::QueryFailed {
+            query: "COMMIT".to_string(),
+            err: e.to_string(),
+        })?;
+        Ok(())
+    }
+
+    async fn insert_with<'c>(matches: &[InDbProductMatch], executor: impl PgExecutor<'c>) -> Result<()> {
+        let query_str = r"
+            INSERT INTO product_matches(
+                store,
+                productkey,
+                matchstore,
+                matchproductkey,
+                relation,
+                confidence,
+                groupkey
+            ) 
+            SELECT * FROM UNNEST(
+                $1::VARCHAR(100)[], 
+                $2::VARCHAR(750)[],
+                $3::VARCHAR(100)[], 
+                $4::VARCHAR(750)[],
+                $5::VARCHAR(30)[],
+                $6::FLOAT8[],
+                $7::VARCHAR(900)[]
+            )";
+
+        let stores: Vec<&str> = matches.iter().map(|m| m.store.as_str()).collect();
+        let keys: Vec<&str> = matches.iter().map(|m| m.product_key.as_str()).collect();
+        let match_stores: Vec<&str> = matches.iter().map(|m| m.match_store.as_str()).collect();
+        let match_keys: Vec<&str> = matches.iter().map(|m| m.match_product_key.as_str()).collect();
+        let relations: Vec<&str> = matches.iter().map(|m| m.relation.as_str()).collect();
+        let confidences: Vec<f64> = matches.iter().map(|m| m.confidence).collect();
+        let group_keys: Vec<Option<&str>> = matches.iter().map(|m| m.group_key.as_deref()).collect();
+
+        sqlx::query_as::<_, ()>(query_str)
+            .bind(stores)
+            .bind(keys)
+            .bind(match_stores)
+            .bind(match_keys)
+            .bind(relations)
+            .bind(confidences)
+            .bind(group_keys)
+            .fetch_all(executor)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(())
+    }
+
+    pub async fn truncate(pool: &PgPool) -> Result<()> {
+        let query_str = "TRUNCATE TABLE product_matches";
+
+        sqlx::query(query_str)
+            .execute(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed { 
+                query: query_str.to_string(),
+                err: e.to_string(), 
+        })?;
+        Ok(())
+    }
+}
+
+pub mod scrape_anomalies {
+    use sqlx::PgPool;
+    use scrape_core::{Acknowledged, Anomaly, AnomalyKind, DbError};
+    use anyhow::Result;
+
+    pub async fn insert(anomalies: &[Anomaly], pool: &PgPool) -> Result<()>{
+        let query_str = r"
+            INSERT INTO scrape_anomalies(
+                store,
+                kind,
+                productkey,
+                category,
+                message
+            ) 
+            SELECT * FROM UNNEST(
+                $1::VARCHAR(100)[], 
+                $2::VARCHAR(30)[],
+                $3::VARCHAR(750)[],
+                $4::VARCHAR(500)[],
+                $5::TEXT[]
+            )";
+
+        let stores: Vec<&str> = anomalies.iter().map(|a| a.store.as_str()).collect();
+        let kinds: Vec<&str> = anomalies.iter().map(|a| a.kind.as_str()).collect();
+        let keys: Vec<Option<&str>> = anomalies.iter().map(|a| a.product_key.as_deref()).collect();
+        let categories: Vec<Option<&str>> = anomalies.iter().map(|a| a.category.as_deref()).collect();
+        let messages: Vec<&str> = anomalies.iter().map(|a| a.message.as_str()).collect();
+
+        sqlx::query_as::<_, ()>(query_str)
+            .bind(stores)
+            .bind(kinds)
+            .bind(keys)
+            .bind(categories)
+            .bind(messages)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(())
+    }
+
+    /// Anomalies of `store` that were acknowledged and haven't been released yet
+    pub async fn select_acknowledged(store: &str, pool: &PgPool) -> Result<Acknowledged> {
+        let query_str = r"
+            SELECT kind, productkey
+            FROM scrape_anomalies
+            WHERE store = $1 AND status = 'acknowledged'";
+
+        let rows = sqlx::query_as::<_, (String, Option<String>)>(query_str)
+            .bind(store)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+
+        let mut acknowledged = Acknowledged::default();
+        for (kind, key) in rows {
+            match (kind.as_str(), key) {
+                (kind, Some(key)) if kind == AnomalyKind::PriceChange.as_str() => { acknowledged.price_changes.insert(key); },
+                (kind, _) if kind == AnomalyKind::CatalogueDrop.as_str() => acknowledged.catalogue_drop = true,
+                _ => {},
+            };
+        }
+        Ok(acknowledged)
+    }
+
+    /// The acknowledged anomalies of `store` in `released` have been applied by a run and won't be applied
+    /// again. Open anomalies earlier runs reported for the same product are closed with them
+    pub async fn release(store: &str, released: &Acknowledged, pool: &PgPool) -> Result<()> {
+        let query_str = r"
+            UPDATE scrape_anomalies SET status = 'released'
+            WHERE store = $1 AND status IN ('acknowledged', 'open') AND (
+                (kind = $2 AND productkey = ANY($3)) OR (kind = $4 AND $5)
+            )";
+
+        let keys: Vec<&str> = released.price_changes.iter().map(String::as_str).collect();
+        sqlx::query(query_str)
+            .bind(store)
+            .bind(AnomalyKind::PriceChange.as_str())
+            .bind(keys)
+            .bind(AnomalyKind::CatalogueDrop.as_str())
+            .bind(released.catalogue_drop)
+            .execute(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(())
+    }
+
+    pub async fn truncate(pool: &PgPool) -> Result<()> {
+        let query_str = "TRUNCATE TABLE scrape_anomalies";
+
+        sqlx::query(query_str)
+            .execute(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed { 
+                query: query_str.to_string(),
+                err: e.to_string(), 
+        })?;
+        Ok(())
+    }
+}
+
+pub mod scrape_runs {
+    use chrono::{DateTime, TimeZone, Utc};
+    use sqlx::PgPool;
+    use scrape_core::DbError;
+    use anyhow::Result;
+
+    /// Start a run of `store`, products written from now on are part of it. Returns the id of the run.
+    /// `started_at` is when the scrape began, pages modified after it are left to the next run.
+    /// An incremental run only writes the products that changed, it isn't a baseline for the next run
+    pub async fn start(store: &str, started_at: DateTime<Utc>, incremental: bool, pool: &PgPool) -> Result<i64> {
+        let query_str = "INSERT INTO scrape_runs (store, startedat, incremental) VALUES ($1, TO_TIMESTAMP($2), $3) RETURNING id";
+
+        let (id,) = sqlx::query_as::<_, (i64,)>(query_str)
+            .bind(store)
+            .bind(started_at.timestamp())
+            .bind(incremental)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(id)
+    }
+
+    /// The run wrote all its products, the next run of the store compares with it
+    pub async fn finish(id: i64, pool: &PgPool) -> Result<()> {
+        let query_str = "UPDATE scrape_runs SET finishedat = NOW() WHERE id = $1";
+
+        sqlx::query(query_str)
+            .bind(id)
+            .execute(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(())
+    }
+
+    /// When the last finished run of `store` started, incremental or not
+    pub async fn last_finished(store: &str, pool: &PgPool) -> Result<Option<DateTime<Utc>>> {
+        let query_str = r"
+            SELECT EXTRACT(EPOCH FROM MAX(startedat)::TIMESTAMPTZ)::INT8
+            FROM scrape_runs
+            WHERE store = $1 AND finishedat IS NOT NULL";
+
+        let (seconds,) = sqlx::query_as::<_, (Option<i64>,)>(query_str)
+            .bind(store)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| DbError::QueryFailed{ 
+                query: query_str.to_string(),
+                err: e.to_string()
+            })?;
+        Ok(seconds.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()))
+    }
+
+    pub async fn truncate(pool: &PgPool) -> Result<()> {
+        let query_str = "TRUNCATE TABLE scrape_runs";
+
         sqlx::query(query_str)
             .execute(pool)
             .await